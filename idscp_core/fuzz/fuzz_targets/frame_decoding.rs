@@ -0,0 +1,26 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+use idscp_default_drivers::secure_channels::openssl::read_frame;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+// the input is treated as the byte stream received via the TLS connection
+fuzz_target!(|data: &[u8]| {
+    let mut stream = Cursor::new(data);
+    while let Ok(frame) = read_frame(&mut stream) {
+        idscp_core::fuzzing::decode_message(&frame);
+    }
+});
@@ -0,0 +1,50 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Writes the seed corpus for all fuzz targets to corpus/<target>/, based on messages that are
+// created via the idscp message factory.
+
+use idscp_core::fuzzing::{seed_fsm_sequences, seed_messages};
+use std::fs;
+use std::path::Path;
+
+fn write_corpus(target: &str, seeds: Vec<(&'static str, Vec<u8>)>) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("corpus")
+        .join(target);
+    fs::create_dir_all(&dir).expect("Cannot create corpus directory");
+    for (name, seed) in seeds {
+        fs::write(dir.join(name), seed).expect("Cannot write seed");
+    }
+    println!("Created seed corpus at {}", dir.display());
+}
+
+fn main() {
+    write_corpus("message_decoding", seed_messages());
+
+    // frames are prefixed by their length, a stream of all messages is added as additional seed
+    let mut frames: Vec<(&'static str, Vec<u8>)> = seed_messages()
+        .into_iter()
+        .map(|(name, msg)| {
+            let mut frame = (msg.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(&msg);
+            (name, frame)
+        })
+        .collect();
+    let stream = frames.iter().flat_map(|(_, f)| f.clone()).collect();
+    frames.push(("stream", stream));
+    write_corpus("frame_decoding", frames);
+
+    write_corpus("fsm_sequence", seed_fsm_sequences());
+}
@@ -12,27 +12,99 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::drivers::daps_driver::DapsDriver;
+use crate::api::idscp_fsm_observer::FsmObserver;
+use crate::drivers::daps_driver::{DapsDriver, TokenVerifier};
 use crate::drivers::rat_driver::RatRegistry;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+// How data is handled while an already trusted peer is re-attested, i.e. in the states
+// WaitForRat, WaitForRatProver and WaitForRatVerifier after the handshake. Data is never exchanged
+// before the initial handshake has been completed or while a DAT is being renewed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReRatDataPolicy {
+    // outbound data is rejected with ConnectionTemporaryNotAvailable, inbound data is not
+    // acknowledged and therefore repeated by the peer after re-attestation
+    Block,
+    // data is sent and received as in the established state
+    Continue,
+    // up to max_messages outbound messages are queued and sent after successful re-attestation,
+    // if the connection is closed before they are reported as IdscpEvent::MessageDiscarded.
+    // Inbound data is held back and only acknowledged once it is delivered after successful
+    // re-attestation, otherwise the peer never sees it acknowledged
+    Buffer { max_messages: usize },
+}
+
+// at most max_requests requests of the peer within any window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    pub max_requests: usize,
+    pub window: Duration,
+}
+
 #[derive(Clone)]
 pub struct AttestationConfig {
     // TODO integrate this with IDSCP2Configuration?
     // TODO: rename to "supported_provers" and "supported verifiers"
     pub supported_attestation_suite: Vec<String>,
     pub expected_attestation_suite: Vec<String>,
+    // interval of the periodic re-attestation of the peer
     pub rat_timeout: Duration,
+    // each interval is randomized by up to +/- rat_timeout_jitter, such that connections that were
+    // opened together do not re-attest together
+    pub rat_timeout_jitter: Duration,
+    // intervals for specific verifier mechanisms, these override rat_timeout
+    pub rat_timeouts_per_mechanism: HashMap<String, Duration>,
+    // intervals for specific peers by the common name of their certificate, these override the
+    // intervals per mechanism
+    pub rat_timeouts_per_peer: HashMap<String, Duration>,
+    // re-attest the peer when it renews its DAT after expiry
+    pub re_rat_on_dat_renewal: bool,
+    // own DAT is renewed and pushed to the peer this margin before it expires, None to renew it
+    // only when the peer reports it as expired
+    pub dat_renewal_margin: Option<Duration>,
+    pub re_rat_data_policy: ReRatDataPolicy,
+    // send the failure reason of the rat drivers to the peer within IdscpClose. This might
+    // disclose details of the own platform or verification policy
+    pub disclose_rat_failure_reason: bool,
+    // limits for re-attestations and DAT renewals requested by the peer via IdscpReRat and
+    // IdscpDatExpired, the connection is closed if the peer exceeds them. None for no limit
+    pub re_rat_rate_limit: Option<RateLimit>,
+    pub dat_expired_rate_limit: Option<RateLimit>,
+}
+
+impl Default for AttestationConfig {
+    fn default() -> Self {
+        AttestationConfig {
+            supported_attestation_suite: vec![],
+            expected_attestation_suite: vec![],
+            rat_timeout: Duration::from_secs(24 * 60 * 60),
+            rat_timeout_jitter: Duration::from_secs(0),
+            rat_timeouts_per_mechanism: HashMap::new(),
+            rat_timeouts_per_peer: HashMap::new(),
+            re_rat_on_dat_renewal: true,
+            dat_renewal_margin: None,
+            re_rat_data_policy: ReRatDataPolicy::Block,
+            disclose_rat_failure_reason: false,
+            re_rat_rate_limit: None,
+            dat_expired_rate_limit: None,
+        }
+    }
 }
 
 #[derive(Clone)] //TODO check if the derived clone functionality is exactly what we want
 pub struct Idscp2Configuration {
     pub rat_config: AttestationConfig,
+    // issues the own DAT and verifies the DATs of peers, unless dat_verifier is set
     pub daps: Arc<dyn DapsDriver + Send + Sync>,
+    // verifies the DATs of peers instead of the daps, e.g. a FederatedVerifier
+    pub dat_verifier: Option<Arc<dyn TokenVerifier + Send + Sync>>,
     pub prover_registry: RatRegistry,
     pub verifier_registry: RatRegistry,
     pub handshake_timeout: Duration,
     pub ack_timeout: Duration,
+    // notified about every fsm transition of the connections, e.g. TracingFsmObserver
+    pub fsm_observer: Option<Arc<dyn FsmObserver + Send + Sync>>,
 }
@@ -13,121 +13,66 @@
 // limitations under the License.
 
 use super::IdscpError;
-use crate::fsm::{FiniteStateMachine, FsmError, UserEvent};
-use std::sync::mpsc::{channel, Iter, Receiver, RecvTimeoutError, Sender};
+use crate::drivers::daps_driver::DatClaims;
+use crate::drivers::rat_driver::RatResult;
+use crate::fsm::event_loop::FsmHandle;
+use crate::fsm::{FsmError, UserEvent};
+use std::sync::mpsc::{Iter, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 
 pub enum IdscpEvent {
     Message(Vec<u8>), // TODO shouldn't this be &[u8] to avoid cloning?
+    // data that was buffered during re-attestation (see ReRatDataPolicy::Buffer) and has not been
+    // sent, since the connection was closed before. It is reported before ConnectionClosed
+    MessageDiscarded(Vec<u8>),
     ConnectionClosed,
 }
 
-pub struct Idscp2Connection {
-    pub(crate) inner: Arc<Mutex<InnerIdscp2connection>>,
-    pub(crate) incoming_message_rx: Receiver<IdscpEvent>,
+// attestation that closed the connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum RatFailure {
+    // the own prover could not attest this connector to the peer
+    Prover(String),
+    // the verifier rejected the attestation of the peer
+    Verifier(String),
 }
 
-impl Idscp2Connection {
-    //toDo make msg &[u8] to avoid cloning in FSM and inner connection
-    pub fn blocking_send(
-        &self,
-        msg: Vec<u8>,
-        timeout: Duration,
-        retry_interval: Option<Duration>,
-    ) -> Result<(), IdscpError> {
-        let guard = self.inner.lock().unwrap();
-        let inner_connection = &*guard;
-        inner_connection.blocking_send(msg, timeout, retry_interval)
-    }
-
-    pub fn close(&mut self) -> Result<(), IdscpError> {
-        let guard = self.inner.lock().unwrap();
-        let inner_connection = &*guard;
-        inner_connection.close()
-    }
-
-    pub fn is_connected(&self) -> bool {
-        let guard = self.inner.lock().unwrap();
-        let inner_connection = &*guard;
-        inner_connection.is_connected()
-    }
-
-    pub fn incoming_messages(&self) -> Iter<IdscpEvent> {
-        self.incoming_message_rx.iter()
-    }
-
-    pub fn recv_incoming_msg_with_timeout(
-        &self,
-        timeout: Duration,
-    ) -> Result<IdscpEvent, RecvTimeoutError> {
-        self.incoming_message_rx.recv_timeout(timeout)
-    }
-
-    pub fn repeat_rat(&self) -> Result<(), IdscpError> {
-        let guard = self.inner.lock().unwrap();
-        let inner_connection = &*guard;
-        inner_connection.repeat_rat()
-    }
+// results of the attestations of a connection as reported by the rat drivers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttestationReport {
+    // last successful verification of the peer
+    pub peer: Option<RatResult>,
+    // last successful attestation of this connector to the peer
+    pub own: Option<RatResult>,
+    pub failure: Option<RatFailure>,
 }
 
-pub(crate) struct InnerIdscp2connection {
-    fsm: Arc<Mutex<FiniteStateMachine>>,
-    incoming_msg_tx: Sender<IdscpEvent>,
+// what the fsm learned about the security of a connection. It is written by the fsm and read by
+// the Idscp2Connection, and remains available after the connection was closed
+#[derive(Default)]
+pub(crate) struct SecurityReport {
+    // claims of the last verified dat of the peer
+    pub(crate) peer_dat_claims: Option<DatClaims>,
+    pub(crate) attestation: AttestationReport,
 }
 
-impl InnerIdscp2connection {
-    pub fn new(
-        fsm: Arc<Mutex<FiniteStateMachine>>,
-    ) -> (InnerIdscp2connection, Receiver<IdscpEvent>) {
-        let (incoming_msg_tx, incoming_msg_rx) = channel();
-        let inner = InnerIdscp2connection {
-            fsm,
-            incoming_msg_tx,
-        };
-
-        (inner, incoming_msg_rx)
-    }
-
-    fn close(&self) -> Result<(), IdscpError> {
-        //terminate fsm
-        log::debug!("closing IDSCP connection");
-
-        let mut guard = match self.fsm.lock() {
-            Err(e) => {
-                log::error!("Cannot access fsm {}", e);
-                return Err(IdscpError::ConnectionNotAccessible);
-            }
-            Ok(guard) => guard,
-        };
-
-        // check if fsm is still active
-        if guard.is_closed() {
-            return Ok(());
-        }
-
-        // ignore result, UserEvent::Stop will always succeed or Fsm is already closed
-        match (*guard).feed_user_event(UserEvent::Stop) {
-            Ok(_) => Ok(()),
-            Err(e) => match e {
-                FsmError::FsmNotStarted => Err(IdscpError::ConnectionNotStarted),
-                _ => Ok(()),
-            },
-        }
-    }
+pub struct Idscp2Connection {
+    pub(crate) id: u64,
+    pub(crate) fsm: FsmHandle,
+    pub(crate) incoming_message_rx: Receiver<IdscpEvent>,
+    pub(crate) security: Arc<Mutex<SecurityReport>>, // shared with the fsm
+}
 
-    fn is_connected(&self) -> bool {
-        match self.fsm.lock() {
-            Err(e) => {
-                log::error!("Cannot access fsm {}", e);
-                false
-            }
-            Ok(guard) => (*guard).is_connected(),
-        }
+impl Idscp2Connection {
+    // id of the connection, which is also reported to fsm observers
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
-    fn blocking_send(
+    //toDo make msg &[u8] to avoid cloning in FSM
+    pub fn blocking_send(
         &self,
         msg: Vec<u8>,
         timeout: Duration,
@@ -136,30 +81,18 @@ impl InnerIdscp2connection {
         log::debug!("Send Idscp data");
         let start_time = std::time::Instant::now();
 
-        // TODO: if no retry_interval is specified:
-        // at the end of the loop, the fsm-lock is released and almost immediately re-aquired in
-        // the next loop iteration. Could there be an issue with Mutex-Unfairness?:
-        // https://stackoverflow.com/questions/56924866/why-do-rust-mutexes-not-seem-to-give-the-lock-to-the-thread-that-wanted-to-lock
         loop {
             let now = std::time::Instant::now();
             if now >= start_time + timeout {
                 return Err(IdscpError::ConnectionTemporaryNotAvailable);
             }
-            let mut guard = match self.fsm.lock() {
-                Err(e) => {
-                    log::error!("Cannot access fsm {}", e);
-                    return Err(IdscpError::ConnectionNotAccessible);
-                }
-                Ok(guard) => guard,
-            };
-
-            match (*guard).feed_user_event(UserEvent::Data(msg.clone())) {
+
+            match self.fsm.feed_user_event(UserEvent::Data(msg.clone())) {
                 Ok(()) => return Ok(()),
                 Err(e) => match e {
                     FsmError::WouldBlock => {
                         /* wait and repeat */
                         if let Some(val) = retry_interval {
-                            drop(guard); // release lock before sleeping to let other threads access the fsm
                             sleep(val);
                         }
                         continue;
@@ -176,18 +109,59 @@ impl InnerIdscp2connection {
         }
     }
 
-    fn repeat_rat(&self) -> Result<(), IdscpError> {
-        log::debug!("triggering re-attestation");
+    pub fn close(&mut self) -> Result<(), IdscpError> {
+        //terminate fsm
+        log::debug!("closing IDSCP connection");
 
-        let mut guard = match self.fsm.lock() {
-            Err(e) => {
-                log::error!("Cannot access fsm {}", e);
-                return Err(IdscpError::ConnectionNotAccessible);
-            }
-            Ok(guard) => guard,
-        };
+        // ignore result, UserEvent::Stop will always succeed or Fsm is already closed
+        match self.fsm.feed_user_event(UserEvent::Stop) {
+            Ok(_) => Ok(()),
+            Err(e) => match e {
+                FsmError::FsmNotStarted => Err(IdscpError::ConnectionNotStarted),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.fsm.is_connected()
+    }
 
-        match (*guard).feed_user_event(UserEvent::RepeatRat) {
+    // verified claims of the peer's DAT, e.g. its connector identity and security profile.
+    // They are updated whenever the peer renews its DAT and remain available after the
+    // connection was closed, None if the handshake did not get that far
+    pub fn peer_dat_claims(&self) -> Option<DatClaims> {
+        self.security.lock().unwrap().peer_dat_claims.clone()
+    }
+
+    // remains available after the connection was closed, e.g. to learn why an attestation failed
+    pub fn attestation_report(&self) -> AttestationReport {
+        self.security.lock().unwrap().attestation.clone()
+    }
+
+    pub fn incoming_messages(&self) -> Iter<IdscpEvent> {
+        self.incoming_message_rx.iter()
+    }
+
+    pub fn recv_incoming_msg_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<IdscpEvent, RecvTimeoutError> {
+        self.incoming_message_rx.recv_timeout(timeout)
+    }
+
+    pub fn repeat_rat(&self) -> Result<(), IdscpError> {
+        self.repeat_rat_with_cause("")
+    }
+
+    // the cause is sent to the peer within the IdscpReRat message
+    pub fn repeat_rat_with_cause(&self, cause: &str) -> Result<(), IdscpError> {
+        log::debug!("triggering re-attestation");
+
+        match self
+            .fsm
+            .feed_user_event(UserEvent::RepeatRat(cause.to_string()))
+        {
             Ok(()) => Ok(()),
             Err(e) => match e {
                 FsmError::FsmLocked => Err(IdscpError::ConnectionAborted(e)),
@@ -198,18 +172,6 @@ impl InnerIdscp2connection {
             },
         }
     }
-
-    pub(crate) fn on_close(&mut self) {
-        self.incoming_msg_tx
-            .send(IdscpEvent::ConnectionClosed)
-            .expect("receiving end should be alive in IDSCP2Connection");
-    }
-
-    pub(crate) fn on_message(&self, msg: Vec<u8>) {
-        self.incoming_msg_tx
-            .send(IdscpEvent::Message(msg))
-            .expect("receiving end should be alive in IDSCP2Connection");
-    }
 }
 
 impl Drop for Idscp2Connection {
@@ -0,0 +1,123 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::SystemTime;
+#[cfg(feature = "tracing")]
+use std::time::UNIX_EPOCH;
+
+// A single transition of the fsm of a connection. Events are only described by their kind, e.g.
+// "Sc(Data)" or "User(Data)", payloads like data, DATs and RAT messages are never included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsmTransition {
+    // id of the connection, see Idscp2Connection::id()
+    pub connection_id: u64,
+    pub old_state: &'static str,
+    pub event: &'static str,
+    pub new_state: &'static str,
+    pub timestamp: SystemTime,
+    // Err contains the error that was returned by the fsm
+    pub outcome: Result<(), String>,
+}
+
+// Misbehavior of the peer that caused the connection to be closed
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityEventKind {
+    ReRatRateLimitExceeded,
+    DatExpiredRateLimitExceeded,
+}
+
+impl SecurityEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecurityEventKind::ReRatRateLimitExceeded => "ReRatRateLimitExceeded",
+            SecurityEventKind::DatExpiredRateLimitExceeded => "DatExpiredRateLimitExceeded",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityEvent {
+    // id of the connection, see Idscp2Connection::id()
+    pub connection_id: u64,
+    pub kind: SecurityEventKind,
+    pub timestamp: SystemTime,
+}
+
+// Observer that is notified about every transition of the fsm. It is called on the event loop
+// thread of the connection, so it must neither block nor call into the connection.
+pub trait FsmObserver {
+    fn on_transition(&self, transition: &FsmTransition);
+
+    // called before the transition in which the connection is closed due to the security event
+    fn on_security_event(&self, _event: &SecurityEvent) {}
+}
+
+// Emits every transition as tracing event within a span of the connection. Transitions that change
+// the state are emitted at info level, all others at debug level. Security events are emitted at
+// warn level. Requires the tracing feature.
+#[cfg(feature = "tracing")]
+pub struct TracingFsmObserver {}
+
+#[cfg(feature = "tracing")]
+impl FsmObserver for TracingFsmObserver {
+    fn on_transition(&self, transition: &FsmTransition) {
+        let span = tracing::info_span!(
+            "idscp2_connection",
+            connection_id = transition.connection_id
+        );
+        let _enter = span.enter();
+
+        let timestamp = transition
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let error = match &transition.outcome {
+            Ok(()) => None,
+            Err(e) => Some(e.as_str()),
+        };
+
+        if transition.old_state != transition.new_state {
+            tracing::info!(
+                old_state = transition.old_state,
+                event = transition.event,
+                new_state = transition.new_state,
+                timestamp,
+                error,
+                "fsm transition"
+            );
+        } else {
+            tracing::debug!(
+                old_state = transition.old_state,
+                event = transition.event,
+                new_state = transition.new_state,
+                timestamp,
+                error,
+                "fsm transition"
+            );
+        }
+    }
+
+    fn on_security_event(&self, event: &SecurityEvent) {
+        let span = tracing::info_span!("idscp2_connection", connection_id = event.connection_id);
+        let _enter = span.enter();
+
+        let timestamp = event
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        tracing::warn!(kind = event.kind.name(), timestamp, "security event");
+    }
+}
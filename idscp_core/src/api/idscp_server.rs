@@ -15,10 +15,14 @@
 use crate::api::idscp_configuration::Idscp2Configuration;
 use crate::api::idscp_connection::Idscp2Connection;
 use crate::create_new_idscp2_connection;
+use crate::drivers::daps_driver::DatClaims;
 use crate::drivers::secure_channel::{SecureChannel, SecureChannelServer};
 use std::sync::mpsc::{channel, Iter, Receiver};
 use std::sync::{Arc, Mutex};
 
+// decides by the verified DAT claims of the peer whether a new connection is admitted
+pub type AdmissionCheck = Arc<dyn Fn(&DatClaims) -> bool + Send + Sync>;
+
 pub struct Idscp2Server<SCS>
 where
     SCS: SecureChannelServer + Send + Sync,
@@ -34,21 +38,47 @@ where
     SCS::SC: SecureChannel + Send + Sync,
 {
     pub fn listen(
+        secure_channel_server: SCS,
+        addr: SCS::AddrType,
+        idscp_config: &Idscp2Configuration,
+    ) -> Result<Idscp2Server<SCS>, &'static str> {
+        Self::start(secure_channel_server, addr, idscp_config, None)
+    }
+
+    // connections are only admitted if the verified DAT claims of the peer pass the admission
+    // check, otherwise they are closed right after the handshake
+    pub fn listen_with_admission(
+        secure_channel_server: SCS,
+        addr: SCS::AddrType,
+        idscp_config: &Idscp2Configuration,
+        admission: AdmissionCheck,
+    ) -> Result<Idscp2Server<SCS>, &'static str> {
+        Self::start(secure_channel_server, addr, idscp_config, Some(admission))
+    }
+
+    fn start(
         mut secure_channel_server: SCS,
         addr: SCS::AddrType,
         idscp_config: &Idscp2Configuration,
-    ) -> Result<Idscp2Server<SCS>, &'static str>
-    where
-        SCS: SecureChannelServer + Send + Sync,
-        SCS::SC: SecureChannel + Send + Sync,
-    {
+        admission: Option<AdmissionCheck>,
+    ) -> Result<Idscp2Server<SCS>, &'static str> {
         log::info!("Starting new Idscp2 server");
         let config = idscp_config.clone();
         let (incoming_connection_tx, incoming_connection_rx) = channel();
         secure_channel_server.listen(
             addr,
             Arc::new(Mutex::new(Box::new(move |sc| {
-                let connection = create_new_idscp2_connection(sc, &config).unwrap();
+                let mut connection = create_new_idscp2_connection(sc, &config).unwrap();
+                let admitted = admission.as_ref().map_or(true, |admission| {
+                    connection
+                        .peer_dat_claims()
+                        .map_or(false, |claims| admission(&claims))
+                });
+                if !admitted {
+                    log::warn!("Peer of connection {} was not admitted", connection.id());
+                    let _ = connection.close();
+                    return;
+                }
                 incoming_connection_tx
                     .send(connection)
                     .expect("receiving end should be alive in IDSCP2Server");
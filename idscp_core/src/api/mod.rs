@@ -14,6 +14,7 @@
 
 pub mod idscp_configuration;
 pub mod idscp_connection;
+pub mod idscp_fsm_observer;
 pub mod idscp_server;
 
 use crate::fsm::FsmError;
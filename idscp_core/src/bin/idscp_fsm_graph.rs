@@ -0,0 +1,66 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Prints the transition graph of the fsm as observed by exhaustive exploration, to compare the
+// implementation against the state diagram of the specification.
+
+use idscp_core::introspection::{render_dot, render_mermaid, transition_graph};
+
+const USAGE: &str = "usage: idscp_fsm_graph [--format dot|mermaid] [--all] [--output <file>]
+    --format   output format, dot (default) or mermaid
+    --all      include events that are ignored by a state
+    --output   write the graph to a file instead of stdout";
+
+fn main() {
+    let mut format = "dot".to_string();
+    let mut include_ignored = false;
+    let mut output = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().unwrap_or_default(),
+            "--all" => include_ignored = true,
+            "--output" => output = args.next(),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("unknown argument '{}'\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let render = match format.as_str() {
+        "dot" => render_dot,
+        "mermaid" => render_mermaid,
+        _ => {
+            eprintln!("unknown format '{}'\n{}", format, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let graph = render(&transition_graph(include_ignored));
+    match output {
+        None => print!("{}", graph),
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, graph) {
+                eprintln!("Cannot write {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -15,20 +15,64 @@
 // public IDSCP2 API
 // Daps Driver for accessing and verifying DynamicAttributeToken
 
-use std::time::Duration;
-
-pub trait DapsDriver {
-    //type for security requirement validation in the verify token method
-    //toDo type SecurityReq;
-
-    //get token as string
-    fn get_token(&self) -> String;
-
-    //verify token and receive validity_period in seconds in an Option<u64>
-    //None if token is not valid
-    fn verify_token(
-        &self,
-        token: &String,
-        //toDo security_requirements: Option<Self::SecurityReq>,
-    ) -> Option<Duration>; //TODO return std::Duration
+use openssl::x509::X509;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+// own DAT of the connector as issued by the DAPS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dat {
+    pub token: String,
+    // the fsm renews the dat before it expires, None if the expiry is unknown
+    pub expires_at: Option<SystemTime>,
+}
+
+// claims of a verified DAT. Timestamps are seconds since the unix epoch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatClaims {
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    pub audience: Vec<String>,
+    pub issued_at: Option<i64>,
+    pub not_before: Option<i64>,
+    pub expires_at: Option<i64>,
+    pub security_profile: Option<String>,
+    pub referring_connector: Option<String>,
+    pub transport_certs_sha256: Vec<String>,
+    // all claims as JSON document, including the ones not parsed above
+    pub json: String,
+}
+
+// result of a successful DAT verification
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedDat {
+    // remaining validity of the DAT, the peer has to renew it afterwards
+    pub validity: Duration,
+    pub claims: DatClaims,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DapsError {
+    #[error("DAT is invalid: {0}")]
+    InvalidDat(String),
+    #[error("DAT does not meet the security requirements: {0}")]
+    SecurityRequirementsNotMet(String),
+    #[error("DAT is not bound to the TLS certificate of the peer")]
+    CertificateBindingMismatch,
+    #[error("Cannot get DAT from DAPS: {0}")]
+    Unavailable(String),
+}
+
+// verifies the DATs of peers. Security requirements on the peer are part of the configuration of
+// the verifier. The token must be bound to the TLS certificate of the peer
+pub trait TokenVerifier {
+    fn verify_token(&self, token: &str, peer_cert: &X509) -> Result<VerifiedDat, DapsError>;
+}
+
+// the DAPS driver verifies the DATs of peers as well, unless a separate TokenVerifier is
+// configured because the peers are registered at other DAPS instances than the own connector
+pub trait DapsDriver: TokenVerifier {
+    //get own token. The call may block for contacting the DAPS, so the fsm never calls it on
+    //its event loop thread
+    fn get_token(&self) -> Result<Dat, DapsError>;
 }
@@ -12,15 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use openssl::hash::{hash, MessageDigest};
 use openssl::x509::X509;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 
+// outcome of a successful attestation as reported by the rat driver
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RatResult {
+    // what was attested, e.g. verified PCR values or the identity of the platform
+    pub claims: BTreeMap<String, String>,
+    // evidence the claims were derived from, e.g. a TPM quote
+    pub evidence: Vec<u8>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum RatIcm {
-    OK,
-    Failed,
+    OK(RatResult),
+    Failed(String), // reason of the failure
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -29,8 +39,31 @@ pub enum RatMessage {
     RawData(Vec<u8>),
 }
 
+// context of a single run of a rat driver
+#[derive(Debug, Clone)]
+pub struct RatContext {
+    pub peer_cert: X509,
+    // see SecureChannel::get_channel_binding, None if the secure channel does not provide one
+    pub channel_binding: Option<Vec<u8>>,
+    // fresh random value for each run of the driver, e.g. the challenge a verifier sends
+    pub nonce: Vec<u8>,
+}
+
+impl RatContext {
+    // sha256 over the given nonce and the channel binding, to be included in the evidence
+    pub fn bind(&self, nonce: &[u8]) -> Vec<u8> {
+        let mut data = nonce.to_vec();
+        if let Some(binding) = &self.channel_binding {
+            data.extend_from_slice(binding);
+        }
+        hash(MessageDigest::sha256(), &data)
+            .map(|digest| digest.to_vec())
+            .expect("sha256 should be available")
+    }
+}
+
 pub trait RatDriver {
-    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, cert: X509);
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext);
     fn get_id(&self) -> &'static str;
 }
 
@@ -74,7 +107,9 @@ impl RatRegistry {
 
 #[cfg(test)]
 mod tests {
-    use crate::drivers::rat_driver::{RatDriver, RatIcm, RatMessage, RatRegistry};
+    use crate::drivers::rat_driver::{
+        RatContext, RatDriver, RatIcm, RatMessage, RatRegistry, RatResult,
+    };
     use openssl::x509::X509;
     use std::path::PathBuf;
     use std::sync::mpsc::{Receiver, Sender};
@@ -85,8 +120,11 @@ mod tests {
 
     pub struct TestDriver {}
     impl RatDriver for TestDriver {
-        fn execute(&self, tx: Sender<RatMessage>, _rx: Receiver<RatMessage>, _peer_cert: X509) {
-            if tx.send(RatMessage::ControlMessage(RatIcm::OK)).is_err() {
+        fn execute(&self, tx: Sender<RatMessage>, _rx: Receiver<RatMessage>, _ctx: RatContext) {
+            if tx
+                .send(RatMessage::ControlMessage(RatIcm::OK(RatResult::default())))
+                .is_err()
+            {
                 log::warn!("Prover was terminated from fsm");
             }
         }
@@ -135,10 +173,34 @@ mod tests {
 
         //spawn thread and execute verifier driver
         thread::spawn(move || {
-            driver_clone.execute(tx, rx, test_cert);
+            let ctx = RatContext {
+                peer_cert: test_cert,
+                channel_binding: None,
+                nonce: vec![],
+            };
+            driver_clone.execute(tx, rx, ctx);
         });
 
         drop(tx_);
         drop(rx_);
     }
+
+    #[test]
+    fn test_rat_context_bind() {
+        let ctx = |binding: Option<&[u8]>| RatContext {
+            peer_cert: get_test_cert(),
+            channel_binding: binding.map(|b| b.to_vec()),
+            nonce: vec![1, 2, 3],
+        };
+
+        // the bound nonce differs between channels
+        let unbound = ctx(None).bind(&[1, 2, 3]);
+        assert_eq!(unbound.len(), 32);
+        assert_eq!(ctx(None).bind(&[1, 2, 3]), unbound);
+        assert_ne!(ctx(Some(b"channel-1")).bind(&[1, 2, 3]), unbound);
+        assert_ne!(
+            ctx(Some(b"channel-1")).bind(&[1, 2, 3]),
+            ctx(Some(b"channel-2")).bind(&[1, 2, 3])
+        );
+    }
 }
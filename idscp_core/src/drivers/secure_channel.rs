@@ -20,6 +20,11 @@ pub trait SecureChannel: Send + 'static {
     fn recv_msg(&self) -> Result<Vec<u8>, std::io::Error>;
     fn terminate(&self);
     fn get_peer_certificate(&self) -> X509;
+    // value that is unique to this channel and known to both peers, e.g. a TLS exporter. RAT
+    // drivers bind their evidence to it, such that it cannot be relayed from another channel
+    fn get_channel_binding(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 pub trait SecureChannelClient {
@@ -0,0 +1,80 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::event_loop::LoopMessage;
+use super::FsmEvent;
+use crate::drivers::daps_driver::DapsDriver;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+
+// Daps Interface
+// Requesting a dat from the DAPS can take seconds or fail, so the own dat is requested on a
+// worker thread and delivered to the event loop as FsmEvent::FromDaps. Only the dat of the
+// handshake is requested before the event loop is started.
+pub(super) struct DapsInterface {
+    daps_driver: Arc<dyn DapsDriver + Send + Sync>,
+    event_tx: Sender<LoopMessage>,
+    pending: Option<Arc<AtomicBool>>, // cancel flag of the pending request
+}
+
+impl DapsInterface {
+    pub(super) fn create(
+        daps_driver: Arc<dyn DapsDriver + Send + Sync>,
+        event_tx: Sender<LoopMessage>,
+    ) -> DapsInterface {
+        DapsInterface {
+            daps_driver,
+            event_tx,
+            pending: None,
+        }
+    }
+
+    // requests while another request is pending are merged into the pending one
+    pub(super) fn request_token(&mut self) {
+        if self.pending.is_some() {
+            log::debug!("Dat request is already pending");
+            return;
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.pending = Some(Arc::clone(&cancelled));
+        let daps_driver = Arc::clone(&self.daps_driver);
+        let event_tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let result = daps_driver.get_token();
+            let _ = event_tx.send(LoopMessage::Event(
+                FsmEvent::FromDaps(result),
+                Some(cancelled),
+            ));
+        });
+    }
+
+    // the pending request was answered
+    pub(super) fn on_token(&mut self) {
+        self.pending = None;
+    }
+
+    pub(super) fn cancel(&mut self) {
+        if let Some(cancelled) = self.pending.take() {
+            cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg(any(test, feature = "introspection"))]
+    pub(super) fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
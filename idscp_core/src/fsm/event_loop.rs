@@ -0,0 +1,125 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{FiniteStateMachine, FsmError, FsmEvent, UserEvent};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+// Event Loop
+// Every connection is driven by a single thread that owns the FiniteStateMachine. The secure
+// channel listener, the rat driver listeners, the timers and the user hand their events over
+// to this thread via one channel, so the fsm is never shared between threads.
+pub(super) enum LoopMessage {
+    // event of the secure channel, a rat driver or a timer. Events of a source that was
+    // cancelled by the fsm while the event was queued are dropped
+    Event(FsmEvent, Option<Arc<AtomicBool>>),
+    // event of the user, the result is sent back to the waiting user thread
+    User(UserEvent, Sender<Result<(), FsmError>>),
+    IsConnected(Sender<bool>),
+}
+
+// receiving end of the event channel of a fsm
+pub(crate) struct EventQueue {
+    rx: Receiver<LoopMessage>,
+}
+
+impl EventQueue {
+    pub(super) fn create() -> (Sender<LoopMessage>, EventQueue) {
+        let (tx, rx) = channel();
+        (tx, EventQueue { rx })
+    }
+
+    // process all events that are queued right now on the current thread. This is used for
+    // driving a fsm without an event loop thread, e.g. when replaying recorded sessions
+    pub(crate) fn process_pending(&self, fsm: &mut FiniteStateMachine) {
+        while let Ok(msg) = self.rx.try_recv() {
+            handle_message(fsm, msg);
+        }
+    }
+}
+
+// handle to the event loop of a connection, used by the upper layer
+pub(crate) struct FsmHandle {
+    tx: Sender<LoopMessage>,
+}
+
+impl FsmHandle {
+    pub(crate) fn feed_user_event(&self, event: UserEvent) -> Result<(), FsmError> {
+        let (reply_tx, reply_rx) = channel();
+        if self.tx.send(LoopMessage::User(event, reply_tx)).is_err() {
+            // event loop has terminated, the fsm is locked forever
+            return Err(FsmError::FsmLocked);
+        }
+        match reply_rx.recv() {
+            Err(_) => Err(FsmError::FsmLocked),
+            Ok(res) => res,
+        }
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        let (reply_tx, reply_rx) = channel();
+        if self.tx.send(LoopMessage::IsConnected(reply_tx)).is_err() {
+            return false;
+        }
+        reply_rx.recv().unwrap_or(false)
+    }
+}
+
+// move the fsm into its own event loop thread, which terminates when the fsm is locked forever
+pub(crate) fn spawn(
+    mut fsm: FiniteStateMachine,
+    queue: EventQueue,
+) -> Result<FsmHandle, std::io::Error> {
+    let handle = FsmHandle {
+        tx: fsm.event_tx.clone(),
+    };
+    let connection_id = fsm.connection_id();
+
+    thread::Builder::new()
+        .name(format!("idscp2-connection-{}", connection_id))
+        .spawn(move || {
+            while let Ok(msg) = queue.rx.recv() {
+                handle_message(&mut fsm, msg);
+                if fsm.is_locked() {
+                    break;
+                }
+            }
+            log::debug!("Event loop of connection {} terminated", connection_id);
+        })?;
+
+    Ok(handle)
+}
+
+fn handle_message(fsm: &mut FiniteStateMachine, msg: LoopMessage) {
+    match msg {
+        LoopMessage::Event(event, cancelled) => {
+            if let Some(c) = cancelled {
+                if c.load(Ordering::SeqCst) {
+                    log::debug!("Drop event {} of cancelled source", event.kind());
+                    return;
+                }
+            }
+            let _ = fsm.process_event(event);
+        }
+        LoopMessage::User(event, reply) => {
+            // the user might have stopped waiting, ignore it
+            let _ = reply.send(fsm.feed_user_event(event));
+        }
+        LoopMessage::IsConnected(reply) => {
+            let _ = reply.send(fsm.is_connected());
+        }
+    }
+}
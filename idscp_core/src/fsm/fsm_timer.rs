@@ -12,15 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{FiniteStateMachine, FsmEvent};
+use super::event_loop::LoopMessage;
+use super::FsmEvent;
 use cancellable_timer::*;
 use std::marker::PhantomData;
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub(super) struct HandshakeTimer;
 pub(super) struct RatTimer;
 pub(super) struct DatTimer;
+pub(super) struct DatRenewalTimer;
 pub(super) struct AckTimer;
 
 pub(super) trait TimerImpl {
@@ -45,6 +49,12 @@ impl TimerImpl for DatTimer {
     }
 }
 
+impl TimerImpl for DatRenewalTimer {
+    fn create_event() -> FsmEvent {
+        FsmEvent::DatRenewalTimeout
+    }
+}
+
 impl TimerImpl for AckTimer {
     fn create_event() -> FsmEvent {
         FsmEvent::AckTimeout
@@ -57,17 +67,13 @@ pub(super) struct StaticTimer<T: 'static + TimerImpl + Send + Sync> {
 }
 
 impl<T: 'static + TimerImpl + Send + Sync> StaticTimer<T> {
-    pub(super) fn new(duration: Duration) -> StaticTimer<T> {
+    pub(super) fn new(duration: Duration, event_tx: Sender<LoopMessage>) -> StaticTimer<T> {
         StaticTimer {
             duration,
-            inner: FsmTimer::new(),
+            inner: FsmTimer::new(event_tx),
         }
     }
 
-    pub(super) fn set_fsm(&mut self, fsm: Weak<Mutex<FiniteStateMachine>>) {
-        self.inner.fsm = fsm;
-    }
-
     pub(super) fn cancel(&mut self) {
         self.inner.cancel();
     }
@@ -75,6 +81,11 @@ impl<T: 'static + TimerImpl + Send + Sync> StaticTimer<T> {
     pub(super) fn start(&mut self) {
         self.inner.start(self.duration);
     }
+
+    #[cfg(any(test, feature = "introspection"))]
+    pub(super) fn is_active(&self) -> bool {
+        self.inner.content.is_some()
+    }
 }
 
 pub(super) struct DynamicTimer<T: 'static + TimerImpl + Send + Sync> {
@@ -82,16 +93,12 @@ pub(super) struct DynamicTimer<T: 'static + TimerImpl + Send + Sync> {
 }
 
 impl<T: 'static + TimerImpl + Send + Sync> DynamicTimer<T> {
-    pub(super) fn new() -> DynamicTimer<T> {
+    pub(super) fn new(event_tx: Sender<LoopMessage>) -> DynamicTimer<T> {
         DynamicTimer {
-            inner: FsmTimer::new(),
+            inner: FsmTimer::new(event_tx),
         }
     }
 
-    pub(super) fn set_fsm(&mut self, fsm: Weak<Mutex<FiniteStateMachine>>) {
-        self.inner.fsm = fsm;
-    }
-
     pub(super) fn cancel(&mut self) {
         self.inner.cancel();
     }
@@ -99,24 +106,29 @@ impl<T: 'static + TimerImpl + Send + Sync> DynamicTimer<T> {
     pub(super) fn start(&mut self, duration: Duration) {
         self.inner.start(duration);
     }
+
+    #[cfg(any(test, feature = "introspection"))]
+    pub(super) fn is_active(&self) -> bool {
+        self.inner.content.is_some()
+    }
 }
 
 struct Content {
     canceller: Canceller,
-    cancelled: Arc<Mutex<bool>>,
+    cancelled: Arc<AtomicBool>,
 }
 
 struct FsmTimer<T: 'static + TimerImpl + Send + Sync> {
     content: Option<Content>,
-    fsm: Weak<Mutex<FiniteStateMachine>>,
+    event_tx: Sender<LoopMessage>,
     phantom: PhantomData<T>,
 }
 
 impl<T: 'static + TimerImpl + Send + Sync> FsmTimer<T> {
-    fn new() -> FsmTimer<T> {
+    fn new(event_tx: Sender<LoopMessage>) -> FsmTimer<T> {
         FsmTimer {
             content: None,
-            fsm: Weak::new(),
+            event_tx,
             phantom: PhantomData,
         }
     }
@@ -125,10 +137,8 @@ impl<T: 'static + TimerImpl + Send + Sync> FsmTimer<T> {
         match self.content.take() {
             None => {}
             Some(c) => {
-                {
-                    let mut b = c.cancelled.lock().unwrap();
-                    (*b) = true;
-                }
+                // a timeout event that is already queued is dropped by the event loop
+                c.cancelled.store(true, Ordering::SeqCst);
                 let _ = c.canceller.cancel();
                 drop(c);
             }
@@ -139,9 +149,9 @@ impl<T: 'static + TimerImpl + Send + Sync> FsmTimer<T> {
         // cancel old fsm_timer
         self.cancel();
 
-        let fsm_clone = Weak::clone(&self.fsm);
+        let event_tx = self.event_tx.clone();
 
-        let cancelled = Arc::new(Mutex::new(false));
+        let cancelled = Arc::new(AtomicBool::new(false));
         let cancelled_clone = Arc::clone(&cancelled);
 
         let (mut timer, canceller) = Timer::new2().unwrap();
@@ -150,29 +160,13 @@ impl<T: 'static + TimerImpl + Send + Sync> FsmTimer<T> {
         std::thread::spawn(move || {
             let _ = timer.sleep(duration);
 
-            // check if it was cancelled to avoid unnecessary fsm lock
-            if *cancelled_clone.lock().unwrap() {
+            // check if it was cancelled to avoid an unnecessary event
+            if cancelled_clone.load(Ordering::SeqCst) {
                 return;
             }
 
-            // get fsm lock
-            match fsm_clone.upgrade() {
-                None => {
-                    log::error!("Cannot upgrade fsm");
-                }
-                Some(fsm) => match fsm.lock() {
-                    Err(e) => {
-                        log::error!("Cannot get fsm lock {}", e);
-                    }
-
-                    Ok(mut guard) => {
-                        // check once again within in mutex if it was cancelled
-                        if !(*cancelled_clone.lock().unwrap()) {
-                            let _ = (*guard).process_event(T::create_event());
-                        }
-                    }
-                },
-            };
+            // the event loop might have terminated already
+            let _ = event_tx.send(LoopMessage::Event(T::create_event(), Some(cancelled_clone)));
         });
 
         self.content = Some(Content {
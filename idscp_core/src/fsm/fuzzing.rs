@@ -0,0 +1,259 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Entry points for the fuzz targets in idscp_core/fuzz. The fsm is built with stub drivers that
+// never produce events on their own, such that each run only depends on the fuzzer input.
+
+use super::test_support::{create_cert, dummy_registry, TestDaps, TestSc, TEST_RAT_ID};
+use super::*;
+use crate::drivers::daps_driver::DapsError;
+use crate::messages::idscp_message_factory::*;
+
+// operations of an fsm event sequence, each operation is encoded as a single byte. A peer message
+// is followed by a two byte big endian length and the encoded IdscpMessage.
+pub const OP_PEER_MESSAGE: u8 = 0;
+pub const OP_PROVER_OK: u8 = 1;
+pub const OP_PROVER_FAILED: u8 = 2;
+pub const OP_PROVER_DATA: u8 = 3;
+pub const OP_VERIFIER_OK: u8 = 4;
+pub const OP_VERIFIER_FAILED: u8 = 5;
+pub const OP_VERIFIER_DATA: u8 = 6;
+pub const OP_USER_DATA: u8 = 7;
+pub const OP_USER_REPEAT_RAT: u8 = 8;
+pub const OP_USER_STOP: u8 = 9;
+pub const OP_HANDSHAKE_TIMEOUT: u8 = 10;
+pub const OP_RAT_TIMEOUT: u8 = 11;
+pub const OP_DAT_TIMEOUT: u8 = 12;
+pub const OP_ACK_TIMEOUT: u8 = 13;
+pub const OP_SC_ERROR: u8 = 14;
+pub const OP_DAT_RENEWAL_TIMEOUT: u8 = 15;
+pub const OP_DAPS_DAT: u8 = 16;
+pub const OP_DAPS_ERROR: u8 = 17;
+const OP_COUNT: u8 = 18;
+
+const VALID_DAT: &[u8] = b"valid";
+
+// decode a message like the secure channel listener does it. Every decodable message must be
+// encodable and decodable again.
+pub fn decode_message(data: &[u8]) {
+    if let Ok(msg) = IdscpMessage::parse_from_bytes(data) {
+        let raw = msg
+            .write_to_bytes()
+            .expect("decoded message cannot be encoded");
+        IdscpMessage::parse_from_bytes(&raw).expect("re-encoded message cannot be decoded");
+
+        if let Some(body) = msg.message {
+            let _ = SecureChannelEvent::from(body);
+        }
+    }
+}
+
+// drive a sequence of events, encoded as described at the OP_* constants, into a fresh fsm that
+// has already started the handshake
+pub fn run_fsm_sequence(data: &[u8]) {
+    let daps = TestDaps {
+        validity: Duration::from_secs(3600),
+    };
+    let sc = Arc::new(TestSc::new(create_cert()));
+    // timer and driver events are not processed, they are injected by the input instead
+    let (mut fsm, _queue) = FiniteStateMachine::create(
+        sc,
+        dummy_registry(),
+        dummy_registry(),
+        Arc::new(daps),
+        Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new())),
+        Duration::from_secs(3600),
+        Duration::from_secs(3600),
+        AttestationConfig {
+            supported_attestation_suite: vec![TEST_RAT_ID.to_string()],
+            expected_attestation_suite: vec![TEST_RAT_ID.to_string()],
+            rat_timeout: Duration::from_secs(3600),
+            ..Default::default()
+        },
+    );
+
+    // there is no upper layer the messages could be delivered to
+    fsm.set_connection(None);
+    let _ = fsm.process_event(FsmEvent::FromUpper(UserEvent::StartHandshake));
+
+    let mut input = data;
+    while let Some((&op, rest)) = input.split_first() {
+        input = rest;
+        let event = match op % OP_COUNT {
+            OP_PEER_MESSAGE => {
+                if input.len() < 2 {
+                    break;
+                }
+                let len = u16::from_be_bytes([input[0], input[1]]) as usize;
+                let len = std::cmp::min(len, input.len() - 2);
+                let frame = &input[2..2 + len];
+                input = &input[2 + len..];
+                match IdscpMessage::parse_from_bytes(frame) {
+                    Ok(IdscpMessage {
+                        message: Some(body),
+                        ..
+                    }) => FsmEvent::FromSecureChannel(SecureChannelEvent::from(body)),
+                    _ => continue,
+                }
+            }
+            OP_PROVER_OK => FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::OK(
+                RatResult::default(),
+            ))),
+            OP_PROVER_FAILED => {
+                FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::Failed(String::new())))
+            }
+            OP_PROVER_DATA => FsmEvent::FromRatProver(RatMessage::RawData(b"prover".to_vec())),
+            OP_VERIFIER_OK => FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK(
+                RatResult::default(),
+            ))),
+            OP_VERIFIER_FAILED => {
+                FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::Failed(String::new())))
+            }
+            OP_VERIFIER_DATA => {
+                FsmEvent::FromRatVerifier(RatMessage::RawData(b"verifier".to_vec()))
+            }
+            OP_USER_DATA => FsmEvent::FromUpper(UserEvent::Data(b"data".to_vec())),
+            OP_USER_REPEAT_RAT => FsmEvent::FromUpper(UserEvent::RepeatRat(String::new())),
+            OP_USER_STOP => FsmEvent::FromUpper(UserEvent::Stop),
+            OP_HANDSHAKE_TIMEOUT => FsmEvent::HandshakeTimeout,
+            OP_RAT_TIMEOUT => FsmEvent::RatTimeout,
+            OP_DAT_TIMEOUT => FsmEvent::DatTimeout,
+            OP_ACK_TIMEOUT => FsmEvent::AckTimeout,
+            OP_DAT_RENEWAL_TIMEOUT => FsmEvent::DatRenewalTimeout,
+            OP_DAPS_DAT => FsmEvent::FromDaps(TestDaps::default().get_token()),
+            OP_DAPS_ERROR => FsmEvent::FromDaps(Err(DapsError::Unavailable(String::new()))),
+            _ => FsmEvent::FromSecureChannel(SecureChannelEvent::Error),
+        };
+        let _ = fsm.process_event(event);
+    }
+
+    // release all timers, drivers and the secure channel
+    fsm.cleanup();
+}
+
+fn encode(msg: IdscpMessage) -> Vec<u8> {
+    msg.write_to_bytes().unwrap()
+}
+
+fn peer_message(msg: IdscpMessage) -> Vec<u8> {
+    let raw = encode(msg);
+    let mut op = vec![OP_PEER_MESSAGE];
+    op.extend_from_slice(&(raw.len() as u16).to_be_bytes());
+    op.extend_from_slice(&raw);
+    op
+}
+
+// one encoded message of each type, created via the message factory
+pub fn seed_messages() -> Vec<(&'static str, Vec<u8>)> {
+    let suite = vec![TEST_RAT_ID.to_string()];
+    vec![
+        (
+            "hello",
+            encode(create_idscp_hello(VALID_DAT.to_vec(), &suite, &suite)),
+        ),
+        (
+            "close",
+            encode(create_idscp_close(
+                IdscpClose_CloseCause::USER_SHUTDOWN,
+                "User Shutdown",
+            )),
+        ),
+        ("dat_exp", encode(create_idscp_dat_exp())),
+        ("dat", encode(create_idscp_dat(VALID_DAT.to_vec()))),
+        ("re_rat", encode(create_idscp_re_rat("Re-Attestation"))),
+        ("rat_prover", encode(create_idscp_rat_prover(vec![1, 2, 3]))),
+        (
+            "rat_verifier",
+            encode(create_idscp_rat_verifier(vec![1, 2, 3])),
+        ),
+        (
+            "data",
+            encode(create_idscp_data(b"data".to_vec(), &AlternatingBit::One)),
+        ),
+        ("ack", encode(create_idscp_ack(AlternatingBit::One))),
+    ]
+}
+
+// event sequences that cover the handshake, data exchange, re-attestation and closing
+pub fn seed_fsm_sequences() -> Vec<(&'static str, Vec<u8>)> {
+    let suite = vec![TEST_RAT_ID.to_string()];
+    let hello = peer_message(create_idscp_hello(VALID_DAT.to_vec(), &suite, &suite));
+    let established = [
+        &hello[..],
+        &[
+            OP_PROVER_DATA,
+            OP_VERIFIER_DATA,
+            OP_PROVER_OK,
+            OP_VERIFIER_OK,
+        ],
+    ]
+    .concat();
+
+    let data_exchange = [
+        &established[..],
+        &[OP_USER_DATA, OP_ACK_TIMEOUT],
+        &peer_message(create_idscp_ack(AlternatingBit::Zero)),
+        &peer_message(create_idscp_data(b"data".to_vec(), &AlternatingBit::Zero)),
+        &peer_message(create_idscp_data(b"data".to_vec(), &AlternatingBit::Zero)),
+    ]
+    .concat();
+
+    let re_attestation = [
+        &established[..],
+        &peer_message(create_idscp_re_rat("Re-Attestation")),
+        &peer_message(create_idscp_rat_verifier(vec![1, 2, 3])),
+        &[OP_PROVER_OK, OP_RAT_TIMEOUT],
+        &peer_message(create_idscp_rat_prover(vec![1, 2, 3])),
+        &[OP_VERIFIER_OK],
+    ]
+    .concat();
+
+    let dat_renewal = [
+        &established[..],
+        &[OP_DAT_TIMEOUT],
+        &peer_message(create_idscp_dat_exp()),
+        &[OP_DAPS_DAT],
+        &peer_message(create_idscp_dat(VALID_DAT.to_vec())),
+        &[OP_VERIFIER_OK],
+    ]
+    .concat();
+
+    let proactive_dat_renewal = [
+        &established[..],
+        &[OP_DAT_RENEWAL_TIMEOUT, OP_DAPS_DAT],
+        &peer_message(create_idscp_dat(VALID_DAT.to_vec())),
+        &[OP_USER_DATA],
+    ]
+    .concat();
+
+    let close = [
+        &established[..],
+        &peer_message(create_idscp_close(IdscpClose_CloseCause::ERROR, "Error")),
+        &[OP_USER_DATA],
+    ]
+    .concat();
+
+    let daps_outage = [&established[..], &[OP_DAT_RENEWAL_TIMEOUT, OP_DAPS_ERROR]].concat();
+
+    vec![
+        ("handshake", established),
+        ("data_exchange", data_exchange),
+        ("re_attestation", re_attestation),
+        ("dat_renewal", dat_renewal),
+        ("proactive_dat_renewal", proactive_dat_renewal),
+        ("close", close),
+        ("daps_outage", daps_outage),
+        ("timeouts", vec![OP_HANDSHAKE_TIMEOUT, OP_SC_ERROR]),
+    ]
+}
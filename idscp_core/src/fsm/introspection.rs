@@ -0,0 +1,632 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Introspection of the fsm by exhaustive exploration: starting from the initial state, every event
+// is applied to every reachable combination of fsm state, ack flag, alternating bits, handshake
+// result and data buffered during re-attestation, for a given ReRatDataPolicy. The observed
+// transitions are used by the model check and can be rendered as DOT or
+// Mermaid graph (see the idscp_fsm_graph binary).
+//
+// Each transition is executed on a freshly created fsm, with running RAT drivers and without any
+// active timers. State that is not part of the explored tuple is therefore not modelled.
+
+use super::test_support::{create_cert, dummy_registry, TestDaps, TestSc, TEST_RAT_ID};
+use super::*;
+use crate::api::idscp_configuration::ReRatDataPolicy;
+use crate::api::idscp_connection::IdscpEvent;
+use crate::drivers::daps_driver::{DapsError, Dat};
+use crate::messages::idscp_message_factory::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::channel;
+
+// the explored part of the fsm state
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct ModelState {
+    pub(super) state: FsmState,
+    pub(super) ack_active: bool,
+    pub(super) next_send_bit: AlternatingBit,
+    pub(super) expected_bit: AlternatingBit,
+    pub(super) handshake_done: bool,
+    // outbound messages buffered during re-attestation, inbound data held back
+    pub(super) buffered: usize,
+    pub(super) held: bool,
+}
+
+impl ModelState {
+    pub(super) fn initial() -> Self {
+        ModelState {
+            state: FsmState::Closed(ClosedStateStatus::Unlocked),
+            ack_active: false,
+            next_send_bit: AlternatingBit::Zero,
+            expected_bit: AlternatingBit::Zero,
+            handshake_done: false,
+            buffered: 0,
+            held: false,
+        }
+    }
+
+    fn of(fsm: &FiniteStateMachine) -> Self {
+        ModelState {
+            state: fsm.current_state.clone(),
+            ack_active: fsm.ack_flag != AckFlag::Inactive,
+            next_send_bit: fsm.next_send_alternating_bit.clone(),
+            expected_bit: fsm.expected_alternating_bit.clone(),
+            handshake_done: fsm.handshake_result_available,
+            buffered: fsm.re_rat_outbound_buffer.len(),
+            held: fsm.re_rat_inbound_data.is_some(),
+        }
+    }
+
+    pub(super) fn is_closed(&self) -> bool {
+        matches!(self.state, FsmState::Closed(_))
+    }
+
+    // values of the variables besides the fsm state
+    pub(super) fn variables(&self) -> [(&'static str, &'static str); 6] {
+        let bit = |b: &AlternatingBit| if b.as_bool() { "1" } else { "0" };
+        let flag = |f: bool| if f { "1" } else { "0" };
+        [
+            (
+                "ack",
+                if self.ack_active {
+                    "active"
+                } else {
+                    "inactive"
+                },
+            ),
+            ("send", bit(&self.next_send_bit)),
+            ("recv", bit(&self.expected_bit)),
+            ("hs", flag(self.handshake_done)),
+            (
+                "buffered",
+                match self.buffered {
+                    0 => "0",
+                    1 => "1",
+                    _ => "n",
+                },
+            ),
+            ("held", flag(self.held)),
+        ]
+    }
+}
+
+fn sc_event(m: IdscpMessage) -> FsmEvent {
+    FsmEvent::FromSecureChannel(SecureChannelEvent::from(m.message.unwrap()))
+}
+
+// all fsm events with a stable label
+pub(super) fn event_alphabet() -> Vec<(&'static str, FsmEvent)> {
+    let suite = vec![TEST_RAT_ID.to_string()];
+    vec![
+        (
+            "RatProver(RawData)",
+            FsmEvent::FromRatProver(RatMessage::RawData(vec![])),
+        ),
+        (
+            "RatProver(OK)",
+            FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::OK(RatResult::default()))),
+        ),
+        (
+            "RatProver(Failed)",
+            FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::Failed(String::new()))),
+        ),
+        (
+            "RatVerifier(RawData)",
+            FsmEvent::FromRatVerifier(RatMessage::RawData(vec![])),
+        ),
+        (
+            "RatVerifier(OK)",
+            FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK(RatResult::default()))),
+        ),
+        (
+            "RatVerifier(Failed)",
+            FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::Failed(String::new()))),
+        ),
+        (
+            "User(StartHandshake)",
+            FsmEvent::FromUpper(UserEvent::StartHandshake),
+        ),
+        ("User(Stop)", FsmEvent::FromUpper(UserEvent::Stop)),
+        (
+            "User(RepeatRat)",
+            FsmEvent::FromUpper(UserEvent::RepeatRat(String::new())),
+        ),
+        (
+            "User(Data)",
+            FsmEvent::FromUpper(UserEvent::Data(b"DATA".to_vec())),
+        ),
+        (
+            "Daps(Dat)",
+            FsmEvent::FromDaps(Ok(Dat {
+                token: "valid".to_string(),
+                expires_at: None,
+            })),
+        ),
+        (
+            "Daps(Error)",
+            FsmEvent::FromDaps(Err(DapsError::Unavailable(String::new()))),
+        ),
+        (
+            "ScError",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Error),
+        ),
+        (
+            "Sc(Hello valid)",
+            sc_event(create_idscp_hello(b"valid".to_vec(), &suite, &suite)),
+        ),
+        (
+            "Sc(Hello invalid)",
+            sc_event(create_idscp_hello(b"invalid".to_vec(), &suite, &suite)),
+        ),
+        (
+            "Sc(Close)",
+            sc_event(create_idscp_close(IdscpClose_CloseCause::USER_SHUTDOWN, "")),
+        ),
+        (
+            "Sc(Dat valid)",
+            sc_event(create_idscp_dat(b"valid".to_vec())),
+        ),
+        (
+            "Sc(Dat invalid)",
+            sc_event(create_idscp_dat(b"invalid".to_vec())),
+        ),
+        ("Sc(DatExpired)", sc_event(create_idscp_dat_exp())),
+        ("Sc(RatProver)", sc_event(create_idscp_rat_prover(vec![]))),
+        (
+            "Sc(RatVerifier)",
+            sc_event(create_idscp_rat_verifier(vec![])),
+        ),
+        ("Sc(ReRat)", sc_event(create_idscp_re_rat(""))),
+        (
+            "Sc(Data 0)",
+            sc_event(create_idscp_data(b"DATA".to_vec(), &AlternatingBit::Zero)),
+        ),
+        (
+            "Sc(Data 1)",
+            sc_event(create_idscp_data(b"DATA".to_vec(), &AlternatingBit::One)),
+        ),
+        (
+            "Sc(Ack 0)",
+            sc_event(create_idscp_ack(AlternatingBit::Zero)),
+        ),
+        ("Sc(Ack 1)", sc_event(create_idscp_ack(AlternatingBit::One))),
+        ("HandshakeTimeout", FsmEvent::HandshakeTimeout),
+        ("RatTimeout", FsmEvent::RatTimeout),
+        ("DatTimeout", FsmEvent::DatTimeout),
+        ("DatRenewalTimeout", FsmEvent::DatRenewalTimeout),
+        ("AckTimeout", FsmEvent::AckTimeout),
+    ]
+}
+
+pub(super) struct TransitionOutcome {
+    pub(super) to: Option<ModelState>, // None if the fsm panicked
+    pub(super) result: String,
+    pub(super) timers_active: Vec<&'static str>,
+    pub(super) sent_messages: Vec<&'static str>,
+    pub(super) delivered_messages: usize,
+    pub(super) discarded_messages: usize,
+    pub(super) close_notifications: usize,
+    pub(super) handshake_result: HandshakeResult,
+}
+
+fn message_name(msg: &IdscpMessage) -> &'static str {
+    match &msg.message {
+        None => "IdscpMessage",
+        Some(IdscpMessage_oneof_message::idscpHello(_)) => "IdscpHello",
+        Some(IdscpMessage_oneof_message::idscpClose(_)) => "IdscpClose",
+        Some(IdscpMessage_oneof_message::idscpDatExpired(_)) => "IdscpDatExpired",
+        Some(IdscpMessage_oneof_message::idscpDat(_)) => "IdscpDat",
+        Some(IdscpMessage_oneof_message::idscpReRat(_)) => "IdscpReRat",
+        Some(IdscpMessage_oneof_message::idscpRatProver(_)) => "IdscpRatProver",
+        Some(IdscpMessage_oneof_message::idscpRatVerifier(_)) => "IdscpRatVerifier",
+        Some(IdscpMessage_oneof_message::idscpData(_)) => "IdscpData",
+        Some(IdscpMessage_oneof_message::idscpAck(_)) => "IdscpAck",
+    }
+}
+
+// create a fresh fsm in the given model state and apply a single event
+fn execute_transition(
+    cert: &X509,
+    policy: &ReRatDataPolicy,
+    from: &ModelState,
+    event: FsmEvent,
+) -> TransitionOutcome {
+    let sc = Arc::new(TestSc::new(cert.clone()));
+    let handshake_cond = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
+    let rat_config = AttestationConfig {
+        supported_attestation_suite: vec![TEST_RAT_ID.to_string()],
+        expected_attestation_suite: vec![TEST_RAT_ID.to_string()],
+        rat_timeout: Duration::from_secs(60),
+        re_rat_data_policy: policy.clone(),
+        ..Default::default()
+    };
+    let (mut fsm, _queue) = FiniteStateMachine::create(
+        Arc::clone(&sc) as Arc<dyn SecureChannel + Send + Sync>,
+        dummy_registry(),
+        dummy_registry(),
+        Arc::new(TestDaps::default()),
+        Arc::clone(&handshake_cond),
+        Duration::from_secs(60),
+        Duration::from_secs(60),
+        rat_config,
+    );
+
+    let (connection_tx, connection_rx) = channel();
+
+    // register rat drivers in the interfaces, like it is done when receiving the hello
+    let _ = fsm
+        .rat_prover
+        .start_driver(TEST_RAT_ID, &fsm.prover_registry);
+    let _ = fsm
+        .rat_verifier
+        .start_driver(TEST_RAT_ID, &fsm.verifier_registry);
+    fsm.set_connection(Some(connection_tx));
+    fsm.current_state = from.state.clone();
+    fsm.ack_flag = if from.ack_active {
+        AckFlag::Active(b"DATA".to_vec())
+    } else {
+        AckFlag::Inactive
+    };
+    fsm.next_send_alternating_bit = from.next_send_bit.clone();
+    fsm.expected_alternating_bit = from.expected_bit.clone();
+    fsm.handshake_result_available = from.handshake_done;
+    fsm.re_rat_outbound_buffer = vec![b"DATA".to_vec(); from.buffered].into();
+    if from.held {
+        let data = create_idscp_data(b"DATA".to_vec(), &from.expected_bit).take_idscpData();
+        fsm.re_rat_inbound_data = Some(data);
+    }
+    if !from.is_closed() {
+        // the secure channel is only unlocked after the handshake was started
+        let _ = fsm.sc_interface.unlock();
+    }
+
+    let result = catch_unwind(AssertUnwindSafe(|| fsm.process_event(event)));
+
+    let (to, result) = match result {
+        Err(_) => (None, "PANIC".to_string()),
+        Ok(r) => (
+            Some(ModelState::of(&fsm)),
+            match r {
+                Ok(()) => "Ok".to_string(),
+                Err(e) => format!("Err({:?})", e),
+            },
+        ),
+    };
+
+    let mut timers_active = Vec::new();
+    for (name, active) in &[
+        ("handshake", fsm.handshake_timer.is_active()),
+        ("prover", fsm.prover_timer.is_active()),
+        ("verifier", fsm.verifier_timer.is_active()),
+        ("rat", fsm.rat_timer.is_active()),
+        ("ack", fsm.ack_timer.is_active()),
+        ("dat", fsm.dat_timer.is_active()),
+        ("dat renewal", fsm.dat_renewal_timer.is_active()),
+        ("dat request", fsm.daps_interface.is_pending()),
+    ] {
+        if *active {
+            timers_active.push(*name);
+        }
+    }
+
+    // collect the outcome before the cleanup, which lets the secure channel listener report an
+    // error to the fsm
+    let sent_messages = sc
+        .take_sent()
+        .iter()
+        .filter_map(|raw| IdscpMessage::parse_from_bytes(raw).ok())
+        .map(|m| message_name(&m))
+        .collect();
+
+    let handshake_result = handshake_cond.0.lock().unwrap().clone();
+
+    // shutdown the fsm without notifying the connection
+    fsm.set_connection(None);
+    fsm.cleanup();
+    drop(fsm);
+
+    let mut delivered_messages = 0;
+    let mut discarded_messages = 0;
+    let mut close_notifications = 0;
+    for e in connection_rx.try_iter() {
+        match e {
+            IdscpEvent::Message(_) => delivered_messages += 1,
+            IdscpEvent::MessageDiscarded(_) => discarded_messages += 1,
+            IdscpEvent::ConnectionClosed => close_notifications += 1,
+        }
+    }
+
+    TransitionOutcome {
+        to,
+        result,
+        timers_active,
+        sent_messages,
+        delivered_messages,
+        discarded_messages,
+        close_notifications,
+        handshake_result,
+    }
+}
+
+// breadth-first exploration of all reachable model states with the given data policy, the callback
+// is invoked once for each pair of reachable model state and event
+pub(super) fn explore<F>(policy: &ReRatDataPolicy, mut callback: F)
+where
+    F: FnMut(&ModelState, &'static str, &FsmEvent, &TransitionOutcome),
+{
+    let cert = create_cert();
+    let alphabet = event_alphabet();
+
+    let mut visited = vec![ModelState::initial()];
+    let mut queue = vec![ModelState::initial()];
+
+    while let Some(from) = queue.pop() {
+        for (label, event) in &alphabet {
+            let outcome = execute_transition(&cert, policy, &from, event.clone());
+            if let Some(to) = &outcome.to {
+                if !visited.contains(to) {
+                    visited.push(to.clone());
+                    queue.push(to.clone());
+                }
+            }
+            callback(&from, label, event, &outcome);
+        }
+    }
+}
+
+// Edge of the transition graph: all explored transitions from one fsm state with the same event,
+// target state and actions are merged into one edge. The guard contains the variable values that
+// are common to all merged transitions, but not to all explored transitions of the source state.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphEdge {
+    pub from: String,
+    pub event: String,
+    pub guard: Vec<String>,
+    pub actions: Vec<String>,
+    pub to: String,
+}
+
+impl GraphEdge {
+    pub fn label(&self) -> String {
+        let mut label = self.event.clone();
+        if !self.guard.is_empty() {
+            let _ = write!(label, " [{}]", self.guard.join(", "));
+        }
+        if !self.actions.is_empty() {
+            let _ = write!(label, " / {}", self.actions.join(", "));
+        }
+        label
+    }
+}
+
+fn transition_actions(
+    from: &ModelState,
+    to: &ModelState,
+    outcome: &TransitionOutcome,
+) -> Vec<String> {
+    let mut actions = Vec::new();
+    if outcome.result.starts_with("Err") {
+        actions.push(format!("error {}", outcome.result));
+    }
+    for msg in &outcome.sent_messages {
+        actions.push(format!("send {}", msg));
+    }
+    for timer in &outcome.timers_active {
+        actions.push(format!("start {} timer", timer));
+    }
+    if outcome.delivered_messages > 0 {
+        actions.push("deliver data".to_string());
+    }
+    if outcome.discarded_messages > 0 {
+        actions.push("discard data".to_string());
+    }
+    if outcome.close_notifications > 0 {
+        actions.push("notify close".to_string());
+    }
+    match outcome.handshake_result {
+        HandshakeResult::NotAvailable => {}
+        HandshakeResult::Successful => actions.push("handshake successful".to_string()),
+        HandshakeResult::Failed => actions.push("handshake failed".to_string()),
+    }
+    for (before, after) in from.variables().iter().zip(to.variables().iter()) {
+        if before.1 != after.1 {
+            actions.push(format!("{}:={}", after.0, after.1));
+        }
+    }
+    actions
+}
+
+// Explore the fsm with the default data policy and merge the transitions into graph edges. Unless
+// include_ignored is set, transitions that neither change the state nor have any action are left
+// out.
+pub fn transition_graph(include_ignored: bool) -> Vec<GraphEdge> {
+    // all explored model states per fsm state, for guard computation
+    let mut sources: BTreeMap<String, Vec<ModelState>> = BTreeMap::new();
+    // (from, event, to, actions) -> model states in which the transition was observed
+    let mut merged: BTreeMap<(String, String, String, Vec<String>), Vec<ModelState>> =
+        BTreeMap::new();
+
+    explore(&ReRatDataPolicy::Block, |from, label, _, outcome| {
+        let from_state = format!("{:?}", from.state);
+        let source = sources.entry(from_state.clone()).or_default();
+        if !source.contains(from) {
+            source.push(from.clone());
+        }
+
+        let (to_state, actions) = match &outcome.to {
+            None => ("PANIC".to_string(), vec!["panic".to_string()]),
+            Some(to) => {
+                let actions = transition_actions(from, to, outcome);
+                let ignored = to == from && actions.iter().all(|a| a.starts_with("error"));
+                if ignored && !include_ignored {
+                    return;
+                }
+                (format!("{:?}", to.state), actions)
+            }
+        };
+        merged
+            .entry((from_state, label.to_string(), to_state, actions))
+            .or_default()
+            .push(from.clone());
+    });
+
+    let mut edges: Vec<GraphEdge> = merged
+        .into_iter()
+        .map(|((from, event, to, actions), states)| {
+            let all = &sources[&from];
+            let mut guard = Vec::new();
+            for i in 0..states[0].variables().len() {
+                let (name, value) = states[0].variables()[i];
+                let common = states.iter().all(|s| s.variables()[i].1 == value);
+                let varies = all.iter().any(|s| s.variables()[i].1 != value);
+                if common && varies {
+                    guard.push(format!("{}={}", name, value));
+                }
+            }
+            GraphEdge {
+                from,
+                event,
+                guard,
+                actions,
+                to,
+            }
+        })
+        .collect();
+    edges.sort();
+    edges
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn render_dot(edges: &[GraphEdge]) -> String {
+    let mut out = String::from("digraph idscp2_fsm {\n    rankdir=LR;\n    node [shape=box];\n");
+    let _ = writeln!(
+        out,
+        "    \"{:?}\" [style=bold];",
+        FsmState::Closed(ClosedStateStatus::Unlocked)
+    );
+    for edge in edges {
+        let _ = writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{}\"];",
+            escape(&edge.from),
+            escape(&edge.to),
+            escape(&edge.label())
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+// mermaid state ids must not contain parentheses
+fn mermaid_id(state: &str) -> String {
+    state
+        .chars()
+        .filter(|c| *c != ')')
+        .map(|c| if c == '(' { '_' } else { c })
+        .collect()
+}
+
+pub fn render_mermaid(edges: &[GraphEdge]) -> String {
+    let mut out = String::from("stateDiagram-v2\n");
+    let _ = writeln!(
+        out,
+        "    [*] --> {}",
+        mermaid_id(&format!(
+            "{:?}",
+            FsmState::Closed(ClosedStateStatus::Unlocked)
+        ))
+    );
+    for edge in edges {
+        // colons and semicolons terminate mermaid labels
+        let label = edge.label().replace(';', "#59;").replace(':', "#58;");
+        let _ = writeln!(
+            out,
+            "    {} --> {} : {}",
+            mermaid_id(&edge.from),
+            mermaid_id(&edge.to),
+            label
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges() -> Vec<GraphEdge> {
+        vec![
+            GraphEdge {
+                from: "Closed(Unlocked)".to_string(),
+                event: "User(StartHandshake)".to_string(),
+                guard: vec![],
+                actions: vec![
+                    "send IdscpHello".to_string(),
+                    "start handshake timer".to_string(),
+                ],
+                to: "WaitForHello".to_string(),
+            },
+            GraphEdge {
+                from: "Established".to_string(),
+                event: "Sc(Data 0)".to_string(),
+                guard: vec!["recv=0".to_string()],
+                actions: vec!["deliver data".to_string(), "recv:=1".to_string()],
+                to: "Established".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let dot = render_dot(&edges());
+        assert!(dot.starts_with("digraph idscp2_fsm {"));
+        assert!(dot.contains(
+            "\"Closed(Unlocked)\" -> \"WaitForHello\" \
+             [label=\"User(StartHandshake) / send IdscpHello, start handshake timer\"];"
+        ));
+        assert!(dot.contains("[label=\"Sc(Data 0) [recv=0] / deliver data, recv:=1\"]"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_transition_graph() {
+        let edges = transition_graph(false);
+        assert!(edges.iter().all(|e| e.to != "PANIC"));
+        assert!(edges.iter().any(|e| e.from == "Closed(Unlocked)"
+            && e.event == "User(StartHandshake)"
+            && e.to == "WaitForHello"
+            && e.actions.contains(&"send IdscpHello".to_string())));
+        // Closed(Locked) ignores all events
+        assert!(!edges.iter().any(|e| e.from == "Closed(Locked)"));
+        assert!(transition_graph(true)
+            .iter()
+            .any(|e| e.from == "Closed(Locked)"));
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        let mermaid = render_mermaid(&edges());
+        assert!(mermaid.starts_with("stateDiagram-v2\n    [*] --> Closed_Unlocked\n"));
+        assert!(mermaid.contains(
+            "Closed_Unlocked --> WaitForHello : \
+             User(StartHandshake) / send IdscpHello, start handshake timer"
+        ));
+        assert!(mermaid.contains("recv#58;=1"));
+    }
+}
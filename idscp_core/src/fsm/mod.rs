@@ -13,30 +13,53 @@
 // limitations under the License.
 
 pub(super) mod alternating_bit;
+mod daps_interface;
+pub(crate) mod event_loop;
 mod fsm_timer;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(any(test, feature = "introspection"))]
+pub mod introspection;
+#[cfg(test)]
+mod model_check;
 mod rat_interface;
+mod rate_limit;
 mod sc_interface;
-
-use crate::api::idscp_configuration::AttestationConfig;
-use crate::api::idscp_connection::InnerIdscp2connection;
-use crate::drivers::daps_driver::DapsDriver;
-use crate::drivers::rat_driver::{RatIcm, RatMessage, RatRegistry};
+#[cfg(any(test, feature = "fuzzing", feature = "introspection"))]
+#[allow(dead_code)] // the fuzz targets and the introspection use only some of the stubs
+pub(crate) mod test_support;
+
+use crate::api::idscp_configuration::{AttestationConfig, ReRatDataPolicy};
+use crate::api::idscp_connection::{IdscpEvent, RatFailure, SecurityReport};
+use crate::api::idscp_fsm_observer::{
+    FsmObserver, FsmTransition, SecurityEvent, SecurityEventKind,
+};
+use crate::drivers::daps_driver::{DapsDriver, DapsError, Dat, TokenVerifier, VerifiedDat};
+use crate::drivers::rat_driver::{RatIcm, RatMessage, RatRegistry, RatResult};
 use crate::drivers::secure_channel::SecureChannel;
 use crate::messages::idscp_message_factory;
 use crate::messages::idscpv2_messages::*;
+use event_loop::{EventQueue, LoopMessage};
 use fsm_timer::*;
 
 use crate::fsm::rat_interface::RatError;
 use crate::fsm::sc_interface::ScIfError;
+use openssl::nid::Nid;
+use openssl::x509::X509;
 use protobuf::Message;
 use rat_interface::{RatDriverInterface, RatProver, RatVerifier};
+use rate_limit::RateLimiter;
 use sc_interface::SecureChannelInterface;
-use std::sync::{Arc, Condvar, Mutex, Weak};
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 use crate::fsm::alternating_bit::AlternatingBitError;
 use alternating_bit::AlternatingBit;
+use daps_interface::DapsInterface;
 
 // FSM Events
 #[derive(Debug, Clone)]
@@ -48,16 +71,61 @@ enum FsmEvent {
     // USER EVENTS
     FromUpper(UserEvent),
 
+    // DAPS EVENTS
+    FromDaps(Result<Dat, DapsError>),
+
     // SECURE CHANNEL EVENTS
     FromSecureChannel(SecureChannelEvent),
 
     // TIMEOUT EVENTS
     RatTimeout,
     DatTimeout,
+    DatRenewalTimeout,
     HandshakeTimeout,
     AckTimeout,
 }
 
+impl FsmEvent {
+    // kind of the event without any payload, used for logging and fsm observers
+    fn kind(&self) -> &'static str {
+        match self {
+            FsmEvent::FromRatProver(RatMessage::RawData(_)) => "RatProver(RawData)",
+            FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::OK(_))) => "RatProver(OK)",
+            FsmEvent::FromRatProver(RatMessage::ControlMessage(RatIcm::Failed(_))) => {
+                "RatProver(Failed)"
+            }
+            FsmEvent::FromRatVerifier(RatMessage::RawData(_)) => "RatVerifier(RawData)",
+            FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK(_))) => {
+                "RatVerifier(OK)"
+            }
+            FsmEvent::FromRatVerifier(RatMessage::ControlMessage(RatIcm::Failed(_))) => {
+                "RatVerifier(Failed)"
+            }
+            FsmEvent::FromUpper(UserEvent::StartHandshake) => "User(StartHandshake)",
+            FsmEvent::FromUpper(UserEvent::Stop) => "User(Stop)",
+            FsmEvent::FromUpper(UserEvent::RepeatRat(_)) => "User(RepeatRat)",
+            FsmEvent::FromUpper(UserEvent::Data(_)) => "User(Data)",
+            FsmEvent::FromDaps(Ok(_)) => "Daps(Dat)",
+            FsmEvent::FromDaps(Err(_)) => "Daps(Error)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Close(_)) => "Sc(Close)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Hello(_)) => "Sc(Hello)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Dat(_)) => "Sc(Dat)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::DatExp(_)) => "Sc(DatExpired)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::RatProver(_)) => "Sc(RatProver)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::RatVerifier(_)) => "Sc(RatVerifier)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::ReRat(_)) => "Sc(ReRat)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Data(_)) => "Sc(Data)",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Error) => "ScError",
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Ack(_)) => "Sc(Ack)",
+            FsmEvent::RatTimeout => "RatTimeout",
+            FsmEvent::DatTimeout => "DatTimeout",
+            FsmEvent::DatRenewalTimeout => "DatRenewalTimeout",
+            FsmEvent::HandshakeTimeout => "HandshakeTimeout",
+            FsmEvent::AckTimeout => "AckTimeout",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum SecureChannelEvent {
     Close(IdscpClose),
@@ -72,11 +140,29 @@ enum SecureChannelEvent {
     Ack(IdscpAck),
 }
 
+impl From<IdscpMessage_oneof_message> for SecureChannelEvent {
+    fn from(body: IdscpMessage_oneof_message) -> Self {
+        match body {
+            IdscpMessage_oneof_message::idscpClose(data) => SecureChannelEvent::Close(data),
+            IdscpMessage_oneof_message::idscpHello(data) => SecureChannelEvent::Hello(data),
+            IdscpMessage_oneof_message::idscpDat(data) => SecureChannelEvent::Dat(data),
+            IdscpMessage_oneof_message::idscpDatExpired(data) => SecureChannelEvent::DatExp(data),
+            IdscpMessage_oneof_message::idscpRatProver(data) => SecureChannelEvent::RatProver(data),
+            IdscpMessage_oneof_message::idscpRatVerifier(data) => {
+                SecureChannelEvent::RatVerifier(data)
+            }
+            IdscpMessage_oneof_message::idscpReRat(data) => SecureChannelEvent::ReRat(data),
+            IdscpMessage_oneof_message::idscpData(data) => SecureChannelEvent::Data(data),
+            IdscpMessage_oneof_message::idscpAck(data) => SecureChannelEvent::Ack(data),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum UserEvent {
     StartHandshake,
     Stop,
-    RepeatRat,
+    RepeatRat(String), // cause that is sent to the peer in IdscpReRat
     Data(Vec<u8>),
 }
 
@@ -100,8 +186,25 @@ enum FsmState {
     Established,               //nothing active
 }
 
+impl FsmState {
+    fn name(&self) -> &'static str {
+        match self {
+            FsmState::Closed(ClosedStateStatus::Locked) => "Closed(Locked)",
+            FsmState::Closed(ClosedStateStatus::Unlocked) => "Closed(Unlocked)",
+            FsmState::WaitForHello => "WaitForHello",
+            FsmState::WaitForRat => "WaitForRat",
+            FsmState::WaitForRatProver => "WaitForRatProver",
+            FsmState::WaitForRatVerifier => "WaitForRatVerifier",
+            FsmState::WaitForDatAndRat => "WaitForDatAndRat",
+            FsmState::WaitForDatAndRatVerifier => "WaitForDatAndRatVerifier",
+            FsmState::WaitForAck => "WaitForAck",
+            FsmState::Established => "Established",
+        }
+    }
+}
+
 //idscp2 handshake result
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum HandshakeResult {
     NotAvailable,
     Failed,
@@ -147,31 +250,53 @@ pub enum FsmError {
     NotConnected,
     #[error("IdscpData must be buffered in state 'WaitForAck'")]
     IdscpDataNotCached,
+    #[error("Peer exceeded the rate limit of {0}")]
+    RateLimitExceeded(&'static str),
+    #[error("Own DAT is not available")]
+    DapsError(#[from] DapsError),
 }
 
+// connection ids are unique within the process
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
 // FSM
 pub(crate) struct FiniteStateMachine {
-    rat_prover: Arc<Mutex<RatDriverInterface<RatProver>>>,
-    rat_verifier: Arc<Mutex<RatDriverInterface<RatVerifier>>>,
+    connection_id: u64,
+    observer: Option<Arc<dyn FsmObserver + Send + Sync>>,
+    event_tx: Sender<LoopMessage>, // events of timers, drivers and the secure channel
+    rat_prover: RatDriverInterface<RatProver>,
+    rat_verifier: RatDriverInterface<RatVerifier>,
     current_state: FsmState,
     handshake_timer: StaticTimer<HandshakeTimer>,
     prover_timer: StaticTimer<HandshakeTimer>, // TODO: maybe make new timer type "RatDriverTimer" to emit more precise error?
     verifier_timer: StaticTimer<HandshakeTimer>, // TODO: maybe make new timer type "RatDriverTimer" to emit more precise error?
-    rat_timer: StaticTimer<RatTimer>,
+    rat_timer: DynamicTimer<RatTimer>,
     ack_timer: StaticTimer<AckTimer>,
     dat_timer: DynamicTimer<DatTimer>,
-    sc_interface: Arc<Mutex<SecureChannelInterface>>,
+    dat_renewal_timer: DynamicTimer<DatRenewalTimer>,
+    sc_interface: SecureChannelInterface,
+    peer_cert: X509, // TLS certificate of the peer, the dat of the peer must be bound to it
+    security: Arc<Mutex<SecurityReport>>, // shared with the Idscp2Connection
     daps_driver: Arc<dyn DapsDriver + Send + Sync>,
+    dat_verifier: Option<Arc<dyn TokenVerifier + Send + Sync>>, // replaces the daps for verifying
+    daps_interface: DapsInterface,                              // requests the own dat for renewals
+    handshake_dat: Option<Result<Dat, DapsError>>, // own dat for the hello, requested in advance
+    restart_prover_on_dat: bool, // the peer requested a new dat and a new attestation
     prover_registry: Arc<RatRegistry>,
     verifier_registry: Arc<RatRegistry>,
-    idscp_connection: Weak<Mutex<InnerIdscp2connection>>,
-    connection_available_var: Arc<(Mutex<bool>, Condvar)>, //wait until connection is available
+    idscp_connection: Option<Sender<IdscpEvent>>, // events for the upper layer
     handshake_cond: Arc<(Mutex<HandshakeResult>, Condvar)>, //handshake result to notify upper layer
     handshake_result_available: bool,
     rat_config: AttestationConfig,
     ack_flag: AckFlag,
     expected_alternating_bit: AlternatingBit,
     next_send_alternating_bit: AlternatingBit,
+    peer_rat_timeout: Option<Duration>, // rat interval of the peer from the attestation config
+    skip_rat_on_dat_renewal: bool,      // the next received dat only renews an expired dat
+    re_rat_outbound_buffer: VecDeque<Vec<u8>>, // data of the user buffered during re-attestation
+    re_rat_inbound_data: Option<IdscpData>, // data of the peer acknowledged after re-attestation
+    re_rat_limiter: RateLimiter,        // limits IdscpReRat requests of the peer
+    dat_expired_limiter: RateLimiter,   // limits IdscpDatExpired requests of the peer
 }
 
 impl FiniteStateMachine {
@@ -184,78 +309,91 @@ impl FiniteStateMachine {
         handshake_timeout: Duration,
         ack_timeout: Duration,
         rat_config: AttestationConfig,
-    ) -> Arc<Mutex<FiniteStateMachine>> {
+    ) -> (FiniteStateMachine, EventQueue) {
+        let (event_tx, queue) = EventQueue::create();
         let peer_cert = secure_channel.get_peer_certificate();
-        let prover: Arc<Mutex<RatDriverInterface<RatProver>>> =
-            RatDriverInterface::create(peer_cert.clone());
-        let verifier: Arc<Mutex<RatDriverInterface<RatVerifier>>> =
-            RatDriverInterface::create(peer_cert);
-        let sc_interface = SecureChannelInterface::create();
-
-        //create fsm in arc mutex for multi-threaded mutable access
-        let fsm = Arc::new(Mutex::new(FiniteStateMachine {
-            rat_prover: Arc::clone(&prover),
-            rat_verifier: Arc::clone(&verifier),
+        let peer_rat_timeout = peer_cert
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .filter_map(|cn| String::from_utf8(cn.data().as_slice().to_vec()).ok())
+            .find_map(|cn| rat_config.rat_timeouts_per_peer.get(&cn).cloned());
+        let channel_binding = secure_channel.get_channel_binding();
+        if channel_binding.is_none() {
+            log::warn!("Secure channel provides no channel binding for the RAT drivers");
+        }
+        let prover = RatDriverInterface::create(
+            peer_cert.clone(),
+            channel_binding.clone(),
+            event_tx.clone(),
+        );
+        let verifier =
+            RatDriverInterface::create(peer_cert.clone(), channel_binding, event_tx.clone());
+        let mut sc_interface = SecureChannelInterface::create(event_tx.clone());
+        sc_interface.init(secure_channel);
+        let daps_interface = DapsInterface::create(Arc::clone(&daps_driver), event_tx.clone());
+        // the fsm is created on the thread of the caller, the event loop is not blocked yet
+        let handshake_dat = Some(daps_driver.get_token());
+        let re_rat_limiter = RateLimiter::new(rat_config.re_rat_rate_limit.clone());
+        let dat_expired_limiter = RateLimiter::new(rat_config.dat_expired_rate_limit.clone());
+
+        let fsm = FiniteStateMachine {
+            connection_id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            observer: None,
+            rat_prover: prover,
+            rat_verifier: verifier,
             current_state: FsmState::Closed(ClosedStateStatus::Unlocked),
-            handshake_timer: StaticTimer::new(handshake_timeout),
-            prover_timer: StaticTimer::new(handshake_timeout),
-            verifier_timer: StaticTimer::new(handshake_timeout),
-            rat_timer: StaticTimer::new(rat_config.rat_timeout),
-            dat_timer: DynamicTimer::new(),
-            sc_interface: Arc::clone(&sc_interface),
+            handshake_timer: StaticTimer::new(handshake_timeout, event_tx.clone()),
+            prover_timer: StaticTimer::new(handshake_timeout, event_tx.clone()),
+            verifier_timer: StaticTimer::new(handshake_timeout, event_tx.clone()),
+            rat_timer: DynamicTimer::new(event_tx.clone()),
+            dat_timer: DynamicTimer::new(event_tx.clone()),
+            dat_renewal_timer: DynamicTimer::new(event_tx.clone()),
+            sc_interface,
+            peer_cert,
+            security: Arc::new(Mutex::new(SecurityReport::default())),
             daps_driver,
+            dat_verifier: None,
+            daps_interface,
+            handshake_dat,
+            restart_prover_on_dat: false,
             prover_registry: Arc::new(prover_registry),
             verifier_registry: Arc::new(verifier_registry),
-            idscp_connection: Weak::new(),
-            connection_available_var: Arc::new((Mutex::new(false), Condvar::new())),
+            idscp_connection: None,
             handshake_cond,
             handshake_result_available: false,
             rat_config,
             ack_flag: AckFlag::Inactive,
-            ack_timer: StaticTimer::new(ack_timeout),
+            ack_timer: StaticTimer::new(ack_timeout, event_tx.clone()),
+            event_tx,
             expected_alternating_bit: AlternatingBit::new(),
             next_send_alternating_bit: AlternatingBit::new(),
-        }));
+            peer_rat_timeout,
+            skip_rat_on_dat_renewal: false,
+            re_rat_outbound_buffer: VecDeque::new(),
+            re_rat_inbound_data: None,
+            re_rat_limiter,
+            dat_expired_limiter,
+        };
 
-        prover.lock().unwrap().fsm = Arc::downgrade(&fsm);
-        verifier.lock().unwrap().fsm = Arc::downgrade(&fsm);
-        {
-            let mut sc_interface_guard = sc_interface.lock().unwrap();
-            sc_interface_guard.fsm = Arc::downgrade(&fsm);
-            sc_interface_guard.init(secure_channel, Arc::downgrade(&sc_interface));
-        }
+        (fsm, queue)
+    }
 
-        {
-            let mut guard = fsm.lock().unwrap();
-            (*guard).handshake_timer.set_fsm(Arc::downgrade(&fsm));
-            (*guard).prover_timer.set_fsm(Arc::downgrade(&fsm));
-            (*guard).verifier_timer.set_fsm(Arc::downgrade(&fsm));
-            (*guard).dat_timer.set_fsm(Arc::downgrade(&fsm));
-            (*guard).rat_timer.set_fsm(Arc::downgrade(&fsm));
-            (*guard).ack_timer.set_fsm(Arc::downgrade(&fsm));
-        }
-        fsm
+    // set the channel to the upper layer, None if messages and closure should not be delivered
+    pub fn set_connection(&mut self, connection: Option<Sender<IdscpEvent>>) {
+        self.idscp_connection = connection;
     }
 
-    pub fn set_connection(&mut self, connection: Option<Weak<Mutex<InnerIdscp2connection>>>) {
-        //set connection if available
-        match connection {
-            Some(c) => self.idscp_connection = c,
-            None => {}
-        }
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn FsmObserver + Send + Sync>>) {
+        self.observer = observer;
+    }
 
-        //set connection available variable
-        let &(ref lock, ref cvar) = &*self.connection_available_var;
-        match lock.lock() {
-            Err(e) => {
-                log::error!("Cannot acquire connection_available lock: {}", e);
-            }
+    // verifier for the dats of the peer, None to verify them with the daps driver
+    pub fn set_dat_verifier(&mut self, verifier: Option<Arc<dyn TokenVerifier + Send + Sync>>) {
+        self.dat_verifier = verifier;
+    }
 
-            Ok(mut guard) => {
-                (*guard) = true;
-            }
-        }
-        cvar.notify_all();
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
     }
 
     pub fn feed_user_event(&mut self, e: UserEvent) -> Result<(), FsmError> {
@@ -263,11 +401,26 @@ impl FiniteStateMachine {
         self.process_event(event)
     }
 
-    pub fn is_closed(&self) -> bool {
-        match self.current_state {
-            FsmState::Closed(_) => true,
-            _ => false,
-        }
+    // feed a message of the peer directly into the fsm, bypassing the secure channel listener.
+    // This is used for replaying recorded sessions
+    pub(crate) fn feed_peer_message(
+        &mut self,
+        msg: IdscpMessage_oneof_message,
+    ) -> Result<(), FsmError> {
+        let event = FsmEvent::FromSecureChannel(SecureChannelEvent::from(msg));
+        self.process_event(event)
+    }
+
+    pub(crate) fn state_name(&self) -> String {
+        self.current_state.name().to_string()
+    }
+
+    fn is_locked(&self) -> bool {
+        self.current_state == FsmState::Closed(ClosedStateStatus::Locked)
+    }
+
+    pub(crate) fn security_report(&self) -> Arc<Mutex<SecurityReport>> {
+        Arc::clone(&self.security)
     }
 
     pub fn is_connected(&self) -> bool {
@@ -279,10 +432,13 @@ impl FiniteStateMachine {
     }
 
     fn process_event(&mut self, event: FsmEvent) -> Result<(), FsmError> {
-        log::info!(
-            "FSM triggered by event{:?} in state {:?}",
-            event,
-            self.current_state
+        let old_state = self.current_state.name();
+        let event_kind = event.kind();
+        log::debug!(
+            "FSM of connection {} triggered by event {} in state {}",
+            self.connection_id,
+            event_kind,
+            old_state
         );
 
         use ClosedStateStatus::*;
@@ -320,7 +476,7 @@ impl FiniteStateMachine {
                             }
                         }
 
-                        FromUpper(UserEvent::RepeatRat)
+                        FromUpper(UserEvent::RepeatRat(_))
                         | FromUpper(UserEvent::Data(_))
                         | FromUpper(UserEvent::Stop) => {
                             log::warn!(
@@ -337,6 +493,12 @@ impl FiniteStateMachine {
                 }
             }
 
+            // the own dat and renewed dats of the peer are handled the same way in all states of
+            // an active connection
+            _ if self.is_state_independent(&event) => {
+                res = self.process_state_independent_event(event);
+            }
+
             WaitForHello => match event {
                 FromUpper(UserEvent::Stop) => {
                     self.action_stop();
@@ -349,7 +511,7 @@ impl FiniteStateMachine {
                     res = Err(FsmError::NotConnected);
                 }
 
-                FromUpper(UserEvent::RepeatRat) => {
+                FromUpper(UserEvent::RepeatRat(_)) => {
                     //nothing to do, res should be OK(()) since Rat will be done in the next state
                     // for the first time
                 }
@@ -412,11 +574,11 @@ impl FiniteStateMachine {
                     self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                 }
 
-                FromUpper(UserEvent::Data(_)) => {
-                    res = Err(FsmError::NotConnected);
-                }
+                FromUpper(UserEvent::Data(msg)) => res = self.handle_data_during_re_rat(msg),
+
+                AckTimeout => res = self.handle_ack_timeout_during_re_rat(),
 
-                FromUpper(UserEvent::RepeatRat) => {
+                FromUpper(UserEvent::RepeatRat(_)) => {
                     //nothing to do, res should be OK(()) since Rat will be done in the next state
                     // for the first time
                 }
@@ -443,14 +605,14 @@ impl FiniteStateMachine {
                 },
 
                 FromRatProver(msg) => match msg {
-                    RatMessage::ControlMessage(RatIcm::OK) => {
-                        log::debug!("Received RatProverOK");
+                    RatMessage::ControlMessage(RatIcm::OK(result)) => {
+                        self.action_rat_prover_ok(result);
                         self.prover_timer.cancel();
                         self.current_state = WaitForRatVerifier;
                     }
 
-                    RatMessage::ControlMessage(RatIcm::Failed) => {
-                        self.action_rat_prover_failed();
+                    RatMessage::ControlMessage(RatIcm::Failed(reason)) => {
+                        self.action_rat_prover_failed(reason);
                         self.cleanup();
                         self.notify_connection_about_close();
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
@@ -469,15 +631,15 @@ impl FiniteStateMachine {
                 },
 
                 FromRatVerifier(msg) => match msg {
-                    RatMessage::ControlMessage(RatIcm::OK) => {
-                        log::debug!("Received RatVerifierOk");
+                    RatMessage::ControlMessage(RatIcm::OK(result)) => {
+                        self.action_rat_verifier_ok(result);
                         self.verifier_timer.cancel();
-                        self.rat_timer.start();
+                        self.start_rat_timer();
                         self.current_state = WaitForRatProver;
                     }
 
-                    RatMessage::ControlMessage(RatIcm::Failed) => {
-                        self.action_rat_verifier_failed();
+                    RatMessage::ControlMessage(RatIcm::Failed(reason)) => {
+                        self.action_rat_verifier_failed(reason);
                         self.cleanup();
                         self.notify_connection_about_close();
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
@@ -543,6 +705,8 @@ impl FiniteStateMachine {
                         }
                     }
 
+                    SecureChannelEvent::Data(data) => self.action_recv_data_during_re_rat(data),
+
                     SecureChannelEvent::Ack(ack_data) => {
                         match self.action_recv_ack(ack_data) {
                             Ok(_) => {
@@ -575,7 +739,8 @@ impl FiniteStateMachine {
                     self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                 }
 
-                FromUpper(UserEvent::RepeatRat) | RatTimeout => match self.action_re_rat() {
+                FromUpper(UserEvent::RepeatRat(_)) | RatTimeout => match self.action_re_rat(&event)
+                {
                     Err(e) => {
                         log::warn!("Error occurred during re_rat handling: {}", e);
                         self.cleanup();
@@ -587,9 +752,9 @@ impl FiniteStateMachine {
                     Ok(_) => self.current_state = FsmState::WaitForRat,
                 },
 
-                FromUpper(UserEvent::Data(_)) => {
-                    res = Err(FsmError::NotConnected);
-                }
+                FromUpper(UserEvent::Data(msg)) => res = self.handle_data_during_re_rat(msg),
+
+                AckTimeout => res = self.handle_ack_timeout_during_re_rat(),
 
                 HandshakeTimeout => {
                     self.handshake_timeout_handler();
@@ -613,8 +778,8 @@ impl FiniteStateMachine {
                 },
 
                 FromRatProver(msg) => match msg {
-                    RatMessage::ControlMessage(RatIcm::OK) => {
-                        log::debug!("Received RatProverOK");
+                    RatMessage::ControlMessage(RatIcm::OK(result)) => {
+                        self.action_rat_prover_ok(result);
                         self.prover_timer.cancel();
                         self.current_state = match self.ack_flag {
                             AckFlag::Inactive => Established,
@@ -625,8 +790,8 @@ impl FiniteStateMachine {
                         };
                     }
 
-                    RatMessage::ControlMessage(RatIcm::Failed) => {
-                        self.action_rat_prover_failed();
+                    RatMessage::ControlMessage(RatIcm::Failed(reason)) => {
+                        self.action_rat_prover_failed(reason);
                         self.cleanup();
                         self.notify_connection_about_close();
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
@@ -695,6 +860,8 @@ impl FiniteStateMachine {
                         }
                     }
 
+                    SecureChannelEvent::Data(data) => self.action_recv_data_during_re_rat(data),
+
                     SecureChannelEvent::Ack(ack_data) => {
                         match self.action_recv_ack(ack_data) {
                             Ok(_) => {
@@ -727,11 +894,11 @@ impl FiniteStateMachine {
                     self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                 }
 
-                FromUpper(UserEvent::Data(_)) => {
-                    res = Err(FsmError::NotConnected);
-                }
+                FromUpper(UserEvent::Data(msg)) => res = self.handle_data_during_re_rat(msg),
+
+                AckTimeout => res = self.handle_ack_timeout_during_re_rat(),
 
-                FromUpper(UserEvent::RepeatRat) => {
+                FromUpper(UserEvent::RepeatRat(_)) => {
                     //nothing to do, res should be OK(()) since Rat will be done in the next state
                     // for the first time
                 }
@@ -758,10 +925,10 @@ impl FiniteStateMachine {
                 },
 
                 FromRatVerifier(msg) => match msg {
-                    RatMessage::ControlMessage(RatIcm::OK) => {
-                        log::debug!("Received RatVerifierOk");
+                    RatMessage::ControlMessage(RatIcm::OK(result)) => {
+                        self.action_rat_verifier_ok(result);
                         self.verifier_timer.cancel();
-                        self.rat_timer.start();
+                        self.start_rat_timer();
                         self.current_state = match self.ack_flag {
                             AckFlag::Inactive => Established,
                             AckFlag::Active(_) => {
@@ -771,8 +938,8 @@ impl FiniteStateMachine {
                         };
                     }
 
-                    RatMessage::ControlMessage(RatIcm::Failed) => {
-                        self.action_rat_verifier_failed();
+                    RatMessage::ControlMessage(RatIcm::Failed(reason)) => {
+                        self.action_rat_verifier_failed(reason);
                         self.cleanup();
                         self.notify_connection_about_close();
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
@@ -841,6 +1008,8 @@ impl FiniteStateMachine {
                         }
                     },
 
+                    SecureChannelEvent::Data(data) => self.action_recv_data_during_re_rat(data),
+
                     SecureChannelEvent::Ack(ack_data) => {
                         match self.action_recv_ack(ack_data) {
                             Ok(_) => {
@@ -877,9 +1046,10 @@ impl FiniteStateMachine {
                     res = Err(FsmError::NotConnected);
                 }
 
-                FromUpper(UserEvent::RepeatRat) => {
+                FromUpper(UserEvent::RepeatRat(_)) => {
                     //nothing to do, res should be OK(()) since Rat will be done in the next state
                     // for the first time
+                    self.skip_rat_on_dat_renewal = false;
                 }
 
                 HandshakeTimeout => {
@@ -890,14 +1060,14 @@ impl FiniteStateMachine {
                 }
 
                 FromRatProver(msg) => match msg {
-                    RatMessage::ControlMessage(RatIcm::OK) => {
-                        log::debug!("Received RatProverOK");
+                    RatMessage::ControlMessage(RatIcm::OK(result)) => {
+                        self.action_rat_prover_ok(result);
                         self.prover_timer.cancel();
                         self.current_state = WaitForDatAndRatVerifier;
                     }
 
-                    RatMessage::ControlMessage(RatIcm::Failed) => {
-                        self.action_rat_prover_failed();
+                    RatMessage::ControlMessage(RatIcm::Failed(reason)) => {
+                        self.action_rat_prover_failed(reason);
                         self.cleanup();
                         self.notify_connection_about_close();
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
@@ -951,9 +1121,12 @@ impl FiniteStateMachine {
                             self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                             res = Err(e);
                         }
-                        Ok(_) => {
+                        Ok(true) => {
                             self.current_state = FsmState::WaitForRat;
                         }
+                        Ok(false) => {
+                            self.current_state = FsmState::WaitForRatProver;
+                        }
                     },
 
                     SecureChannelEvent::RatVerifier(data) => {
@@ -1015,9 +1188,10 @@ impl FiniteStateMachine {
                     res = Err(FsmError::NotConnected);
                 }
 
-                FromUpper(UserEvent::RepeatRat) => {
+                FromUpper(UserEvent::RepeatRat(_)) => {
                     //nothing to do, res should be OK(()) since Rat will be done in the next state
                     // for the first time
+                    self.skip_rat_on_dat_renewal = false;
                 }
 
                 HandshakeTimeout => {
@@ -1063,9 +1237,18 @@ impl FiniteStateMachine {
                             self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                             res = Err(e);
                         }
-                        Ok(_) => {
+                        Ok(true) => {
                             self.current_state = FsmState::WaitForRatVerifier;
                         }
+                        Ok(false) => {
+                            self.current_state = match self.ack_flag {
+                                AckFlag::Inactive => Established,
+                                AckFlag::Active(_) => {
+                                    self.ack_timer.start();
+                                    WaitForAck
+                                }
+                            };
+                        }
                     },
 
                     SecureChannelEvent::ReRat(data) => match self.action_recv_re_rat(data) {
@@ -1117,20 +1300,22 @@ impl FiniteStateMachine {
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                     }
 
-                    FromUpper(UserEvent::RepeatRat) | RatTimeout => match self.action_re_rat() {
-                        Err(e) => {
-                            log::warn!("Error occurred during re_rat handling: {}", e);
-                            self.cleanup();
-                            self.notify_connection_about_close(); // inspected: no deadlock, asynchronous notification to the connection
-                            self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
-                            res = Err(e);
-                        }
+                    FromUpper(UserEvent::RepeatRat(_)) | RatTimeout => {
+                        match self.action_re_rat(&event) {
+                            Err(e) => {
+                                log::warn!("Error occurred during re_rat handling: {}", e);
+                                self.cleanup();
+                                self.notify_connection_about_close(); // inspected: no deadlock, asynchronous notification to the connection
+                                self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
+                                res = Err(e);
+                            }
 
-                        Ok(_) => {
-                            self.ack_timer.cancel();
-                            self.current_state = FsmState::WaitForRatVerifier
+                            Ok(_) => {
+                                self.ack_timer.cancel();
+                                self.current_state = FsmState::WaitForRatVerifier
+                            }
                         }
-                    },
+                    }
 
                     FromUpper(UserEvent::Data(_)) => {
                         log::warn!("Cannot send data in WaitForAck state");
@@ -1149,6 +1334,7 @@ impl FiniteStateMachine {
                         Ok(_) => {
                             self.ack_timer.cancel();
                             self.handshake_timer.start();
+                            self.skip_rat_on_dat_renewal = !self.rat_config.re_rat_on_dat_renewal;
                             self.current_state = WaitForDatAndRatVerifier;
                         }
                     },
@@ -1250,17 +1436,19 @@ impl FiniteStateMachine {
                         self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
                     }
 
-                    FromUpper(UserEvent::RepeatRat) | RatTimeout => match self.action_re_rat() {
-                        Err(e) => {
-                            log::warn!("Error occurred during re_rat handling: {}", e);
-                            self.cleanup();
-                            self.notify_connection_about_close(); // inspected: no deadlock, asynchronous notification to the connection
-                            self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
-                            res = Err(e);
-                        }
+                    FromUpper(UserEvent::RepeatRat(_)) | RatTimeout => {
+                        match self.action_re_rat(&event) {
+                            Err(e) => {
+                                log::warn!("Error occurred during re_rat handling: {}", e);
+                                self.cleanup();
+                                self.notify_connection_about_close(); // inspected: no deadlock, asynchronous notification to the connection
+                                self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
+                                res = Err(e);
+                            }
 
-                        Ok(_) => self.current_state = FsmState::WaitForRatVerifier,
-                    },
+                            Ok(_) => self.current_state = FsmState::WaitForRatVerifier,
+                        }
+                    }
 
                     FromUpper(UserEvent::Data(msg)) => {
                         match self.action_send_data(msg.clone()) {
@@ -1290,6 +1478,7 @@ impl FiniteStateMachine {
                         }
                         Ok(_) => {
                             self.handshake_timer.start();
+                            self.skip_rat_on_dat_renewal = !self.rat_config.re_rat_on_dat_renewal;
                             self.current_state = WaitForDatAndRatVerifier;
                         }
                     },
@@ -1351,6 +1540,17 @@ impl FiniteStateMachine {
             }
         };
 
+        // flush data that has been buffered during re-attestation
+        if self.is_connected() {
+            if let Err(e) = self.action_flush_re_rat_buffers() {
+                log::warn!("Error occurred during sending buffered data");
+                self.cleanup();
+                self.notify_connection_about_close();
+                self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
+                res = Err(e);
+            }
+        }
+
         //set handshake result
         let handshake_cond_clone = Arc::clone(&self.handshake_cond);
         let set_handshake_result = move |result: HandshakeResult| {
@@ -1385,11 +1585,26 @@ impl FiniteStateMachine {
             }
         }
 
-        log::info!(
-            "After processing event, FSM is in state: {:?}",
-            self.current_state
+        log::debug!(
+            "After processing event, FSM of connection {} is in state: {}",
+            self.connection_id,
+            self.current_state.name()
         );
 
+        if let Some(observer) = &self.observer {
+            observer.on_transition(&FsmTransition {
+                connection_id: self.connection_id,
+                old_state,
+                event: event_kind,
+                new_state: self.current_state.name(),
+                timestamp: SystemTime::now(),
+                outcome: match &res {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                },
+            });
+        }
+
         //return result
         res
     } //end of process_event
@@ -1398,14 +1613,21 @@ impl FiniteStateMachine {
         log::debug!("Starting IDSCP2 Handshake ...");
 
         //unlock secure channel listener
-        let _ = self.sc_interface.lock().unwrap().unlock();
-
-        //get dat from daps
-        let dat = self.daps_driver.get_token();
+        let _ = self.sc_interface.unlock();
+
+        //dat was requested from the daps in advance
+        let dat = match self.handshake_dat.take() {
+            Some(Ok(dat)) => dat,
+            Some(Err(e)) => {
+                self.action_daps_failed(&e);
+                return Err(FsmError::DapsError(e));
+            }
+            None => return Err(FsmError::MissingDat),
+        };
 
         //create idscp_hello msg
         let idscp_hello = idscp_message_factory::create_idscp_hello(
-            dat.into_bytes(),
+            dat.token.clone().into_bytes(),
             &self.rat_config.expected_attestation_suite,
             &self.rat_config.supported_attestation_suite,
         );
@@ -1413,22 +1635,24 @@ impl FiniteStateMachine {
         //send idscp hello via secure channel
         let mut data = Vec::new();
         let _ = idscp_hello.write_to_vec(&mut data);
-        match self.sc_interface.lock().unwrap().write(data) {
-            Err(e) => Err(FsmError::IoError(e)),
-            Ok(_) => Ok(()),
+        if let Err(e) = self.sc_interface.write(data) {
+            return Err(FsmError::IoError(e));
         }
+
+        self.start_dat_renewal_timer(&dat);
+        Ok(())
     }
 
     fn dat_timeout_handler(&mut self) -> Result<(), FsmError> {
         log::debug!("Dat timeout occurred. Send IdscpDatExpired");
-        self.rat_verifier.lock().unwrap().stop_driver();
+        self.rat_verifier.stop_driver();
         self.rat_timer.cancel();
 
         //send IdscpDatExpired
         let idscp_dat_exp = idscp_message_factory::create_idscp_dat_exp();
         let mut data = Vec::new();
         let _ = idscp_dat_exp.write_to_vec(&mut data);
-        match self.sc_interface.lock().unwrap().write(data) {
+        match self.sc_interface.write(data) {
             Err(e) => Err(FsmError::IoError(e)),
             Ok(_) => Ok(()),
         }
@@ -1444,7 +1668,7 @@ impl FiniteStateMachine {
         );
         let mut data = Vec::new();
         let _ = idscp_close.write_to_vec(&mut data);
-        let _ = self.sc_interface.lock().unwrap().write(data);
+        let _ = self.sc_interface.write(data);
     }
 
     fn action_stop(&mut self) {
@@ -1459,8 +1683,7 @@ impl FiniteStateMachine {
         let mut data = Vec::new();
         let _ = idscp_close.write_to_vec(&mut data);
 
-        let sc = self.sc_interface.lock().unwrap();
-        if let Err(e) = sc.write(data) {
+        if let Err(e) = self.sc_interface.write(data) {
             log::debug!("Cannot send IdscpClose: {}", e)
         }
     }
@@ -1525,75 +1748,43 @@ impl FiniteStateMachine {
         let prover_mechanism = FiniteStateMachine::calculate_rat_prover_mechanism(
             &peer_expected,
             &own_supported_provers,
-        )?;
+        )?
+        .to_string();
 
         let own_expected_verifiers = &self.rat_config.expected_attestation_suite;
         let peer_supported = hello.get_supportedRatSuite().to_vec();
         let verifier_mechanism = FiniteStateMachine::calculate_rat_verifier_mechanism(
             &peer_supported,
             &own_expected_verifiers,
-        )?;
-
-        let send_close = || {
-            let idscp_close = idscp_message_factory::create_idscp_close(
-                IdscpClose_CloseCause::NO_VALID_DAT,
-                "No valid dat",
-            );
-            let mut data = Vec::new();
-            let _ = idscp_close.write_to_vec(&mut data);
-            let _ = self.sc_interface.lock().unwrap().write(data);
-        };
+        )?
+        .to_string();
 
         //get DAT from hello and verify DAT
-        let remote_dat = match hello.dynamicAttributeToken.into_option() {
+        match hello.dynamicAttributeToken.into_option() {
             None => {
                 log::warn!("No dat available. Send Close and close connection");
-                send_close();
+                self.action_send_no_valid_dat();
                 return Err(FsmError::MissingDat);
             }
-            Some(dat) => match String::from_utf8(dat.token.to_vec()) {
-                Err(_) => {
-                    log::warn!("Cannot parse dat. Send close and close connection");
-                    send_close();
-                    return Err(FsmError::InvalidDat);
-                }
-                Ok(token) => token,
-            },
-        };
-
-        match self.daps_driver.verify_token(&remote_dat) {
-            None => {
-                log::warn!("Dat is not valid. Send close and close connection");
-                send_close();
-                return Err(FsmError::InvalidDat);
-            }
-
-            Some(t) => {
-                log::debug!("Dat is valid. Start dat timer");
-                self.dat_timer.start(t);
-            }
+            Some(dat) => self.verify_dat(dat)?,
         }
 
         // start rat verifier
         log::debug!("Start rat prover and verifier");
-        let mut verifier_guard = self.rat_verifier.lock().unwrap();
-        if let Err(e) = (*verifier_guard).start_driver(
-            &verifier_mechanism,
-            Arc::downgrade(&self.verifier_registry),
-            Arc::clone(&self.rat_verifier),
-        ) {
+        if let Err(e) = self
+            .rat_verifier
+            .start_driver(&verifier_mechanism, &self.verifier_registry)
+        {
             log::error!("Cannot start RatVerifier driver");
             return Err(FsmError::RatError(e));
         }
         self.verifier_timer.start();
 
         // start rat prover
-        let mut prover_guard = self.rat_prover.lock().unwrap();
-        if let Err(e) = (*prover_guard).start_driver(
-            &prover_mechanism,
-            Arc::downgrade(&self.prover_registry),
-            Arc::clone(&self.rat_prover),
-        ) {
+        if let Err(e) = self
+            .rat_prover
+            .start_driver(&prover_mechanism, &self.prover_registry)
+        {
             log::error!("Cannot start RatProver driver");
             return Err(FsmError::RatError(e));
         }
@@ -1607,7 +1798,7 @@ impl FiniteStateMachine {
             idscp_message_factory::create_idscp_data(data, &self.next_send_alternating_bit);
         let mut raw = Vec::new();
         let _ = idscp_data.write_to_vec(&mut raw);
-        match self.sc_interface.lock().unwrap().write(raw) {
+        match self.sc_interface.write(raw) {
             Err(e) => Err(FsmError::IoError(e)),
             Ok(_) => Ok(()),
         }
@@ -1615,37 +1806,144 @@ impl FiniteStateMachine {
 
     fn action_recv_data(&mut self, data: IdscpData) {
         log::debug!("Receive new message for connection (if connection available)");
+        if self.action_ack_data(&data) {
+            self.deliver_data(Vec::from(data.get_data()));
+        }
+    }
+
+    // acknowledges the data if it has the expected alternating bit, returns false for old packets
+    fn action_ack_data(&mut self, data: &IdscpData) -> bool {
         let recv_alternating_bit = AlternatingBit::from_bool(data.alternating_bit);
         if recv_alternating_bit != self.expected_alternating_bit {
             log::debug!("received IDSCPData with unexpected alternating bit. Could be an old packet replayed. Ignoring it.");
-        } else {
-            // send IdscpAck
-            let idscp_ack = idscp_message_factory::create_idscp_ack(recv_alternating_bit);
-            let mut raw = Vec::new();
-            let _ = idscp_ack.write_to_vec(&mut raw);
-            if self.sc_interface.lock().unwrap().write(raw).is_err() {
-                log::error!("Cannot send IdscpAck");
+            return false;
+        }
+
+        // send IdscpAck
+        let idscp_ack = idscp_message_factory::create_idscp_ack(recv_alternating_bit);
+        let mut raw = Vec::new();
+        let _ = idscp_ack.write_to_vec(&mut raw);
+        if self.sc_interface.write(raw).is_err() {
+            log::error!("Cannot send IdscpAck");
+        }
+        self.expected_alternating_bit.alternate();
+        true
+    }
+
+    fn deliver_data(&self, data: Vec<u8>) {
+        // forward payload data to upper layer
+        match &self.idscp_connection {
+            None => {
+                log::warn!("No connection available");
+            }
+            Some(tx) => {
+                if tx.send(IdscpEvent::Message(data)).is_err() {
+                    log::warn!("Connection was dropped, discard message");
+                }
+            }
+        }
+    }
+
+    // user data while an established connection is re-attested, see ReRatDataPolicy
+    fn handle_data_during_re_rat(&mut self, data: Vec<u8>) -> Result<(), FsmError> {
+        match self.action_send_data_during_re_rat(data) {
+            Err(FsmError::IoError(e)) => {
+                log::warn!("Error occurred during sending data");
+                self.fail_and_lock(FsmError::IoError(e))
+            }
+            r => r,
+        }
+    }
+
+    // unacknowledged data is only repeated during re-attestation if data is exchanged as usual
+    fn handle_ack_timeout_during_re_rat(&mut self) -> Result<(), FsmError> {
+        if self.rat_config.re_rat_data_policy != ReRatDataPolicy::Continue {
+            log::warn!("No transition available for AckTimeout during re-attestation");
+            return Err(FsmError::UnknownTransition);
+        }
+        if let AckFlag::Active(data) = self.ack_flag.clone() {
+            if let Err(e) = self.action_send_data(data) {
+                log::warn!("Error occurred during sending data");
+                return self.fail_and_lock(e);
             }
-            self.expected_alternating_bit.alternate();
+            self.ack_timer.start();
+        }
+        Ok(())
+    }
 
-            // forward payload data to upper layer
-            self.wait_for_connection_available();
-            match self.idscp_connection.upgrade() {
-                None => {
-                    log::warn!("No connection available");
+    fn action_send_data_during_re_rat(&mut self, data: Vec<u8>) -> Result<(), FsmError> {
+        if !self.handshake_result_available {
+            return Err(FsmError::NotConnected);
+        }
+        match self.rat_config.re_rat_data_policy {
+            ReRatDataPolicy::Block => Err(FsmError::NotConnected),
+            ReRatDataPolicy::Continue => match self.ack_flag {
+                AckFlag::Active(_) => Err(FsmError::WouldBlock),
+                AckFlag::Inactive => {
+                    self.action_send_data(data.clone())?;
+                    self.ack_flag = AckFlag::Active(data);
+                    self.ack_timer.start();
+                    Ok(())
                 }
-                Some(c_lock) => {
-                    log::debug!("try to aquire lock to connection");
-                    let lock_result = c_lock.lock();
-                    match lock_result {
-                        Err(e) => {
-                            log::warn!("Cannot acquire read lock: {}", e);
-                        }
-                        Ok(c_guard) => {
-                            (*c_guard).on_message(Vec::from(data.get_data()));
-                        }
-                    }
+            },
+            ReRatDataPolicy::Buffer { max_messages } => {
+                if self.re_rat_outbound_buffer.len() >= max_messages {
+                    return Err(FsmError::WouldBlock);
                 }
+                log::debug!("Buffer data until re-attestation has been completed");
+                self.re_rat_outbound_buffer.push_back(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn action_recv_data_during_re_rat(&mut self, data: IdscpData) {
+        if !self.handshake_result_available {
+            log::debug!("Drop IdscpData received before the handshake has been completed");
+            return;
+        }
+        match self.rat_config.re_rat_data_policy {
+            ReRatDataPolicy::Continue => self.action_recv_data(data),
+            ReRatDataPolicy::Buffer { .. }
+                if AlternatingBit::from_bool(data.alternating_bit)
+                    == self.expected_alternating_bit =>
+            {
+                // the data is acknowledged when it is delivered, until then the peer repeats it
+                log::debug!("Hold received data until re-attestation has been completed");
+                self.re_rat_inbound_data = Some(data);
+            }
+            _ => {
+                // the data is not acknowledged, so the peer repeats it after re-attestation
+                log::debug!("Drop IdscpData received during re-attestation");
+            }
+        }
+    }
+
+    // delivers and sends the data that has been buffered during re-attestation, once the
+    // connection is established again. Outbound data is sent message by message, each after the
+    // previous one has been acknowledged
+    fn action_flush_re_rat_buffers(&mut self) -> Result<(), FsmError> {
+        if let Some(data) = self.re_rat_inbound_data.take() {
+            self.action_recv_data(data);
+        }
+        if self.current_state == FsmState::Established {
+            if let Some(data) = self.re_rat_outbound_buffer.pop_front() {
+                self.action_send_data(data.clone())?;
+                self.ack_flag = AckFlag::Active(data);
+                self.ack_timer.start();
+                self.current_state = FsmState::WaitForAck;
+            }
+        }
+        Ok(())
+    }
+
+    // data of the user that was buffered during re-attestation will never be sent
+    fn action_discard_re_rat_buffers(&mut self) {
+        self.re_rat_inbound_data = None;
+        for data in self.re_rat_outbound_buffer.drain(..) {
+            log::warn!("Discard data buffered during re-attestation");
+            if let Some(tx) = &self.idscp_connection {
+                let _ = tx.send(IdscpEvent::MessageDiscarded(data));
             }
         }
     }
@@ -1672,21 +1970,54 @@ impl FiniteStateMachine {
         }
     }
 
-    fn action_re_rat(&mut self) -> Result<(), FsmError> {
+    // interval until the next periodic re-attestation, including a random jitter
+    fn next_rat_interval(&self) -> Duration {
+        let interval = match self.peer_rat_timeout {
+            Some(t) => t,
+            None => self
+                .rat_verifier
+                .driver_id()
+                .and_then(|id| self.rat_config.rat_timeouts_per_mechanism.get(id).cloned())
+                .unwrap_or(self.rat_config.rat_timeout),
+        };
+
+        let jitter = self.rat_config.rat_timeout_jitter;
+        if jitter.as_nanos() == 0 {
+            return interval;
+        }
+        let mut random = [0u8; 8];
+        if openssl::rand::rand_bytes(&mut random).is_err() {
+            log::warn!("Cannot generate random rat interval jitter");
+            return interval;
+        }
+        let offset = u128::from(u64::from_be_bytes(random)) % (2 * jitter.as_nanos() + 1);
+        interval.checked_sub(jitter).unwrap_or_default() + Duration::from_nanos(offset as u64)
+    }
+
+    fn start_rat_timer(&mut self) {
+        let interval = self.next_rat_interval();
+        log::debug!("Next re-attestation in {:?}", interval);
+        self.rat_timer.start(interval);
+    }
+
+    fn action_re_rat(&mut self, event: &FsmEvent) -> Result<(), FsmError> {
         log::debug!("Repeat Rat. Send IdscpReRat and start RatVerifier");
         self.rat_timer.cancel();
 
         //send idscp re-rat
-        let idscp_rerat = idscp_message_factory::create_idscp_re_rat("");
+        let cause = match event {
+            FsmEvent::FromUpper(UserEvent::RepeatRat(cause)) => cause.as_str(),
+            _ => "RAT interval expired",
+        };
+        let idscp_rerat = idscp_message_factory::create_idscp_re_rat(cause);
         let mut raw = Vec::new();
         let _ = idscp_rerat.write_to_vec(&mut raw);
-        if let Err(e) = self.sc_interface.lock().unwrap().write(raw) {
+        if let Err(e) = self.sc_interface.write(raw) {
             return Err(FsmError::IoError(e));
         }
 
         //start verifier
-        let mut verifier_guard = self.rat_verifier.lock().unwrap();
-        if let Err(e) = (*verifier_guard).restart_driver(Arc::clone(&self.rat_verifier)) {
+        if let Err(e) = self.rat_verifier.restart_driver() {
             log::error!("Cannot restart RatVerifier driver");
             return Err(FsmError::RatError(e));
         }
@@ -1700,8 +2031,12 @@ impl FiniteStateMachine {
             _data.cause
         );
 
-        let mut prover_guard = self.rat_prover.lock().unwrap();
-        if let Err(e) = (*prover_guard).restart_driver(Arc::clone(&self.rat_prover)) {
+        if !self.re_rat_limiter.allow() {
+            self.action_rate_limit_exceeded(SecurityEventKind::ReRatRateLimitExceeded);
+            return Err(FsmError::RateLimitExceeded("IdscpReRat"));
+        }
+
+        if let Err(e) = self.rat_prover.restart_driver() {
             log::error!("Cannot restart RatProver driver");
             return Err(FsmError::RatError(e));
         }
@@ -1710,19 +2045,52 @@ impl FiniteStateMachine {
         Ok(())
     }
 
-    fn action_rat_prover_failed(&mut self) {
-        log::debug!("Received RatProver Failed");
+    // the peer requested too many re-attestations or dats, report it and send IdscpClose
+    fn action_rate_limit_exceeded(&mut self, kind: SecurityEventKind) {
+        log::warn!("Peer exceeded rate limit: {}", kind.name());
+
+        let cause_msg = match kind {
+            SecurityEventKind::ReRatRateLimitExceeded => "Rate limit of IdscpReRat exceeded",
+            SecurityEventKind::DatExpiredRateLimitExceeded => {
+                "Rate limit of IdscpDatExpired exceeded"
+            }
+        };
+
+        if let Some(observer) = &self.observer {
+            observer.on_security_event(&SecurityEvent {
+                connection_id: self.connection_id,
+                kind,
+                timestamp: SystemTime::now(),
+            });
+        }
+
+        let idscp_close =
+            idscp_message_factory::create_idscp_close(IdscpClose_CloseCause::ERROR, cause_msg);
+        let mut data = Vec::new();
+        let _ = idscp_close.write_to_vec(&mut data);
+        let _ = self.sc_interface.write(data);
+    }
+
+    fn action_rat_prover_ok(&mut self, result: RatResult) {
+        log::debug!("Received RatProverOK with claims {:?}", result.claims);
+        self.security.lock().unwrap().attestation.own = Some(result);
+    }
+
+    fn action_rat_prover_failed(&mut self, reason: String) {
+        log::warn!("RatProver failed: {}", reason);
 
         self.prover_timer.cancel();
 
         //send IdscpClose
+        let cause_msg = self.rat_failure_cause("RatProver failed", &reason);
         let idscp_close = idscp_message_factory::create_idscp_close(
             IdscpClose_CloseCause::RAT_PROVER_FAILED,
-            "RatProver failed",
+            &cause_msg,
         );
         let mut data = Vec::new();
         let _ = idscp_close.write_to_vec(&mut data);
-        let _ = self.sc_interface.lock().unwrap().write(data);
+        let _ = self.sc_interface.write(data);
+        self.security.lock().unwrap().attestation.failure = Some(RatFailure::Prover(reason));
     }
 
     fn action_rat_prover_data(&mut self, data: Vec<u8>) -> Result<(), FsmError> {
@@ -1731,25 +2099,41 @@ impl FiniteStateMachine {
         let idscp_prover = idscp_message_factory::create_idscp_rat_prover(data);
         let mut raw = Vec::new();
         let _ = idscp_prover.write_to_vec(&mut raw);
-        match self.sc_interface.lock().unwrap().write(raw) {
+        match self.sc_interface.write(raw) {
             Err(e) => Err(FsmError::IoError(e)),
             Ok(_) => Ok(()),
         }
     }
 
-    fn action_rat_verifier_failed(&mut self) {
-        log::debug!("Received RatVerifier Failed");
+    fn action_rat_verifier_ok(&mut self, result: RatResult) {
+        log::info!("Peer was attested with claims {:?}", result.claims);
+        self.security.lock().unwrap().attestation.peer = Some(result);
+    }
+
+    fn action_rat_verifier_failed(&mut self, reason: String) {
+        log::warn!("RatVerifier failed: {}", reason);
 
         self.verifier_timer.cancel();
 
         //send close
+        let cause_msg = self.rat_failure_cause("RatVerifier failed", &reason);
         let idscp_close = idscp_message_factory::create_idscp_close(
             IdscpClose_CloseCause::RAT_VERIFIER_FAILED,
-            "RatVerifier failed",
+            &cause_msg,
         );
         let mut data = Vec::new();
         let _ = idscp_close.write_to_vec(&mut data);
-        let _ = self.sc_interface.lock().unwrap().write(data);
+        let _ = self.sc_interface.write(data);
+        self.security.lock().unwrap().attestation.failure = Some(RatFailure::Verifier(reason));
+    }
+
+    // the reason of the driver is only disclosed to the peer if configured
+    fn rat_failure_cause(&self, cause: &str, reason: &str) -> String {
+        if self.rat_config.disclose_rat_failure_reason {
+            format!("{}: {}", cause, reason)
+        } else {
+            cause.to_string()
+        }
     }
 
     fn action_rat_verifier_data(&mut self, data: Vec<u8>) -> Result<(), FsmError> {
@@ -1758,7 +2142,7 @@ impl FiniteStateMachine {
         let idscp_verifier = idscp_message_factory::create_idscp_rat_verifier(data);
         let mut raw = Vec::new();
         let _ = idscp_verifier.write_to_vec(&mut raw);
-        match self.sc_interface.lock().unwrap().write(raw) {
+        match self.sc_interface.write(raw) {
             Err(e) => Err(FsmError::IoError(e)),
             Ok(_) => Ok(()),
         }
@@ -1766,8 +2150,10 @@ impl FiniteStateMachine {
 
     fn action_delegate_rat_prover(&mut self, data: IdscpRatProver) -> Result<(), FsmError> {
         log::debug!("Delegate received RatProver msg to RatVerifier");
-        let verifier_guard = self.rat_verifier.lock().unwrap();
-        match (*verifier_guard).write_to_driver(RatMessage::RawData(data.data.to_vec())) {
+        match self
+            .rat_verifier
+            .write_to_driver(RatMessage::RawData(data.data.to_vec()))
+        {
             Err(e) => Err(FsmError::RatError(e)),
             Ok(_) => Ok(()),
         }
@@ -1775,245 +2161,292 @@ impl FiniteStateMachine {
 
     fn action_delegate_rat_verifier(&mut self, data: IdscpRatVerifier) -> Result<(), FsmError> {
         log::debug!("Delegate received RatVerifier msg to RatProver");
-        let prover_guard = self.rat_prover.lock().unwrap();
-        match (*prover_guard).write_to_driver(RatMessage::RawData(data.data.to_vec())) {
+        match self
+            .rat_prover
+            .write_to_driver(RatMessage::RawData(data.data.to_vec()))
+        {
             Err(e) => Err(FsmError::RatError(e)),
             Ok(_) => Ok(()),
         }
     }
 
-    fn action_recv_dat(&mut self, data: IdscpDat) -> Result<(), FsmError> {
-        log::debug!("Receive IdscpDat. Verifying Dat ...");
-        self.handshake_timer.cancel();
-
-        let send_close = || {
-            let idscp_close = idscp_message_factory::create_idscp_close(
-                IdscpClose_CloseCause::NO_VALID_DAT,
-                "No valid dat",
-            );
-            let mut data = Vec::new();
-            let _ = idscp_close.write_to_vec(&mut data);
-            let _ = self.sc_interface.lock().unwrap().write(data);
-        };
-
-        //get DAT from hello and verify DAT
+    // verifies a dat received from the peer and restarts the dat timer with its validity
+    fn verify_dat(&mut self, data: IdscpDat) -> Result<(), FsmError> {
         let remote_dat = match String::from_utf8(data.token.to_vec()) {
             Err(_) => {
                 log::warn!("Cannot parse dat. Send close and close connection");
-                send_close();
+                self.action_send_no_valid_dat();
                 return Err(FsmError::InvalidDat);
             }
             Ok(token) => token,
         };
 
-        match self.daps_driver.verify_token(&remote_dat) {
-            None => {
-                log::warn!("Dat is not valid. Send close and close connection");
-                send_close();
-                return Err(FsmError::InvalidDat);
+        match self.verify_token(&remote_dat) {
+            Err(e) => {
+                log::warn!("{}. Send close and close connection", e);
+                self.action_send_no_valid_dat();
+                Err(FsmError::InvalidDat)
             }
 
-            Some(t) => {
+            Ok(verified) => {
                 log::debug!("Dat is valid. Start dat timer");
-                self.dat_timer.start(t);
+                self.dat_timer.start(verified.validity);
+                self.security.lock().unwrap().peer_dat_claims = Some(verified.claims);
+                Ok(())
             }
         }
+    }
 
-        log::debug!("Start RatVerifier");
-        let mut verifier_guard = self.rat_verifier.lock().unwrap();
-        if let Err(e) = (*verifier_guard).restart_driver(Arc::clone(&self.rat_verifier)) {
-            log::error!("Cannot restart RatVerifier driver");
-            return Err(FsmError::RatError(e));
+    fn action_send_no_valid_dat(&mut self) {
+        let idscp_close = idscp_message_factory::create_idscp_close(
+            IdscpClose_CloseCause::NO_VALID_DAT,
+            "No valid dat",
+        );
+        let mut data = Vec::new();
+        let _ = idscp_close.write_to_vec(&mut data);
+        let _ = self.sc_interface.write(data);
+    }
+
+    fn verify_token(&self, token: &str) -> Result<VerifiedDat, DapsError> {
+        match &self.dat_verifier {
+            Some(verifier) => verifier.verify_token(token, &self.peer_cert),
+            None => self.daps_driver.verify_token(token, &self.peer_cert),
         }
-        self.verifier_timer.start();
+    }
 
-        Ok(())
+    // own dat renewals and renewed dats of the peer do not depend on the progress of the
+    // handshake or the attestation
+    fn is_state_independent(&self, event: &FsmEvent) -> bool {
+        match event {
+            FsmEvent::DatRenewalTimeout | FsmEvent::FromDaps(_) => true,
+            // in the remaining states the dat is expected after IdscpDatExpired or IdscpHello
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Dat(_)) => matches!(
+                self.current_state,
+                FsmState::WaitForRat
+                    | FsmState::WaitForRatProver
+                    | FsmState::WaitForRatVerifier
+                    | FsmState::WaitForAck
+                    | FsmState::Established
+            ),
+            _ => false,
+        }
     }
 
-    fn action_recv_dat_exp(&mut self) -> Result<(), FsmError> {
-        log::debug!("Receive IdscpDatExpired. Send new Dat and start RatProver");
+    fn process_state_independent_event(&mut self, event: FsmEvent) -> Result<(), FsmError> {
+        match event {
+            FsmEvent::DatRenewalTimeout => {
+                self.action_renew_dat();
+                Ok(())
+            }
 
-        //send new Dat
-        let dat = self.daps_driver.get_token();
-        let idscp_dat = idscp_message_factory::create_idscp_dat(dat.into_bytes());
-        let mut raw = Vec::new();
-        let _ = idscp_dat.write_to_vec(&mut raw);
-        if let Err(e) = self.sc_interface.lock().unwrap().write(raw) {
-            log::error!("Cannot send IdscpDat");
-            return Err(FsmError::IoError(e));
-        }
+            FsmEvent::FromDaps(result) => match self.action_recv_own_dat(result) {
+                Err(e) => {
+                    log::warn!("Cannot provide own dat to the peer: {}", e);
+                    self.fail_and_lock(e)
+                }
+                Ok(_) => Ok(()),
+            },
 
-        let mut prover_guard = self.rat_prover.lock().unwrap();
-        if let Err(e) = (*prover_guard).restart_driver(Arc::clone(&self.rat_prover)) {
-            log::error!("Cannot restart RatProver driver");
-            return Err(FsmError::RatError(e));
-        }
-        self.prover_timer.start();
+            FsmEvent::FromSecureChannel(SecureChannelEvent::Dat(data)) => {
+                match self.action_recv_dat_renewal(data) {
+                    Err(e) => {
+                        log::warn!("Error occurred during validating renewed dat: {}", e);
+                        self.fail_and_lock(e)
+                    }
+                    Ok(_) => Ok(()),
+                }
+            }
 
-        Ok(())
+            _ => Err(FsmError::UnknownTransition),
+        }
     }
 
-    fn cleanup(&mut self) {
+    // returns whether the RatVerifier was restarted for the new dat
+    fn action_recv_dat(&mut self, data: IdscpDat) -> Result<bool, FsmError> {
+        log::debug!("Receive IdscpDat. Verifying Dat ...");
         self.handshake_timer.cancel();
-        self.dat_timer.cancel();
-        self.rat_timer.cancel();
-        self.verifier_timer.cancel();
-        self.prover_timer.cancel();
-        self.ack_timer.cancel();
 
-        self.rat_prover.lock().unwrap().stop_driver();
-        self.rat_verifier.lock().unwrap().stop_driver();
+        self.verify_dat(data)?;
 
-        //close secure channel
-        {
-            let mut guard = self.sc_interface.lock().unwrap();
-            let _ = (*guard).unlock();
-            (*guard).stop();
+        if self.skip_rat_on_dat_renewal {
+            log::debug!("Dat renewed, continue periodic re-attestation");
+            self.skip_rat_on_dat_renewal = false;
+            self.start_rat_timer();
+            return Ok(false);
         }
+
+        log::debug!("Start RatVerifier");
+        if let Err(e) = self.rat_verifier.restart_driver() {
+            log::error!("Cannot restart RatVerifier driver");
+            return Err(FsmError::RatError(e));
+        }
+        self.verifier_timer.start();
+
+        Ok(true)
     }
 
-    fn notify_connection_about_close(&self) {
-        // notify connection about closure
+    // the peer renewed its dat before it expired, there is no need to re-attest the peer
+    fn action_recv_dat_renewal(&mut self, data: IdscpDat) -> Result<(), FsmError> {
+        log::debug!("Receive renewed IdscpDat. Verifying Dat ...");
+        self.verify_dat(data)
+    }
 
-        // if the handshake result was not available, the handshake seems to have failed
-        // in this case, a connection will never be available. To avoid deadlocks with the user
-        // thread that wait for the handshake result, which will be set directly after this cleanup
-        // routine, the wait_for_connection_available function must be skipped in the case when no
-        // handshake result is available so far. Otherwise, the connection is promised to be set to
-        // None (on handshake failure) or Some (on handshake success).
-        if self.handshake_result_available {
-            self.wait_for_connection_available();
-            match self.idscp_connection.upgrade() {
-                None => {}
-                Some(c_lock) => {
-                    log::debug!("try to acquire lock to connection");
-                    let lock_result = c_lock.lock();
-                    log::debug!("connection lock acquired");
-                    match lock_result {
-                        Err(e) => {
-                            log::warn!("Cannot aquire connection as write lock {}", e);
-                        }
-                        Ok(mut c_guard) => {
-                            (*c_guard).on_close();
-                        }
-                    }
-                }
-            }
+    fn action_send_dat(&mut self, dat: &Dat) -> Result<(), FsmError> {
+        let idscp_dat = idscp_message_factory::create_idscp_dat(dat.token.clone().into_bytes());
+        let mut raw = Vec::new();
+        let _ = idscp_dat.write_to_vec(&mut raw);
+        if let Err(e) = self.sc_interface.write(raw) {
+            log::error!("Cannot send IdscpDat");
+            return Err(FsmError::IoError(e));
         }
+
+        self.start_dat_renewal_timer(dat);
+        Ok(())
     }
 
-    fn wait_for_connection_available(&self) {
-        // wait until connection result is available to avoid race conditions
-        let &(ref lock, ref cvar) = &*self.connection_available_var;
-        match lock.lock() {
+    fn action_renew_dat(&mut self) {
+        log::debug!("Own dat expires soon. Request renewed Dat");
+        self.daps_interface.request_token();
+    }
+
+    // the own dat that was requested for a renewal or for an IdscpDatExpired arrived
+    fn action_recv_own_dat(&mut self, result: Result<Dat, DapsError>) -> Result<(), FsmError> {
+        self.daps_interface.on_token();
+        let dat = match result {
             Err(e) => {
-                log::error!("Cannot acquire connection_available lock: {}", e);
+                self.action_daps_failed(&e);
+                return Err(FsmError::DapsError(e));
             }
+            Ok(dat) => dat,
+        };
 
-            Ok(mut available) => {
-                while !*available {
-                    available = match cvar.wait(available) {
-                        Err(e) => {
-                            log::error!("Waiting for connection available failed: {}", e);
-                            return;
-                        }
-                        Ok(guard) => guard,
-                    }
-                }
+        log::debug!("Received own dat from daps. Send IdscpDat");
+        self.action_send_dat(&dat)?;
+
+        // the peer verifies the new dat before it starts its RatVerifier
+        if self.restart_prover_on_dat {
+            self.restart_prover_on_dat = false;
+            if let Err(e) = self.rat_prover.restart_driver() {
+                log::error!("Cannot restart RatProver driver");
+                return Err(FsmError::RatError(e));
             }
         }
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
 
-    // Test Transitions //
-    use super::*;
-    use crate::drivers::daps_driver::DapsDriver;
-    use crate::drivers::rat_driver::RatDriver;
-    use crate::fsm::AckFlag::Inactive;
-    use crate::messages::idscp_message_factory::*;
-    use openssl::hash::MessageDigest;
-    use openssl::nid::Nid;
-    use openssl::pkey::PKey;
-    use openssl::rsa::Rsa;
-    use openssl::x509::{X509Name, X509};
-    use std::io::Error;
-    use std::sync::mpsc::{Receiver, Sender};
-    use FsmEvent::*;
-    use FsmState::*;
+    fn action_daps_failed(&mut self, e: &DapsError) {
+        log::error!("{}. Send close and close connection", e);
+        let idscp_close = idscp_message_factory::create_idscp_close(
+            IdscpClose_CloseCause::ERROR,
+            "Own DAT is not available",
+        );
+        let mut data = Vec::new();
+        let _ = idscp_close.write_to_vec(&mut data);
+        let _ = self.sc_interface.write(data);
+    }
 
-    struct TestDaps {}
-    impl DapsDriver for TestDaps {
-        fn get_token(&self) -> String {
-            "valid".to_string()
-        }
+    // schedule the renewal of the own dat the configured margin before it expires
+    fn start_dat_renewal_timer(&mut self, dat: &Dat) {
+        let margin = match self.rat_config.dat_renewal_margin {
+            None => return,
+            Some(m) => m,
+        };
 
-        fn verify_token(&self, token: &String) -> Option<Duration> {
-            if token.eq("valid") {
-                Some(Duration::from_secs(1))
-            } else {
-                None
+        let validity = dat
+            .expires_at
+            .and_then(|exp| exp.duration_since(SystemTime::now()).ok());
+        match validity {
+            Some(validity) if validity > margin => {
+                log::debug!("Renew own dat in {:?}", validity - margin);
+                self.dat_renewal_timer.start(validity - margin);
+            }
+            _ => {
+                log::warn!("Validity of own dat is unknown or below renewal margin, do not renew");
+                self.dat_renewal_timer.cancel();
             }
         }
     }
 
-    struct TestSc {}
+    fn action_recv_dat_exp(&mut self) -> Result<(), FsmError> {
+        log::debug!("Receive IdscpDatExpired. Request new Dat and restart RatProver afterwards");
 
-    impl SecureChannel for TestSc {
-        fn send_msg(&self, _data: Vec<u8>) -> Result<(), Error> {
-            Ok(())
+        if !self.dat_expired_limiter.allow() {
+            self.action_rate_limit_exceeded(SecurityEventKind::DatExpiredRateLimitExceeded);
+            return Err(FsmError::RateLimitExceeded("IdscpDatExpired"));
         }
 
-        fn recv_msg(&self) -> Result<Vec<u8>, Error> {
-            Ok(Vec::new())
-        }
+        // the RatProver is restarted when the new dat was sent, the prover timer covers the
+        // request to the daps as well
+        self.rat_prover.stop_driver();
+        self.restart_prover_on_dat = true;
+        self.daps_interface.request_token();
+        self.prover_timer.start();
 
-        fn terminate(&self) {}
+        Ok(())
+    }
+
+    fn cleanup(&mut self) {
+        self.handshake_timer.cancel();
+        self.dat_timer.cancel();
+        self.dat_renewal_timer.cancel();
+        self.rat_timer.cancel();
+        self.verifier_timer.cancel();
+        self.prover_timer.cancel();
+        self.ack_timer.cancel();
 
-        fn get_peer_certificate(&self) -> X509 {
-            let rsa = Rsa::generate(2048).unwrap();
-            let pkey = PKey::from_rsa(rsa).unwrap();
+        self.daps_interface.cancel();
+        self.restart_prover_on_dat = false;
 
-            let mut name = X509Name::builder().unwrap();
-            name.append_entry_by_nid(Nid::COMMONNAME, "foobar.com")
-                .unwrap();
-            let name = name.build();
+        self.action_discard_re_rat_buffers();
 
-            let mut builder = X509::builder().unwrap();
-            builder.set_version(2).unwrap();
-            builder.set_subject_name(&name).unwrap();
-            builder.set_issuer_name(&name).unwrap();
-            builder.set_pubkey(&pkey).unwrap();
-            builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+        self.rat_prover.stop_driver();
+        self.rat_verifier.stop_driver();
 
-            let certificate: X509 = builder.build();
-            return certificate;
-        }
+        //close secure channel
+        let _ = self.sc_interface.unlock();
+        self.sc_interface.stop();
     }
 
-    struct RatProverDummy {}
-    struct RatVerifierDummy {}
+    // closes the connection after an action failed, the fsm is locked forever
+    fn fail_and_lock(&mut self, e: FsmError) -> Result<(), FsmError> {
+        self.cleanup();
+        self.notify_connection_about_close();
+        self.current_state = FsmState::Closed(ClosedStateStatus::Locked);
+        Err(e)
+    }
 
-    impl RatDriver for RatProverDummy {
-        fn get_id(&self) -> &'static str {
-            "NullRat"
-        }
+    fn notify_connection_about_close(&self) {
+        // notify connection about closure
 
-        fn execute(&self, _tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _peer_cert: X509) {
-            let _ = rx.recv();
+        // if the handshake result was not available, the handshake seems to have failed
+        // in this case, a connection will never be available
+        if self.handshake_result_available {
+            if let Some(tx) = &self.idscp_connection {
+                let _ = tx.send(IdscpEvent::ConnectionClosed);
+            }
         }
     }
+}
 
-    impl RatDriver for RatVerifierDummy {
-        fn get_id(&self) -> &'static str {
-            "NullRat"
-        }
+#[cfg(test)]
+mod tests {
 
-        fn execute(&self, _tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _peer_cert: X509) {
-            let _ = rx.recv();
-        }
+    // Test Transitions //
+    use super::test_support::{create_cert, dummy_registry, TestDaps, TestSc, TEST_PEER_NAME};
+    use super::*;
+    use crate::api::idscp_configuration::RateLimit;
+    use crate::drivers::daps_driver::{DapsError, Dat};
+    use crate::fsm::AckFlag::Inactive;
+    use crate::messages::idscp_message_factory::*;
+    use std::collections::BTreeMap;
+    use FsmEvent::*;
+    use FsmState::*;
+
+    fn own_dat(validity_ms: u64) -> FsmEvent {
+        FromDaps(Ok(Dat {
+            token: "valid".to_string(),
+            expires_at: Some(SystemTime::now() + Duration::from_millis(validity_ms)),
+        }))
     }
 
     fn create_test_fsm(
@@ -2021,27 +2454,40 @@ mod tests {
         ack_flag: AckFlag,
         next_send_alternating_bit: AlternatingBit,
         expected_alternating_bit: AlternatingBit,
-    ) -> Arc<Mutex<FiniteStateMachine>> {
-        let mut prover_registry = RatRegistry::new();
-        let mut verifier_registry = RatRegistry::new();
-        let prover = Arc::new(RatProverDummy {});
-        let verifier = Arc::new(RatVerifierDummy {});
-        prover_registry.register_driver(prover);
-        verifier_registry.register_driver(verifier);
-        let sc = Arc::new(TestSc {});
-        let daps = Arc::new(TestDaps {});
-        let handshake_cond = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
-        let handshake_timeout = Duration::from_millis(5000);
-        let ack_timeout = Duration::from_millis(1000);
+    ) -> FiniteStateMachine {
         let rat_config = AttestationConfig {
             supported_attestation_suite: vec!["NullRat".to_string()],
             expected_attestation_suite: vec!["NullRat".to_string()],
             rat_timeout: Duration::from_millis(1000),
+            ..Default::default()
         };
-        let fsm = FiniteStateMachine::create(
+        create_test_fsm_with_config(
+            state,
+            ack_flag,
+            next_send_alternating_bit,
+            expected_alternating_bit,
+            rat_config,
+        )
+    }
+
+    fn create_test_fsm_with_config(
+        state: FsmState,
+        ack_flag: AckFlag,
+        next_send_alternating_bit: AlternatingBit,
+        expected_alternating_bit: AlternatingBit,
+        rat_config: AttestationConfig,
+    ) -> FiniteStateMachine {
+        let sc = Arc::new(TestSc::new(create_cert()));
+        let daps = Arc::new(TestDaps {
+            validity: Duration::from_secs(1),
+        });
+        let handshake_cond = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
+        let handshake_timeout = Duration::from_millis(5000);
+        let ack_timeout = Duration::from_millis(1000);
+        let (mut fsm, _queue) = FiniteStateMachine::create(
             sc,
-            prover_registry,
-            verifier_registry,
+            dummy_registry(),
+            dummy_registry(),
             daps,
             handshake_cond,
             handshake_timeout,
@@ -2051,41 +2497,24 @@ mod tests {
 
         // register rat drivers in interface (this would be done via receiving hello in normal
         // handshake and enables restart methods on interfaces)
-        let rat_p_interface = Arc::clone(&fsm.lock().unwrap().rat_prover);
-        let rat_v_interface = Arc::clone(&fsm.lock().unwrap().rat_verifier);
-        let rat_p_registry = Arc::downgrade(&fsm.lock().unwrap().prover_registry);
-        let rat_v_registry = Arc::downgrade(&fsm.lock().unwrap().verifier_registry);
-
-        let _ = fsm.lock().unwrap().rat_prover.lock().unwrap().start_driver(
-            "NullRat",
-            rat_p_registry,
-            rat_p_interface,
-        );
+        let _ = fsm.rat_prover.start_driver("NullRat", &fsm.prover_registry);
         let _ = fsm
-            .lock()
-            .unwrap()
             .rat_verifier
-            .lock()
-            .unwrap()
-            .start_driver("NullRat", rat_v_registry, rat_v_interface);
-
-        let mut guard = fsm.lock().unwrap();
-        (*guard).set_connection(None); //set no connection to ensure process_event is not
-                                       // blocking until connection is available
-        (*guard).current_state = state;
-        (*guard).ack_flag = ack_flag;
-        (*guard).next_send_alternating_bit = next_send_alternating_bit;
-        (*guard).expected_alternating_bit = expected_alternating_bit;
-        drop(guard);
+            .start_driver("NullRat", &fsm.verifier_registry);
+
+        fsm.set_connection(None);
+        fsm.current_state = state;
+        fsm.ack_flag = ack_flag;
+        fsm.next_send_alternating_bit = next_send_alternating_bit;
+        fsm.expected_alternating_bit = expected_alternating_bit;
 
         fsm
     }
 
     fn check_transition(s1: FsmState, s2: FsmState, e: FsmEvent, ack: AckFlag) -> bool {
-        let fsm = create_test_fsm(s1, ack, AlternatingBit::new(), AlternatingBit::new());
-        let mut guard = fsm.lock().unwrap();
-        let _ = (*guard).process_event(e);
-        (*guard).current_state == s2
+        let mut fsm = create_test_fsm(s1, ack, AlternatingBit::new(), AlternatingBit::new());
+        let _ = fsm.process_event(e);
+        fsm.current_state == s2
     }
 
     fn get_sc_event(m: IdscpMessage) -> FsmEvent {
@@ -2130,11 +2559,13 @@ mod tests {
     }
 
     fn p_ok() -> FsmEvent {
-        FromRatProver(RatMessage::ControlMessage(RatIcm::OK))
+        FromRatProver(RatMessage::ControlMessage(RatIcm::OK(RatResult::default())))
     }
 
     fn p_failed() -> FsmEvent {
-        FromRatProver(RatMessage::ControlMessage(RatIcm::Failed))
+        FromRatProver(RatMessage::ControlMessage(RatIcm::Failed(
+            "prover failed".to_string(),
+        )))
     }
 
     fn v_msg() -> FsmEvent {
@@ -2142,11 +2573,13 @@ mod tests {
     }
 
     fn v_ok() -> FsmEvent {
-        FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK))
+        FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK(RatResult::default())))
     }
 
     fn v_failed() -> FsmEvent {
-        FromRatVerifier(RatMessage::ControlMessage(RatIcm::Failed))
+        FromRatVerifier(RatMessage::ControlMessage(RatIcm::Failed(
+            "verifier failed".to_string(),
+        )))
     }
 
     fn sc_err() -> FsmEvent {
@@ -2162,7 +2595,7 @@ mod tests {
     }
 
     fn u_re_rat() -> FsmEvent {
-        FromUpper(UserEvent::RepeatRat)
+        FromUpper(UserEvent::RepeatRat("ReRat".to_string()))
     }
 
     fn u_data() -> FsmEvent {
@@ -2613,7 +3046,7 @@ mod tests {
         ));
         assert!(check_transition(
             WaitForRat,
-            WaitForRat,
+            Closed(ClosedStateStatus::Locked),
             get_sc_event(create_idscp_dat(Vec::from("invalid"))),
             Inactive
         ));
@@ -2770,7 +3203,7 @@ mod tests {
         ));
         assert!(check_transition(
             WaitForRatProver,
-            WaitForRatProver,
+            Closed(ClosedStateStatus::Locked),
             get_sc_event(create_idscp_dat(Vec::from("invalid"))),
             Inactive
         ));
@@ -2927,7 +3360,7 @@ mod tests {
         ));
         assert!(check_transition(
             WaitForRatVerifier,
-            WaitForRatVerifier,
+            Closed(ClosedStateStatus::Locked),
             get_sc_event(create_idscp_dat(Vec::from("invalid"))),
             Inactive
         ));
@@ -3378,7 +3811,7 @@ mod tests {
         ));
         assert!(check_transition(
             Established,
-            Established,
+            Closed(ClosedStateStatus::Locked),
             get_sc_event(create_idscp_dat(Vec::from("invalid"))),
             Inactive
         ));
@@ -3428,118 +3861,603 @@ mod tests {
 
     #[test]
     fn test_alternating_bit_sending() {
-        let fsm = create_test_fsm(
+        let mut fsm = create_test_fsm(
             FsmState::Established,
             AckFlag::Active(vec![]),
             AlternatingBit::Zero,
             AlternatingBit::Zero,
         );
-        let mut guard = fsm.lock().unwrap();
         let event = u_data();
-        let _ = (*guard).process_event(event);
-        assert_eq!((*guard).next_send_alternating_bit, AlternatingBit::Zero);
-        assert_eq!((*guard).current_state, FsmState::WaitForAck);
-        assert_eq!((*guard).ack_flag, AckFlag::Active(vec![]));
+        let _ = fsm.process_event(event);
+        assert_eq!(fsm.next_send_alternating_bit, AlternatingBit::Zero);
+        assert_eq!(fsm.current_state, FsmState::WaitForAck);
+        assert_eq!(fsm.ack_flag, AckFlag::Active(vec![]));
 
-        let fsm = create_test_fsm(
+        let mut fsm = create_test_fsm(
             FsmState::Established,
             AckFlag::Active(vec![]),
             AlternatingBit::One,
             AlternatingBit::Zero,
         );
-        let mut guard = fsm.lock().unwrap();
         let event = u_data();
-        let _ = (*guard).process_event(event);
-        assert_eq!((*guard).next_send_alternating_bit, AlternatingBit::One);
-        assert_eq!((*guard).current_state, FsmState::WaitForAck);
-        assert_eq!((*guard).ack_flag, AckFlag::Active(vec![]));
+        let _ = fsm.process_event(event);
+        assert_eq!(fsm.next_send_alternating_bit, AlternatingBit::One);
+        assert_eq!(fsm.current_state, FsmState::WaitForAck);
+        assert_eq!(fsm.ack_flag, AckFlag::Active(vec![]));
     }
 
     #[test]
     fn test_alternating_bit_in_ack() {
-        let fsm = create_test_fsm(
+        let mut fsm = create_test_fsm(
             FsmState::WaitForAck,
             AckFlag::Active(vec![]),
             AlternatingBit::Zero,
             AlternatingBit::Zero,
         );
-        let mut guard = fsm.lock().unwrap();
         let event = get_sc_event(create_idscp_ack(AlternatingBit::Zero));
-        let _ = (*guard).process_event(event);
-        assert_eq!((*guard).next_send_alternating_bit, AlternatingBit::One);
-        assert_eq!((*guard).current_state, FsmState::Established);
-        assert_eq!((*guard).ack_flag, AckFlag::Inactive);
+        let _ = fsm.process_event(event);
+        assert_eq!(fsm.next_send_alternating_bit, AlternatingBit::One);
+        assert_eq!(fsm.current_state, FsmState::Established);
+        assert_eq!(fsm.ack_flag, AckFlag::Inactive);
 
-        let fsm = create_test_fsm(
+        let mut fsm = create_test_fsm(
             FsmState::WaitForAck,
             AckFlag::Active(vec![]),
             AlternatingBit::Zero,
             AlternatingBit::Zero,
         );
-        let mut guard = fsm.lock().unwrap();
         let event = get_sc_event(create_idscp_ack(AlternatingBit::One));
-        let _ = (*guard).process_event(event);
-        assert_eq!((*guard).next_send_alternating_bit, AlternatingBit::Zero);
-        assert_eq!((*guard).current_state, FsmState::WaitForAck);
+        let _ = fsm.process_event(event);
+        assert_eq!(fsm.next_send_alternating_bit, AlternatingBit::Zero);
+        assert_eq!(fsm.current_state, FsmState::WaitForAck);
     }
 
     #[test]
     fn test_alternating_bit_receiving() {
         for state in &[FsmState::Established, FsmState::WaitForAck] {
             // expect 0, get 0, flip
-            let fsm = create_test_fsm(
+            let mut fsm = create_test_fsm(
                 state.clone(),
                 AckFlag::Active(vec![]),
                 AlternatingBit::Zero,
                 AlternatingBit::Zero,
             );
-            let mut guard = fsm.lock().unwrap();
             let event = get_sc_event(create_idscp_data(Vec::from("DATA"), &AlternatingBit::Zero));
-            let _ = (*guard).process_event(event);
-            assert_eq!((*guard).expected_alternating_bit, AlternatingBit::One);
-            assert_eq!((*guard).current_state, state.clone());
+            let _ = fsm.process_event(event);
+            assert_eq!(fsm.expected_alternating_bit, AlternatingBit::One);
+            assert_eq!(fsm.current_state, state.clone());
 
             // expect 1, get 1, flip
-            let fsm = create_test_fsm(
+            let mut fsm = create_test_fsm(
                 state.clone(),
                 AckFlag::Active(vec![]),
                 AlternatingBit::Zero,
                 AlternatingBit::One,
             );
-            let mut guard = fsm.lock().unwrap();
             let event = get_sc_event(create_idscp_data(Vec::from("DATA"), &AlternatingBit::One));
-            let _ = (*guard).process_event(event);
-            assert_eq!((*guard).expected_alternating_bit, AlternatingBit::Zero);
-            assert_eq!((*guard).current_state, state.clone());
+            let _ = fsm.process_event(event);
+            assert_eq!(fsm.expected_alternating_bit, AlternatingBit::Zero);
+            assert_eq!(fsm.current_state, state.clone());
 
             // expect 0, get 1, don't flip
-            let fsm = create_test_fsm(
+            let mut fsm = create_test_fsm(
                 state.clone(),
                 AckFlag::Active(vec![]),
                 AlternatingBit::Zero,
                 AlternatingBit::Zero,
             );
-            let mut guard = fsm.lock().unwrap();
             let event = get_sc_event(create_idscp_data(Vec::from("DATA"), &AlternatingBit::One));
-            let _ = (*guard).process_event(event);
-            assert_eq!((*guard).expected_alternating_bit, AlternatingBit::Zero);
-            assert_eq!((*guard).current_state, state.clone());
+            let _ = fsm.process_event(event);
+            assert_eq!(fsm.expected_alternating_bit, AlternatingBit::Zero);
+            assert_eq!(fsm.current_state, state.clone());
 
             // expect 1, get 0, don't flip
-            let fsm = create_test_fsm(
+            let mut fsm = create_test_fsm(
                 state.clone(),
                 AckFlag::Active(vec![]),
                 AlternatingBit::Zero,
                 AlternatingBit::One,
             );
-            let mut guard = fsm.lock().unwrap();
             let event = get_sc_event(create_idscp_data(Vec::from("DATA"), &AlternatingBit::Zero));
-            let _ = (*guard).process_event(event);
-            assert_eq!((*guard).expected_alternating_bit, AlternatingBit::One);
-            assert_eq!((*guard).current_state, state.clone());
+            let _ = fsm.process_event(event);
+            assert_eq!(fsm.expected_alternating_bit, AlternatingBit::One);
+            assert_eq!(fsm.current_state, state.clone());
         }
     }
 
+    #[test]
+    fn test_rat_interval() {
+        let mut rat_config = AttestationConfig {
+            supported_attestation_suite: vec!["NullRat".to_string()],
+            expected_attestation_suite: vec!["NullRat".to_string()],
+            rat_timeout: Duration::from_millis(1000),
+            ..Default::default()
+        };
+        rat_config
+            .rat_timeouts_per_mechanism
+            .insert("NullRat".to_string(), Duration::from_secs(10));
+        let fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        assert_eq!(fsm.next_rat_interval(), Duration::from_secs(10));
+
+        // the test secure channel provides a certificate for TEST_PEER_NAME
+        rat_config
+            .rat_timeouts_per_peer
+            .insert(TEST_PEER_NAME.to_string(), Duration::from_secs(20));
+        rat_config.rat_timeout_jitter = Duration::from_secs(5);
+        let fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config,
+        );
+        let intervals: Vec<Duration> = (0..50).map(|_| fsm.next_rat_interval()).collect();
+        assert!(intervals
+            .iter()
+            .all(|i| *i >= Duration::from_secs(15) && *i <= Duration::from_secs(25)));
+        assert!(intervals.iter().any(|i| *i != intervals[0]));
+    }
+
+    #[test]
+    fn test_dat_renewal_without_re_rat() {
+        let dat = || get_sc_event(create_idscp_dat(Vec::from("valid")));
+
+        // by default, the peer is re-attested after renewing its dat
+        let mut fsm = create_test_fsm(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        let _ = fsm.process_event(DatTimeout);
+        let _ = fsm.process_event(dat());
+        assert_eq!(fsm.current_state, WaitForRatVerifier);
+
+        let rat_config = AttestationConfig {
+            supported_attestation_suite: vec!["NullRat".to_string()],
+            expected_attestation_suite: vec!["NullRat".to_string()],
+            rat_timeout: Duration::from_millis(1000),
+            re_rat_on_dat_renewal: false,
+            ..Default::default()
+        };
+        let mut fsm = create_test_fsm_with_config(
+            WaitForAck,
+            AckFlag::Active(vec![]),
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        let _ = fsm.process_event(DatTimeout);
+        assert_eq!(fsm.current_state, WaitForDatAndRatVerifier);
+        let _ = fsm.process_event(dat());
+        assert_eq!(fsm.current_state, WaitForAck);
+        assert!(fsm.rat_timer.is_active());
+        assert!(!fsm.verifier_timer.is_active());
+
+        // a repeat rat during the renewal still re-attests the peer
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config,
+        );
+        let _ = fsm.process_event(DatTimeout);
+        let _ = fsm.process_event(u_re_rat());
+        let _ = fsm.process_event(dat());
+        assert_eq!(fsm.current_state, WaitForRatVerifier);
+    }
+
+    #[test]
+    fn test_peer_dat_claims() {
+        let dat = |token: &str| get_sc_event(create_idscp_dat(Vec::from(token)));
+        let claims = |fsm: &FiniteStateMachine| {
+            let report = fsm.security_report();
+            let claims = report.lock().unwrap().peer_dat_claims.clone();
+            claims
+        };
+        let subject = |fsm: &FiniteStateMachine| claims(fsm).and_then(|c| c.subject);
+
+        let mut fsm = create_test_fsm(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        assert_eq!(claims(&fsm), None);
+
+        // claims are replaced by the ones of the renewed dat
+        assert!(fsm.process_event(dat("valid-1")).is_ok());
+        assert_eq!(subject(&fsm).as_deref(), Some("valid-1"));
+        let _ = fsm.process_event(DatTimeout);
+        let _ = fsm.process_event(dat("valid-2"));
+        assert_eq!(subject(&fsm).as_deref(), Some("valid-2"));
+
+        // and remain available after the connection was closed
+        let _ = fsm.process_event(dat("invalid"));
+        assert_eq!(fsm.current_state, locked());
+        assert_eq!(subject(&fsm).as_deref(), Some("valid-2"));
+    }
+
+    #[test]
+    fn test_attestation_report() {
+        let mut fsm = create_test_fsm(
+            WaitForRat,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        let report = fsm.security_report();
+        let mut claims = BTreeMap::new();
+        claims.insert("pcr0".to_string(), "ab12".to_string());
+        let result = RatResult {
+            claims,
+            evidence: vec![1, 2, 3],
+        };
+        let _ = fsm.process_event(FromRatVerifier(RatMessage::ControlMessage(RatIcm::OK(
+            result.clone(),
+        ))));
+        assert_eq!(report.lock().unwrap().attestation.peer, Some(result));
+
+        // the failure reason remains readable after the connection was closed
+        let _ = fsm.process_event(p_failed());
+        assert_eq!(fsm.current_state, locked());
+        assert_eq!(
+            report.lock().unwrap().attestation.failure,
+            Some(RatFailure::Prover("prover failed".to_string()))
+        );
+
+        // and is only sent to the peer if configured
+        assert_eq!(
+            fsm.rat_failure_cause("RatProver failed", "prover failed"),
+            "RatProver failed"
+        );
+        fsm.rat_config.disclose_rat_failure_reason = true;
+        assert_eq!(
+            fsm.rat_failure_cause("RatProver failed", "prover failed"),
+            "RatProver failed: prover failed"
+        );
+    }
+
+    struct RejectingVerifier {}
+    impl TokenVerifier for RejectingVerifier {
+        fn verify_token(&self, _token: &str, _peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+            Err(DapsError::InvalidDat("untrusted issuer".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_separate_dat_verifier() {
+        // the dats of the peer are verified by the dat verifier instead of the daps driver
+        let mut fsm = create_test_fsm(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        fsm.set_dat_verifier(Some(Arc::new(RejectingVerifier {})));
+        let _ = fsm.process_event(get_sc_event(create_idscp_dat(Vec::from("valid"))));
+        assert_eq!(fsm.current_state, locked());
+    }
+
+    #[test]
+    fn test_proactive_dat_renewal() {
+        let rat_config = AttestationConfig {
+            supported_attestation_suite: vec!["NullRat".to_string()],
+            expected_attestation_suite: vec!["NullRat".to_string()],
+            rat_timeout: Duration::from_millis(1000),
+            dat_renewal_margin: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        assert!(fsm.process_event(DatRenewalTimeout).is_ok());
+        assert_eq!(fsm.current_state, Established);
+        assert!(fsm.daps_interface.is_pending());
+        assert!(fsm.process_event(own_dat(1000)).is_ok());
+        assert_eq!(fsm.current_state, Established);
+        assert!(!fsm.daps_interface.is_pending());
+        assert!(fsm.dat_renewal_timer.is_active());
+
+        // a dat the peer renewed before expiry is accepted without re-attestation
+        let mut fsm = create_test_fsm_with_config(
+            WaitForAck,
+            AckFlag::Active(vec![]),
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        let dat = get_sc_event(create_idscp_dat(Vec::from("valid")));
+        assert!(fsm.process_event(dat).is_ok());
+        assert_eq!(fsm.current_state, WaitForAck);
+        assert!(fsm.dat_timer.is_active());
+        assert!(!fsm.verifier_timer.is_active());
+
+        // an invalid renewed dat closes the connection
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        let dat = get_sc_event(create_idscp_dat(Vec::from("invalid")));
+        assert!(fsm.process_event(dat).is_err());
+        assert_eq!(fsm.current_state, Closed(ClosedStateStatus::Locked));
+
+        // the dat is not renewed when its validity is below the margin
+        let rat_config = AttestationConfig {
+            dat_renewal_margin: Some(Duration::from_secs(5)),
+            ..rat_config
+        };
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config,
+        );
+        assert!(fsm.process_event(own_dat(1000)).is_ok());
+        assert!(!fsm.dat_renewal_timer.is_active());
+    }
+
+    #[test]
+    fn test_own_dat_from_daps() {
+        let daps_error = || FromDaps(Err(DapsError::Unavailable("DAPS is down".to_string())));
+
+        // the handshake is aborted if the dat could not be requested in advance
+        let mut fsm = create_test_fsm(
+            Closed(ClosedStateStatus::Unlocked),
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        fsm.handshake_dat = Some(Err(DapsError::Unavailable("DAPS is down".to_string())));
+        let res = fsm.process_event(FromUpper(UserEvent::StartHandshake));
+        assert!(matches!(res, Err(FsmError::DapsError(_))));
+        assert_eq!(fsm.current_state, Closed(ClosedStateStatus::Locked));
+
+        // the RatProver is restarted after the new dat was sent
+        let mut fsm = create_test_fsm(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+        );
+        assert!(fsm
+            .process_event(get_sc_event(create_idscp_dat_exp()))
+            .is_ok());
+        assert_eq!(fsm.current_state, WaitForRatProver);
+        assert!(fsm.daps_interface.is_pending() && fsm.restart_prover_on_dat);
+        assert!(fsm.process_event(own_dat(1000)).is_ok());
+        assert!(!fsm.daps_interface.is_pending() && !fsm.restart_prover_on_dat);
+        assert!(fsm.prover_timer.is_active());
+
+        // a DAPS outage closes the connection
+        assert!(fsm.process_event(DatRenewalTimeout).is_ok());
+        let res = fsm.process_event(daps_error());
+        assert!(matches!(res, Err(FsmError::DapsError(_))));
+        assert_eq!(fsm.current_state, Closed(ClosedStateStatus::Locked));
+        assert!(!fsm.daps_interface.is_pending());
+    }
+
+    struct SecurityEventCollector {
+        events: Mutex<Vec<SecurityEventKind>>,
+    }
+
+    impl FsmObserver for SecurityEventCollector {
+        fn on_transition(&self, _transition: &FsmTransition) {}
+
+        fn on_security_event(&self, event: &SecurityEvent) {
+            self.events.lock().unwrap().push(event.kind.clone());
+        }
+    }
+
+    #[test]
+    fn test_peer_request_rate_limits() {
+        let limit = Some(RateLimit {
+            max_requests: 1,
+            window: Duration::from_secs(60),
+        });
+        let rat_config = AttestationConfig {
+            supported_attestation_suite: vec!["NullRat".to_string()],
+            expected_attestation_suite: vec!["NullRat".to_string()],
+            rat_timeout: Duration::from_millis(1000),
+            re_rat_rate_limit: limit.clone(),
+            dat_expired_rate_limit: limit,
+            ..Default::default()
+        };
+        let observer = Arc::new(SecurityEventCollector {
+            events: Mutex::new(Vec::new()),
+        });
+
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config.clone(),
+        );
+        fsm.set_observer(Some(
+            Arc::clone(&observer) as Arc<dyn FsmObserver + Send + Sync>
+        ));
+        let re_rat = || get_sc_event(create_idscp_re_rat("ReRat"));
+        assert!(fsm.process_event(re_rat()).is_ok());
+        assert_eq!(fsm.current_state, WaitForRatProver);
+        match fsm.process_event(re_rat()) {
+            Err(FsmError::RateLimitExceeded(_)) => {}
+            r => panic!("Unexpected result {:?}", r),
+        }
+        assert_eq!(fsm.current_state, Closed(ClosedStateStatus::Locked));
+
+        let mut fsm = create_test_fsm_with_config(
+            Established,
+            Inactive,
+            AlternatingBit::new(),
+            AlternatingBit::new(),
+            rat_config,
+        );
+        fsm.set_observer(Some(
+            Arc::clone(&observer) as Arc<dyn FsmObserver + Send + Sync>
+        ));
+        let dat_exp = || get_sc_event(create_idscp_dat_exp());
+        assert!(fsm.process_event(dat_exp()).is_ok());
+        assert_eq!(fsm.current_state, WaitForRatProver);
+        assert!(fsm.process_event(dat_exp()).is_err());
+        assert_eq!(fsm.current_state, Closed(ClosedStateStatus::Locked));
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec![
+                SecurityEventKind::ReRatRateLimitExceeded,
+                SecurityEventKind::DatExpiredRateLimitExceeded
+            ]
+        );
+    }
+
+    fn create_re_rat_test_fsm(policy: ReRatDataPolicy) -> FiniteStateMachine {
+        let rat_config = AttestationConfig {
+            supported_attestation_suite: vec!["NullRat".to_string()],
+            expected_attestation_suite: vec!["NullRat".to_string()],
+            rat_timeout: Duration::from_millis(1000),
+            re_rat_data_policy: policy,
+            ..Default::default()
+        };
+        let mut fsm = create_test_fsm_with_config(
+            WaitForRatVerifier,
+            Inactive,
+            AlternatingBit::Zero,
+            AlternatingBit::Zero,
+            rat_config,
+        );
+        fsm.handshake_result_available = true;
+        fsm
+    }
+
+    #[test]
+    fn test_re_rat_data_policy_block() {
+        let mut fsm = create_re_rat_test_fsm(ReRatDataPolicy::Block);
+        assert!(matches!(
+            fsm.process_event(FromUpper(UserEvent::Data(Vec::from("out")))),
+            Err(FsmError::NotConnected)
+        ));
+
+        // inbound data is not acknowledged
+        let data = get_sc_event(create_idscp_data(Vec::from("in"), &AlternatingBit::Zero));
+        assert!(fsm.process_event(data).is_ok());
+        assert_eq!(fsm.expected_alternating_bit, AlternatingBit::Zero);
+        assert_eq!(fsm.current_state, WaitForRatVerifier);
+    }
+
+    #[test]
+    fn test_re_rat_data_policy_continue() {
+        let mut fsm = create_re_rat_test_fsm(ReRatDataPolicy::Continue);
+        assert!(fsm
+            .process_event(FromUpper(UserEvent::Data(Vec::from("out"))))
+            .is_ok());
+        assert_eq!(fsm.ack_flag, AckFlag::Active(Vec::from("out")));
+        assert!(fsm.ack_timer.is_active());
+        assert!(matches!(
+            fsm.process_event(FromUpper(UserEvent::Data(Vec::from("next")))),
+            Err(FsmError::WouldBlock)
+        ));
+
+        let data = get_sc_event(create_idscp_data(Vec::from("in"), &AlternatingBit::Zero));
+        assert!(fsm.process_event(data).is_ok());
+        assert_eq!(fsm.expected_alternating_bit, AlternatingBit::One);
+
+        let _ = fsm.process_event(get_sc_event(create_idscp_ack(AlternatingBit::Zero)));
+        assert_eq!(fsm.ack_flag, AckFlag::Inactive);
+        assert_eq!(fsm.current_state, WaitForRatVerifier);
+
+        let _ = fsm.process_event(v_ok());
+        assert_eq!(fsm.current_state, Established);
+    }
+
+    #[test]
+    fn test_re_rat_data_policy_buffer() {
+        let mut fsm = create_re_rat_test_fsm(ReRatDataPolicy::Buffer { max_messages: 2 });
+        for msg in &["first", "second"] {
+            assert!(fsm
+                .process_event(FromUpper(UserEvent::Data(Vec::from(*msg))))
+                .is_ok());
+        }
+        assert!(matches!(
+            fsm.process_event(FromUpper(UserEvent::Data(Vec::from("third")))),
+            Err(FsmError::WouldBlock)
+        ));
+        assert_eq!(fsm.ack_flag, AckFlag::Inactive);
+
+        // inbound data is held back without acknowledging it
+        let data = get_sc_event(create_idscp_data(Vec::from("in"), &AlternatingBit::Zero));
+        assert!(fsm.process_event(data).is_ok());
+        assert_eq!(fsm.expected_alternating_bit, AlternatingBit::Zero);
+        assert!(fsm.re_rat_inbound_data.is_some());
+
+        // buffers are flushed after successful re-attestation, one message per ack
+        let _ = fsm.process_event(v_ok());
+        assert!(fsm.re_rat_inbound_data.is_none());
+        assert_eq!(fsm.expected_alternating_bit, AlternatingBit::One);
+        assert_eq!(fsm.current_state, WaitForAck);
+        assert_eq!(fsm.ack_flag, AckFlag::Active(Vec::from("first")));
+
+        let _ = fsm.process_event(get_sc_event(create_idscp_ack(AlternatingBit::Zero)));
+        assert_eq!(fsm.current_state, WaitForAck);
+        assert_eq!(fsm.ack_flag, AckFlag::Active(Vec::from("second")));
+
+        let _ = fsm.process_event(get_sc_event(create_idscp_ack(AlternatingBit::One)));
+        assert_eq!(fsm.current_state, Established);
+        assert_eq!(fsm.ack_flag, AckFlag::Inactive);
+    }
+
+    #[test]
+    fn test_re_rat_data_policy_buffer_failed() {
+        let mut fsm = create_re_rat_test_fsm(ReRatDataPolicy::Buffer { max_messages: 2 });
+        let (connection_tx, connection_rx) = std::sync::mpsc::channel();
+        fsm.set_connection(Some(connection_tx));
+        for msg in &["first", "second"] {
+            assert!(fsm
+                .process_event(FromUpper(UserEvent::Data(Vec::from(*msg))))
+                .is_ok());
+        }
+        let data = get_sc_event(create_idscp_data(Vec::from("in"), &AlternatingBit::Zero));
+        assert!(fsm.process_event(data).is_ok());
+
+        // the inbound data was never acknowledged, the outbound data is reported as discarded
+        let _ = fsm.process_event(v_failed());
+        assert_eq!(fsm.current_state, locked());
+        assert_eq!(fsm.expected_alternating_bit, AlternatingBit::Zero);
+        let events: Vec<String> = connection_rx
+            .try_iter()
+            .map(|e| match e {
+                IdscpEvent::Message(m) => format!("Message({})", String::from_utf8_lossy(&m)),
+                IdscpEvent::MessageDiscarded(m) => {
+                    format!("MessageDiscarded({})", String::from_utf8_lossy(&m))
+                }
+                IdscpEvent::ConnectionClosed => "ConnectionClosed".to_string(),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "MessageDiscarded(first)",
+                "MessageDiscarded(second)",
+                "ConnectionClosed"
+            ]
+        );
+    }
+
     #[test]
     fn test_rat_algorithm_calculation() {
         let peer_rat_suites = ["C".to_string(), "B".to_string(), "A".to_string()];
@@ -0,0 +1,198 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Model check of the fsm: every transition observed by the exhaustive exploration of the
+// introspection module is checked against the fsm invariants for each ReRatDataPolicy and the
+// resulting transition table is compared to a reviewed snapshot. Set IDSCP_UPDATE_SNAPSHOTS=1 to regenerate the snapshot after an
+// intended change of the fsm.
+
+use super::introspection::{explore, ModelState, TransitionOutcome};
+use super::*;
+use crate::api::idscp_configuration::ReRatDataPolicy;
+use std::path::PathBuf;
+
+const SNAPSHOT_FILE: &str = "src/fsm/snapshots/transition_table.txt";
+
+impl ModelState {
+    fn accepts_data(&self) -> bool {
+        matches!(self.state, FsmState::Established | FsmState::WaitForAck)
+    }
+
+    fn is_re_rat(&self) -> bool {
+        self.handshake_done
+            && matches!(
+                self.state,
+                FsmState::WaitForRat | FsmState::WaitForRatProver | FsmState::WaitForRatVerifier
+            )
+    }
+
+    fn describe(&self) -> String {
+        let mut out = format!("{:?}", self.state);
+        for (name, value) in self.variables().iter() {
+            out.push_str(&format!(" {}={}", name, value));
+        }
+        out
+    }
+}
+
+// data is exchanged in Established and WaitForAck. During re-attestation it is exchanged as usual
+// with the Continue policy, with the Buffer policy once the re-attestation succeeded
+fn exchanges_data(policy: &ReRatDataPolicy, from: &ModelState, to: &ModelState) -> bool {
+    from.accepts_data()
+        || (from.is_re_rat()
+            && match policy {
+                ReRatDataPolicy::Block => false,
+                ReRatDataPolicy::Continue => true,
+                ReRatDataPolicy::Buffer { .. } => to.accepts_data(),
+            })
+}
+
+// check all invariants of a single transition, returns a list of violations
+fn check_invariants(
+    policy: &ReRatDataPolicy,
+    from: &ModelState,
+    label: &str,
+    event: &FsmEvent,
+    outcome: &TransitionOutcome,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+    let mut violation = |msg: &str| {
+        violations.push(format!("{} --{}--> {}", from.describe(), label, msg));
+    };
+
+    let to = match &outcome.to {
+        None => {
+            violation("fsm panicked");
+            return violations;
+        }
+        Some(to) => to,
+    };
+
+    if from.state == FsmState::Closed(ClosedStateStatus::Locked) && from.state != to.state {
+        violation("left Closed(Locked)");
+    }
+
+    if to.is_closed() && !outcome.timers_active.is_empty() {
+        violation(&format!(
+            "timers {:?} still active in {:?}",
+            outcome.timers_active, to.state
+        ));
+    }
+
+    if outcome.delivered_messages > 0 && !exchanges_data(policy, from, to) {
+        violation(&format!("data delivered in {:?}", from.state));
+    }
+    if outcome.sent_messages.contains(&"IdscpData") && !exchanges_data(policy, from, to) {
+        violation(&format!("data sent in {:?}", from.state));
+    }
+    let buffering = to.buffered > 0 || to.held;
+    if buffering && (to.is_closed() || !matches!(policy, ReRatDataPolicy::Buffer { .. })) {
+        violation(&format!("data buffered in {:?}", to.state));
+    }
+
+    // buffered outbound data is reported to the user once it will never be sent
+    let closing = !from.is_closed() && to.is_closed();
+    let discarded = if closing { from.buffered } else { 0 };
+    if outcome.discarded_messages != discarded {
+        violation(&format!(
+            "{} of {} buffered messages reported as discarded",
+            outcome.discarded_messages, from.buffered
+        ));
+    }
+
+    // a stop is triggered by the user, who gets the result of UserEvent::Stop directly and is
+    // therefore not notified
+    let user_stop = matches!(event, FsmEvent::FromUpper(UserEvent::Stop));
+    if closing && user_stop {
+        if outcome.close_notifications != 0 {
+            violation("connection notified about close on user stop");
+        }
+    } else if closing && from.handshake_done {
+        if outcome.close_notifications != 1 {
+            violation(&format!(
+                "connection notified {} times about close",
+                outcome.close_notifications
+            ));
+        }
+    } else if closing {
+        // connection was never established, the user is notified via the handshake result
+        if outcome.close_notifications != 0 || outcome.handshake_result != HandshakeResult::Failed {
+            violation("failed handshake was not reported");
+        }
+    } else if outcome.close_notifications != 0 {
+        violation("connection notified about close without closing");
+    }
+
+    violations
+}
+
+#[test]
+fn fsm_model_check() {
+    let mut table = Vec::new();
+    let mut violations = Vec::new();
+
+    let policies = [
+        ReRatDataPolicy::Block,
+        ReRatDataPolicy::Continue,
+        ReRatDataPolicy::Buffer { max_messages: 1 },
+    ];
+    for policy in policies.iter() {
+        explore(policy, |from, label, event, outcome| {
+            violations.extend(check_invariants(policy, from, label, event, outcome));
+            let to = match &outcome.to {
+                None => "PANIC".to_string(),
+                Some(to) => to.describe(),
+            };
+            table.push(format!(
+                "{:?} | {} | {} => {} | {}",
+                policy,
+                from.describe(),
+                label,
+                to,
+                outcome.result
+            ));
+        });
+    }
+
+    assert!(
+        violations.is_empty(),
+        "fsm invariants violated:\n{}",
+        violations.join("\n")
+    );
+
+    table.sort();
+    let table = table.join("\n") + "\n";
+
+    let snapshot_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT_FILE);
+    if std::env::var("IDSCP_UPDATE_SNAPSHOTS").is_ok() {
+        std::fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+        std::fs::write(&snapshot_path, &table).unwrap();
+        return;
+    }
+
+    let snapshot = std::fs::read_to_string(&snapshot_path).unwrap_or_default();
+    if snapshot != table {
+        let first_diff = snapshot
+            .lines()
+            .zip(table.lines())
+            .find(|(s, t)| s != t)
+            .map(|(s, t)| format!("snapshot: {}\nactual:   {}", s, t))
+            .unwrap_or_else(|| "transition tables differ in length".to_string());
+        panic!(
+            "fsm transition table differs from the reviewed snapshot {}\n{}\n\
+             Review the changes and run the test with IDSCP_UPDATE_SNAPSHOTS=1 to update it",
+            SNAPSHOT_FILE, first_diff
+        );
+    }
+}
@@ -12,14 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{FiniteStateMachine, FsmEvent};
-use crate::drivers::rat_driver::{RatDriver, RatMessage, RatRegistry};
+use super::event_loop::LoopMessage;
+use super::FsmEvent;
+use crate::drivers::rat_driver::{RatContext, RatDriver, RatMessage, RatRegistry};
 
+use openssl::rand::rand_bytes;
 use openssl::x509::X509;
 
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use thiserror::Error;
 
@@ -56,12 +59,16 @@ pub enum RatError {
     RatConnectionAborted,
     #[error("RAT driver has not been cached")]
     RatDriverNotCached,
+    #[error("Cannot create nonce for the RAT driver")]
+    NonceUnavailable,
 }
 
+const RAT_NONCE_SIZE: usize = 32; // byte
+
 // Rat Driver Interfaces
 // A rat driver interface is owned by the FiniteStateMachine and can only be used by this FSM
-// Since a FSM is only accessible via mutex lock, the RatDriverInterface can only be accessed by
-// one thread at a time
+// Since a FSM is only accessed by the event loop thread of its connection, the
+// RatDriverInterface is never shared between threads
 struct RatDriverContent {
     tx_to_driver: Sender<RatMessage>,
     tx_to_listener: Sender<RatMessage>,
@@ -69,42 +76,39 @@ struct RatDriverContent {
 }
 
 pub(super) struct RatDriverInterface<RatType: RatDriverType + Send + Sync + 'static> {
-    pub(super) fsm: Weak<Mutex<FiniteStateMachine>>,
+    event_tx: Sender<LoopMessage>,
     content: Option<RatDriverContent>,
     cached_driver: Option<Arc<dyn RatDriver + Send + Sync>>,
     phantom: PhantomData<RatType>,
     peer_cert: X509,
+    channel_binding: Option<Vec<u8>>,
 }
 
 impl<RatType: RatDriverType + Send + Sync + 'static> RatDriverInterface<RatType> {
-    pub(super) fn create(peer_cert: X509) -> Arc<Mutex<RatDriverInterface<RatType>>> {
-        Arc::new(Mutex::new(RatDriverInterface {
-            fsm: Weak::new(),
+    pub(super) fn create(
+        peer_cert: X509,
+        channel_binding: Option<Vec<u8>>,
+        event_tx: Sender<LoopMessage>,
+    ) -> RatDriverInterface<RatType> {
+        RatDriverInterface {
+            event_tx,
             content: None,
             cached_driver: None,
             phantom: PhantomData,
             peer_cert,
-        }))
+            channel_binding,
+        }
     }
 
     pub(super) fn start_driver(
         &mut self,
         rat_mechanism: &str,
-        registry: Weak<RatRegistry>,
-        strong_ref_interface: Arc<Mutex<RatDriverInterface<RatType>>>,
+        registry: &RatRegistry,
     ) -> Result<(), RatError> {
         // terminate running driver
         self.stop_driver();
 
         // get driver from registry
-        let registry = match registry.upgrade() {
-            None => {
-                return Err(RatError::RegistryNotAvailable);
-            }
-
-            Some(r) => r,
-        };
-
         let driver_clone = match registry.get_driver(rat_mechanism) {
             None => {
                 return Err(RatError::UnknownRatDriver);
@@ -114,23 +118,17 @@ impl<RatType: RatDriverType + Send + Sync + 'static> RatDriverInterface<RatType>
 
         //cache driver clone
         self.cached_driver = Some(driver_clone);
-        self.run_driver(strong_ref_interface)
+        self.run_driver()
     }
 
-    pub(super) fn restart_driver(
-        &mut self,
-        strong_ref_interface: Arc<Mutex<RatDriverInterface<RatType>>>,
-    ) -> Result<(), RatError> {
+    pub(super) fn restart_driver(&mut self) -> Result<(), RatError> {
         // terminate running driver
         self.stop_driver();
 
-        self.run_driver(strong_ref_interface)
+        self.run_driver()
     }
 
-    fn run_driver(
-        &mut self,
-        strong_ref_interface: Arc<Mutex<RatDriverInterface<RatType>>>,
-    ) -> Result<(), RatError> {
+    fn run_driver(&mut self) -> Result<(), RatError> {
         let driver_clone = match &self.cached_driver {
             None => {
                 return Err(RatError::RatDriverNotCached);
@@ -139,10 +137,21 @@ impl<RatType: RatDriverType + Send + Sync + 'static> RatDriverInterface<RatType>
             Some(driver) => Arc::clone(driver),
         };
 
+        // each run gets a fresh nonce, such that evidence of former runs cannot be replayed
+        let mut nonce = vec![0u8; RAT_NONCE_SIZE];
+        if let Err(e) = rand_bytes(&mut nonce) {
+            log::error!("Cannot create nonce for RAT driver: {}", e);
+            return Err(RatError::NonceUnavailable);
+        }
+        let ctx = RatContext {
+            peer_cert: self.peer_cert.clone(),
+            channel_binding: self.channel_binding.clone(),
+            nonce,
+        };
+
         // create channels
         let (tx_to_driver, rx_from_interface) = mpsc::channel();
         let (tx_to_interface, rx_from_driver) = mpsc::channel();
-        let p1 = self.peer_cert.clone();
 
         // create listener
         let listener = DriverListener::new();
@@ -157,19 +166,22 @@ impl<RatType: RatDriverType + Send + Sync + 'static> RatDriverInterface<RatType>
 
         // execute driver
         thread::spawn(move || {
-            driver_clone.execute(tx_to_interface, rx_from_interface, p1);
+            driver_clone.execute(tx_to_interface, rx_from_interface, ctx);
         });
 
         //start listener
-        let fsm_clone = Weak::clone(&self.fsm);
+        let event_tx = self.event_tx.clone();
         let content = self.content.as_mut().unwrap();
-        content
-            .listener
-            .listen::<RatType>(fsm_clone, strong_ref_interface, rx_from_driver);
+        content.listener.listen::<RatType>(event_tx, rx_from_driver);
 
         Ok(())
     }
 
+    // id of the driver that was started last
+    pub(super) fn driver_id(&self) -> Option<&'static str> {
+        self.cached_driver.as_ref().map(|d| d.get_id())
+    }
+
     pub(super) fn write_to_driver(&self, msg: RatMessage) -> Result<(), RatError> {
         let sender = match &self.content {
             None => return Err(RatError::RatDriverInactive),
@@ -202,29 +214,17 @@ impl<RatType: RatDriverType + Send + Sync + 'static> RatDriverInterface<RatType>
             }
         };
     }
-
-    fn on_driver_stop(&mut self) {
-        let content = match self.content.take() {
-            None => {
-                return;
-            }
-            Some(content) => content,
-        };
-
-        drop(content.tx_to_driver);
-        drop(content.tx_to_listener);
-    }
 }
 
 struct DriverListener {
-    is_cancelled: Arc<Mutex<bool>>,
+    is_cancelled: Arc<AtomicBool>,
     is_locked: bool,
 }
 
 impl DriverListener {
     fn new() -> DriverListener {
         DriverListener {
-            is_cancelled: Arc::new(Mutex::new(false)),
+            is_cancelled: Arc::new(AtomicBool::new(false)),
             is_locked: false,
         }
     }
@@ -232,8 +232,7 @@ impl DriverListener {
     //from upper layer, start listener thread
     fn listen<RatType: RatDriverType + Send + Sync + 'static>(
         &mut self,
-        fsm: Weak<Mutex<FiniteStateMachine>>,
-        interface: Arc<Mutex<RatDriverInterface<RatType>>>,
+        event_tx: Sender<LoopMessage>,
         rx_from_driver: Receiver<RatMessage>,
     ) {
         if self.is_locked {
@@ -246,59 +245,27 @@ impl DriverListener {
         //create clones for listener thread
         let is_cancelled_clone = Arc::clone(&self.is_cancelled);
 
-        //enter cancel lock
-        let mut cancel_guard = self.is_cancelled.lock().unwrap();
-
-        //set cancel lock to false
-        (*cancel_guard) = false;
-
         //spawn thread
-        thread::spawn(move || {
-            let driver_stop_handler = || {
-                let mut interface_guard = interface.lock().unwrap();
-                (*interface_guard).on_driver_stop();
-            };
-
-            loop {
-                match rx_from_driver.recv() {
-                    Err(_) => {
-                        // driver closed, notify interface and terminate listener
-                        driver_stop_handler();
+        thread::spawn(move || loop {
+            match rx_from_driver.recv() {
+                Err(_) => {
+                    // driver closed, terminate listener
+                    return;
+                }
+                Ok(msg) => {
+                    //received new message, check if cancelled
+                    if is_cancelled_clone.load(Ordering::SeqCst) {
+                        log::debug!("Driver listener has been cancelled");
                         return;
                     }
-                    Ok(msg) => {
-                        //received new message
-
-                        //lock fsm
-                        let fsm_strong = match fsm.upgrade() {
-                            None => {
-                                log::debug!("FSM is not available anymore");
-                                driver_stop_handler();
-                                return;
-                            }
-                            Some(strong) => strong,
-                        };
-                        let mut fsm_guard = match fsm_strong.lock() {
-                            Err(_) => {
-                                log::error!("FSM lock failed");
-
-                                // notify interface
-                                driver_stop_handler();
-                                return;
-                            }
-                            Ok(guard) => guard,
-                        };
-
-                        //check if cancelled
-                        let cancel_guard = is_cancelled_clone.lock().unwrap();
-                        let cancelled = *cancel_guard;
-                        if cancelled {
-                            log::debug!("Driver listener has been cancelled");
-                            return;
-                        } else {
-                            // driver was not cancelled, delegate message to fsm
-                            let _ = (*fsm_guard).process_event(RatType::create_event(msg));
-                        }
+
+                    // delegate message to the event loop of the fsm, which drops it if the
+                    // listener gets cancelled in the meantime
+                    let event = RatType::create_event(msg);
+                    let cancelled = Some(Arc::clone(&is_cancelled_clone));
+                    if event_tx.send(LoopMessage::Event(event, cancelled)).is_err() {
+                        log::debug!("FSM is not available anymore");
+                        return;
                     }
                 }
             }
@@ -307,11 +274,7 @@ impl DriverListener {
 
     //from upper layer, terminate listener
     fn stop(&mut self) {
-        //enter cancel lock
-        let mut cancel_guard = self.is_cancelled.lock().unwrap();
-
-        //set cancel lock to true
-        (*cancel_guard) = true;
+        self.is_cancelled.store(true, Ordering::SeqCst);
     }
 }
 
@@ -319,97 +282,23 @@ impl DriverListener {
 mod tests {
     use super::*;
     use crate::api::idscp_configuration::AttestationConfig;
-    use crate::drivers::daps_driver::DapsDriver;
     use crate::drivers::rat_driver;
-    use crate::drivers::secure_channel::SecureChannel;
-    use crate::fsm::HandshakeResult;
-    use openssl::hash::MessageDigest;
-    use openssl::nid::Nid;
-    use openssl::pkey::PKey;
-    use openssl::rsa::Rsa;
-    use openssl::x509::{X509Name, X509};
-    use std::io::Error;
-    use std::sync::Condvar;
+    use crate::fsm::test_support::{create_cert, RatDummy, TestDaps, TestSc, TEST_RAT_ID};
+    use crate::fsm::{FiniteStateMachine, HandshakeResult};
+    use std::sync::{Condvar, Mutex};
     use std::time::Duration;
 
-    struct RatDummy {}
-    impl rat_driver::RatDriver for RatDummy {
-        fn get_id(&self) -> &'static str {
-            "RatDummy"
-        }
-
-        fn execute(&self, _tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _peer_cert: X509) {
-            println!("Dummy has been started");
-            match rx.recv() {
-                Err(_) => {
-                    println!("Quit dummy, channel was closed");
-                    return;
-                }
-
-                Ok(_) => {
-                    println!("Dummy received msg");
-                }
-            }
-
-            match rx.recv() {
-                Err(_) => {
-                    println!("Quit dummy, channel was closed");
-                    return;
-                }
-
-                Ok(_) => {
-                    println!("Dummy received msg");
-                }
-            }
-        }
-    }
-
-    struct TestDaps {}
-    impl DapsDriver for TestDaps {
-        fn get_token(&self) -> String {
-            "valid".to_string()
-        }
-
-        fn verify_token(&self, token: &String) -> Option<Duration> {
-            if token.eq("valid") {
-                Some(Duration::from_millis(1000))
-            } else {
-                None
-            }
-        }
+    // reports the context of each run
+    struct ContextDriver {
+        contexts: Mutex<Sender<RatContext>>,
     }
-
-    struct TestSc {}
-
-    impl SecureChannel for TestSc {
-        fn send_msg(&self, _data: Vec<u8>) -> Result<(), Error> {
-            Ok(())
-        }
-
-        fn recv_msg(&self) -> Result<Vec<u8>, Error> {
-            Ok(vec![])
+    impl rat_driver::RatDriver for ContextDriver {
+        fn get_id(&self) -> &'static str {
+            "ContextDriver"
         }
 
-        fn terminate(&self) {}
-
-        fn get_peer_certificate(&self) -> X509 {
-            let rsa = Rsa::generate(2048).unwrap();
-            let pkey = PKey::from_rsa(rsa).unwrap();
-
-            let mut name = X509Name::builder().unwrap();
-            name.append_entry_by_nid(Nid::COMMONNAME, "foobar.com")
-                .unwrap();
-            let name = name.build();
-
-            let mut builder = X509::builder().unwrap();
-            builder.set_version(2).unwrap();
-            builder.set_subject_name(&name).unwrap();
-            builder.set_issuer_name(&name).unwrap();
-            builder.set_pubkey(&pkey).unwrap();
-            builder.sign(&pkey, MessageDigest::sha256()).unwrap();
-
-            let certificate: X509 = builder.build();
-            return certificate;
+        fn execute(&self, _tx: Sender<RatMessage>, _rx: Receiver<RatMessage>, ctx: RatContext) {
+            let _ = self.contexts.lock().unwrap().send(ctx);
         }
     }
 
@@ -423,11 +312,11 @@ mod tests {
 
         let handshake_cond = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
         //create fsm
-        let fsm = FiniteStateMachine::create(
-            Arc::new(TestSc {}),
+        let (mut fsm, _queue) = FiniteStateMachine::create(
+            Arc::new(TestSc::new(create_cert())),
             prover_registry,
             verifier_registry,
-            Arc::new(TestDaps {}),
+            Arc::new(TestDaps::default()),
             handshake_cond,
             Duration::from_millis(5000),
             Duration::from_millis(1000),
@@ -435,46 +324,72 @@ mod tests {
                 supported_attestation_suite: vec![],
                 expected_attestation_suite: vec![],
                 rat_timeout: Duration::from_millis(1000),
+                ..Default::default()
             },
         );
 
-        //get fsm lock
-        let fsm_guard = fsm.lock().unwrap();
-
-        //get prover interface clones
-        let prover_arc = Arc::clone(&fsm_guard.rat_prover);
-
-        let prover_strong = Arc::clone(&prover_arc);
-
-        //get prover lock
-        let mut prover_guard = prover_arc.lock().unwrap();
+        //get prover interface
+        let prover = &mut fsm.rat_prover;
 
         //check if content is none
-        assert!((*prover_guard).content.is_none());
+        assert!(prover.content.is_none());
 
         let mut prover_registry = RatRegistry::new();
-        let prover = RatDummy {};
-        prover_registry.register_driver(Arc::new(prover));
-        let prover_registry = Arc::new(prover_registry);
+        let dummy = RatDummy {};
+        prover_registry.register_driver(Arc::new(dummy));
 
         //start prover driver
-
-        assert!((*prover_guard)
-            .start_driver("RatDummy", Arc::downgrade(&prover_registry), prover_strong)
-            .is_ok());
+        assert!(prover.start_driver(TEST_RAT_ID, &prover_registry).is_ok());
 
         //check if content is some
-        assert!((*prover_guard).content.is_some());
+        assert!(prover.content.is_some());
 
         //write to prover
-        assert!((*prover_guard)
+        assert!(prover
             .write_to_driver(RatMessage::RawData(Vec::from("Hello")))
             .is_ok());
 
         //stop prover again
-        (*prover_guard).stop_driver();
+        prover.stop_driver();
 
         //check if content is none
-        assert!((*prover_guard).content.is_none());
+        assert!(prover.content.is_none());
+    }
+
+    #[test]
+    fn rat_context_test() {
+        let (contexts_tx, contexts_rx) = mpsc::channel();
+        let mut verifier_registry = RatRegistry::new();
+        verifier_registry.register_driver(Arc::new(ContextDriver {
+            contexts: Mutex::new(contexts_tx),
+        }));
+        let handshake_cond = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
+        let mut sc = TestSc::new(create_cert());
+        sc.channel_binding = Some(b"channel-binding".to_vec());
+        let (mut fsm, _queue) = FiniteStateMachine::create(
+            Arc::new(sc),
+            RatRegistry::new(),
+            verifier_registry.clone(),
+            Arc::new(TestDaps::default()),
+            handshake_cond,
+            Duration::from_millis(5000),
+            Duration::from_millis(1000),
+            AttestationConfig::default(),
+        );
+        let verifier = &mut fsm.rat_verifier;
+
+        // the driver gets the channel binding of the secure channel and a fresh nonce per run
+        assert!(verifier
+            .start_driver("ContextDriver", &verifier_registry)
+            .is_ok());
+        let first = contexts_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(first.channel_binding, Some(b"channel-binding".to_vec()));
+        assert_eq!(first.nonce.len(), RAT_NONCE_SIZE);
+
+        assert!(verifier.restart_driver().is_ok());
+        let second = contexts_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(second.channel_binding, first.channel_binding);
+        assert_ne!(second.nonce, first.nonce);
+        verifier.stop_driver();
     }
 }
@@ -0,0 +1,84 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Sliding window rate limiter for requests of the peer, such as IdscpReRat and IdscpDatExpired
+
+use crate::api::idscp_configuration::RateLimit;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+pub(crate) struct RateLimiter {
+    limit: Option<RateLimit>,
+    requests: VecDeque<Instant>, // points in time of the requests within the current window
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: Option<RateLimit>) -> RateLimiter {
+        RateLimiter {
+            limit,
+            requests: VecDeque::new(),
+        }
+    }
+
+    // records a new request, returns false if the request exceeds the rate limit
+    pub(crate) fn allow(&mut self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&mut self, now: Instant) -> bool {
+        let limit = match &self.limit {
+            None => return true,
+            Some(l) => l,
+        };
+
+        while let Some(oldest) = self.requests.front() {
+            if now.duration_since(*oldest) < limit.window {
+                break;
+            }
+            self.requests.pop_front();
+        }
+
+        if self.requests.len() >= limit.max_requests {
+            return false;
+        }
+        self.requests.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut unlimited = RateLimiter::new(None);
+        assert!((0..100).all(|_| unlimited.allow()));
+
+        let mut limiter = RateLimiter::new(Some(RateLimit {
+            max_requests: 2,
+            window: Duration::from_secs(10),
+        }));
+        let start = Instant::now();
+        assert!(limiter.allow_at(start));
+        assert!(limiter.allow_at(start + Duration::from_secs(1)));
+        assert!(!limiter.allow_at(start + Duration::from_secs(2)));
+
+        // the first request leaves the window
+        assert!(limiter.allow_at(start + Duration::from_secs(10)));
+        assert!(!limiter.allow_at(start + Duration::from_secs(10)));
+        assert!(limiter.allow_at(start + Duration::from_secs(11)));
+    }
+}
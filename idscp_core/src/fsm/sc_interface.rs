@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{FiniteStateMachine, FsmEvent, SecureChannelEvent};
+use super::event_loop::LoopMessage;
+use super::{FsmEvent, SecureChannelEvent};
 use crate::drivers::secure_channel::SecureChannel;
-use crate::messages::idscpv2_messages::{IdscpMessage, IdscpMessage_oneof_message};
+use crate::messages::idscpv2_messages::IdscpMessage;
 use protobuf::parse_from_bytes;
-use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use thiserror::Error;
 
@@ -36,31 +38,23 @@ struct InterfaceContent {
 
 //TODO(obr): maybe name SecureChannelProxy to avoid ambiguity
 pub(super) struct SecureChannelInterface {
-    pub(super) fsm: Weak<Mutex<FiniteStateMachine>>,
+    event_tx: Sender<LoopMessage>,
     content: Option<InterfaceContent>,
 }
 
 impl SecureChannelInterface {
-    // create a new secure channel interface within a mutex
-    pub(super) fn create() -> Arc<Mutex<SecureChannelInterface>> {
-        Arc::new(Mutex::new(SecureChannelInterface {
-            fsm: Weak::new(),
+    // create a new secure channel interface that is owned by the fsm
+    pub(super) fn create(event_tx: Sender<LoopMessage>) -> SecureChannelInterface {
+        SecureChannelInterface {
+            event_tx,
             content: None,
-        }))
+        }
     }
 
-    pub(super) fn init(
-        &mut self,
-        sc: Arc<dyn SecureChannel + Send + Sync>,
-        interface_ref: Weak<Mutex<SecureChannelInterface>>,
-    ) {
+    pub(super) fn init(&mut self, sc: Arc<dyn SecureChannel + Send + Sync>) {
         log::debug!("Init secure channel interface");
         let c = InterfaceContent {
-            listener: SecureChannelListener::create(
-                Weak::clone(&self.fsm),
-                interface_ref,
-                Arc::clone(&sc),
-            ),
+            listener: SecureChannelListener::create(self.event_tx.clone(), Arc::clone(&sc)),
             secure_channel: sc,
         };
 
@@ -122,21 +116,18 @@ impl SecureChannelInterface {
  * and notifies fsm
  */
 struct SecureChannelListener {
-    fsm: Weak<Mutex<FiniteStateMachine>>,
-    interface: Weak<Mutex<SecureChannelInterface>>,
+    event_tx: Sender<LoopMessage>,
     sc: Arc<dyn SecureChannel + Send + Sync>,
     unlocked_cond_var: Arc<(Mutex<bool>, Condvar)>,
 }
 
 impl SecureChannelListener {
     fn create(
-        fsm: Weak<Mutex<FiniteStateMachine>>,
-        interface: Weak<Mutex<SecureChannelInterface>>,
+        event_tx: Sender<LoopMessage>,
         sc: Arc<dyn SecureChannel + Send + Sync>,
     ) -> SecureChannelListener {
         SecureChannelListener {
-            fsm,
-            interface,
+            event_tx,
             sc,
             unlocked_cond_var: Arc::new((Mutex::new(false), Condvar::new())),
         }
@@ -147,20 +138,11 @@ impl SecureChannelListener {
         // function within a loop and terminate on error
         log::debug!("Start listening the secure channel listener on secure channel messages");
 
-        let fsm_clone = Weak::clone(&self.fsm);
-        let if_clone = Weak::clone(&self.interface);
+        let event_tx = self.event_tx.clone();
         let sc_clone = Arc::clone(&self.sc);
         let unlocked_clone = Arc::clone(&self.unlocked_cond_var);
 
         thread::spawn(move || {
-            let unregister_listener = || match if_clone.upgrade() {
-                None => {}
-                Some(interface) => {
-                    log::debug!("Stopping secure channel interface listener");
-                    interface.lock().unwrap().stop();
-                }
-            };
-
             loop {
                 let recv_result = sc_clone.recv_msg();
                 match recv_result {
@@ -168,22 +150,15 @@ impl SecureChannelListener {
                         log::warn!(
                             "Secure Channel was closed. Shutting down SecureChannelInterface"
                         );
-                        //unregister listener to interface
-                        unregister_listener();
-                        //hand over error to the fsm if it is still available
-                        match fsm_clone.upgrade() {
-                            None => {
-                                log::debug!("SecureChannelInterface is terminating. FSM is not available anymore");
-                                return;
-                            }
-                            Some(fsm_strong) => match fsm_strong.lock() {
-                                Err(e) => log::error!("Cannot access fsm lock {}", e),
-                                Ok(mut guard) => {
-                                    let _ = (*guard).process_event(FsmEvent::FromSecureChannel(
-                                        SecureChannelEvent::Error,
-                                    ));
-                                }
-                            },
+                        //hand over error to the fsm, which stops the interface
+                        if event_tx
+                            .send(LoopMessage::Event(
+                                FsmEvent::FromSecureChannel(SecureChannelEvent::Error),
+                                None,
+                            ))
+                            .is_err()
+                        {
+                            log::debug!("SecureChannelInterface is terminating. FSM is not available anymore");
                         }
                         return;
                     }
@@ -211,43 +186,7 @@ impl SecureChannelListener {
                         };
 
                         //create event
-                        let sc_event = match body {
-                            IdscpMessage_oneof_message::idscpClose(data) => {
-                                SecureChannelEvent::Close(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpHello(data) => {
-                                SecureChannelEvent::Hello(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpDat(data) => {
-                                SecureChannelEvent::Dat(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpDatExpired(data) => {
-                                SecureChannelEvent::DatExp(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpRatProver(data) => {
-                                SecureChannelEvent::RatProver(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpRatVerifier(data) => {
-                                SecureChannelEvent::RatVerifier(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpReRat(data) => {
-                                SecureChannelEvent::ReRat(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpData(data) => {
-                                SecureChannelEvent::Data(data)
-                            }
-
-                            IdscpMessage_oneof_message::idscpAck(data) => {
-                                SecureChannelEvent::Ack(data)
-                            }
-                        };
+                        let sc_event = SecureChannelEvent::from(body);
 
                         // wait until the fsm was started by the user or closed forever
                         let &(ref lock, ref cvar) = &*unlocked_clone;
@@ -280,32 +219,18 @@ impl SecureChannelListener {
                             }
                         }
 
-                        //hand data to fsm if available
-                        log::debug!("check if fsm available");
-                        match fsm_clone.upgrade() {
-                            None => {
-                                // fsm is not available anymore
-                                // unregister at listener interface and quit listener
-                                log::warn!("Fsm is not available anymore");
-                                unregister_listener();
-                                return;
-                            }
-                            Some(fsm_strong) => {
-                                log::debug!("try to get lock on fsm");
-                                let lock_result = fsm_strong.lock();
-                                log::debug!("got lock on fsm");
-                                match lock_result {
-                                    Err(e) => {
-                                        log::error!("Cannot access fsm lock {}", e);
-                                        return;
-                                    }
-                                    Ok(mut guard) => {
-                                        let _ = (*guard)
-                                            .process_event(FsmEvent::FromSecureChannel(sc_event));
-                                    }
-                                }
-                            }
-                        };
+                        //hand data to the event loop of the fsm if available
+                        if event_tx
+                            .send(LoopMessage::Event(
+                                FsmEvent::FromSecureChannel(sc_event),
+                                None,
+                            ))
+                            .is_err()
+                        {
+                            // fsm is not available anymore, quit listener
+                            log::warn!("Fsm is not available anymore");
+                            return;
+                        }
                     }
                 }
             }
@@ -0,0 +1,174 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Stub drivers for running the fsm without a peer, shared by the unit tests, the fuzz targets and
+// the fsm introspection. The stubs never produce events on their own.
+
+use crate::drivers::daps_driver::{
+    DapsDriver, DapsError, Dat, DatClaims, TokenVerifier, VerifiedDat,
+};
+use crate::drivers::rat_driver::{RatContext, RatDriver, RatMessage, RatRegistry};
+use crate::drivers::secure_channel::SecureChannel;
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::x509::{X509Name, X509};
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub(crate) const TEST_RAT_ID: &str = "NullRat";
+// common name of the certificate of create_cert
+pub(crate) const TEST_PEER_NAME: &str = "foobar.com";
+
+// issues the token "valid" and accepts all tokens that start with "valid". The token is reported
+// as subject, such that renewed dats can be told apart
+pub(crate) struct TestDaps {
+    pub(crate) validity: Duration,
+}
+
+impl Default for TestDaps {
+    fn default() -> Self {
+        TestDaps {
+            validity: Duration::from_secs(60),
+        }
+    }
+}
+
+impl DapsDriver for TestDaps {
+    fn get_token(&self) -> Result<Dat, DapsError> {
+        Ok(Dat {
+            token: "valid".to_string(),
+            expires_at: Some(SystemTime::now() + self.validity),
+        })
+    }
+}
+
+impl TokenVerifier for TestDaps {
+    fn verify_token(&self, token: &str, _peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        if token.starts_with("valid") {
+            Ok(VerifiedDat {
+                validity: self.validity,
+                claims: DatClaims {
+                    subject: Some(token.to_string()),
+                    ..Default::default()
+                },
+            })
+        } else {
+            Err(DapsError::InvalidDat("unknown token".to_string()))
+        }
+    }
+}
+
+// secure channel that collects sent frames and blocks in recv until it is terminated
+pub(crate) struct TestSc {
+    cert: X509,
+    pub(crate) channel_binding: Option<Vec<u8>>,
+    sent: Mutex<Vec<Vec<u8>>>,
+    terminated: (Mutex<bool>, Condvar),
+}
+
+impl TestSc {
+    pub(crate) fn new(cert: X509) -> Self {
+        TestSc {
+            cert,
+            channel_binding: None,
+            sent: Mutex::new(Vec::new()),
+            terminated: (Mutex::new(false), Condvar::new()),
+        }
+    }
+
+    // frames sent since the last call
+    pub(crate) fn take_sent(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.sent.lock().unwrap())
+    }
+}
+
+impl SecureChannel for TestSc {
+    fn send_msg(&self, data: Vec<u8>) -> Result<(), Error> {
+        self.sent.lock().unwrap().push(data);
+        Ok(())
+    }
+
+    fn recv_msg(&self) -> Result<Vec<u8>, Error> {
+        let (lock, cvar) = &self.terminated;
+        let mut terminated = lock.lock().unwrap();
+        while !*terminated {
+            terminated = cvar.wait(terminated).unwrap();
+        }
+        Err(Error::new(ErrorKind::ConnectionAborted, "terminated"))
+    }
+
+    fn terminate(&self) {
+        let (lock, cvar) = &self.terminated;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+    }
+
+    fn get_peer_certificate(&self) -> X509 {
+        self.cert.clone()
+    }
+
+    fn get_channel_binding(&self) -> Option<Vec<u8>> {
+        self.channel_binding.clone()
+    }
+}
+
+// rat driver that consumes all messages until it is stopped
+pub(crate) struct RatDummy {}
+
+impl RatDriver for RatDummy {
+    fn get_id(&self) -> &'static str {
+        TEST_RAT_ID
+    }
+
+    fn execute(&self, _tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _ctx: RatContext) {
+        while rx.recv().is_ok() {}
+    }
+}
+
+// registry containing the RatDummy
+pub(crate) fn dummy_registry() -> RatRegistry {
+    let mut registry = RatRegistry::new();
+    registry.register_driver(Arc::new(RatDummy {}));
+    registry
+}
+
+// an EC certificate is used, since RSA key generation would dominate the execution time
+pub(crate) fn create_cert() -> X509 {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let pkey = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+    let mut name = X509Name::builder().unwrap();
+    name.append_entry_by_nid(Nid::COMMONNAME, TEST_PEER_NAME)
+        .unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    builder.set_pubkey(&pkey).unwrap();
+    builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+    builder.build()
+}
@@ -14,15 +14,26 @@
 // limitations under the License.
 
 use crate::api::idscp_configuration::Idscp2Configuration;
-use crate::api::idscp_connection::{Idscp2Connection, InnerIdscp2connection};
+use crate::api::idscp_connection::Idscp2Connection;
 use crate::drivers::secure_channel::{SecureChannel, SecureChannelClient};
-use crate::fsm::{FiniteStateMachine, HandshakeResult, UserEvent};
+use crate::fsm::{event_loop, FiniteStateMachine, FsmError, HandshakeResult, UserEvent};
+use std::sync::mpsc::channel;
 use std::sync::{Arc, Condvar, Mutex};
 
 pub mod api;
 pub mod drivers;
 mod fsm;
-mod messages;
+pub mod messages;
+pub mod recording;
+
+// entry points for the fuzz targets in idscp_core/fuzz, not part of the public api
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub use fsm::fuzzing;
+
+// exhaustive exploration of the fsm, used by the idscp_fsm_graph binary
+#[cfg(feature = "introspection")]
+pub use fsm::introspection;
 
 pub fn connect<SCC: SecureChannelClient>(
     secure_channel_client: SCC,
@@ -88,7 +99,7 @@ fn create_new_idscp2_connection(
     let handshake_wait = Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new()));
 
     //create fsm
-    let fsm = FiniteStateMachine::create(
+    let (mut fsm, queue) = FiniteStateMachine::create(
         sc,
         config.prover_registry.clone(),
         config.verifier_registry.clone(),
@@ -98,22 +109,34 @@ fn create_new_idscp2_connection(
         config.ack_timeout,
         config.rat_config.clone(),
     );
+    fsm.set_observer(config.fsm_observer.clone());
+    fsm.set_dat_verifier(config.dat_verifier.clone());
+    let id = fsm.connection_id();
+    let security = fsm.security_report();
 
-    //start fsm handshake
-    match fsm.lock() {
+    // messages received before the connection is returned are buffered in the channel
+    let (incoming_msg_tx, incoming_msg_rx) = channel();
+    fsm.set_connection(Some(incoming_msg_tx));
+
+    //move fsm into its event loop
+    let handle = match event_loop::spawn(fsm, queue) {
         Err(e) => {
-            log::error!("Cannot acquire fsm lock: {}", e);
-            return Err("Cannot acquire fsm lock");
+            log::error!("Cannot spawn connection thread: {}", e);
+            return Err("Cannot spawn connection thread");
         }
+        Ok(handle) => handle,
+    };
 
-        Ok(mut fsm_guard) => {
-            log::debug!("Start Idscp2 handshake");
-            match (*fsm_guard).feed_user_event(UserEvent::StartHandshake) {
-                Ok(()) => {}
-                Err(e) => {
-                    log::warn!("Cannot start handshake: {}", e);
-                }
-            }
+    //start fsm handshake
+    log::debug!("Start Idscp2 handshake");
+    match handle.feed_user_event(UserEvent::StartHandshake) {
+        Ok(()) => {}
+        Err(FsmError::DapsError(e)) => {
+            log::error!("Cannot start handshake: {}", e);
+            return Err("Cannot get DAT from DAPS");
+        }
+        Err(e) => {
+            log::warn!("Cannot start handshake: {}", e);
         }
     }
 
@@ -127,23 +150,11 @@ fn create_new_idscp2_connection(
                 log::debug!("Idscp2 handshake successful");
 
                 // if handshake was successful create new Idscp2Connection
-                let (inner, incoming_msg_rx) = InnerIdscp2connection::new(Arc::clone(&fsm));
-                let inner_wrapper = Arc::new(Mutex::new(inner));
-
-                // make inner idscp connection available in fsm
-                match fsm.lock() {
-                    Err(e) => {
-                        log::error!("Cannot acquire fsm lock: {}", e);
-                        return Err("Cannot acquire fsm lock");
-                    }
-                    Ok(mut fsm_guard) => {
-                        (*fsm_guard).set_connection(Some(Arc::downgrade(&inner_wrapper)));
-                    }
-                }
-
                 let idscp_connection = Idscp2Connection {
-                    inner: inner_wrapper,
+                    id,
+                    fsm: handle,
                     incoming_message_rx: incoming_msg_rx,
+                    security,
                 };
                 return Ok(idscp_connection);
             }
@@ -39,7 +39,7 @@ pub(crate) fn create_idscp_hello(
     idscp
 }
 
-pub(crate) fn create_idscp_close(code: IdscpClose_CloseCause, msg: &'static str) -> IdscpMessage {
+pub(crate) fn create_idscp_close(code: IdscpClose_CloseCause, msg: &str) -> IdscpMessage {
     let mut close = IdscpClose::new();
     close.cause_code = code;
     close.cause_msg = String::from(msg);
@@ -64,7 +64,7 @@ pub(crate) fn create_idscp_dat(dat: Vec<u8>) -> IdscpMessage {
     idscp
 }
 
-pub(crate) fn create_idscp_re_rat(cause: &'static str) -> IdscpMessage {
+pub(crate) fn create_idscp_re_rat(cause: &str) -> IdscpMessage {
     let mut idscp_rerat = IdscpReRat::new();
     idscp_rerat.cause = String::from(cause);
 
@@ -0,0 +1,505 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Recording of IDSCP2 sessions for debugging purposes.
+//
+// A recording file starts with the magic bytes "IDSCPREC", a format version byte and the DER
+// encoded peer certificate (u32 big endian length prefix). It is followed by a sequence of records:
+//
+//   direction (u8) | timestamp in µs since UNIX epoch (u64 BE) | flags (u8) | length (u32 BE) | frame
+//
+// The frame is the IdscpMessage as it was sent or received via the secure channel. If payload
+// redaction is enabled, the data of IdscpData messages is removed before the frame is written.
+
+pub mod replay;
+
+use crate::drivers::secure_channel::{
+    SecureChannel, SecureChannelClient, SecureChannelIncomingConnectionCallback,
+    SecureChannelServer,
+};
+use crate::messages::idscpv2_messages::{IdscpMessage, IdscpMessage_oneof_message};
+use bytes::Bytes;
+use openssl::x509::X509;
+use protobuf::Message;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"IDSCPREC";
+const FORMAT_VERSION: u8 = 1;
+
+const FLAG_REDACTED: u8 = 0x01;
+const FLAG_UNDECODABLE: u8 = 0x02;
+
+// upper bound for a single frame or certificate in a recording file
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+static SESSION_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+pub struct SessionRecordingConfig {
+    // directory in which a new recording file is created for every connection
+    pub directory: PathBuf,
+    // remove the payload of IdscpData messages, so recordings are safe to share
+    pub redact_data: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RecordDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    pub direction: RecordDirection,
+    pub timestamp: SystemTime,
+    pub redacted: bool,
+    pub frame: Vec<u8>,
+}
+
+impl SessionRecord {
+    // decode the recorded frame, None if the frame is not a valid IdscpMessage
+    pub fn decode(&self) -> Option<IdscpMessage> {
+        IdscpMessage::parse_from_bytes(&self.frame).ok()
+    }
+}
+
+pub struct SessionRecorder {
+    writer: BufWriter<File>,
+    redact_data: bool,
+}
+
+impl SessionRecorder {
+    pub fn create(path: &Path, peer_cert: &X509, redact_data: bool) -> Result<Self, Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let der = peer_cert
+            .to_der()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        writer.write_all(&(der.len() as u32).to_be_bytes())?;
+        writer.write_all(&der)?;
+        writer.flush()?;
+
+        Ok(SessionRecorder {
+            writer,
+            redact_data,
+        })
+    }
+
+    // create a new recording file with a unique name in the configured directory
+    pub fn create_in_directory(
+        config: &SessionRecordingConfig,
+        peer_cert: &X509,
+    ) -> Result<(Self, PathBuf), Error> {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let path = config.directory.join(format!(
+            "idscp_session_{}_{}.rec",
+            since_epoch.as_millis(),
+            SESSION_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let recorder = SessionRecorder::create(&path, peer_cert, config.redact_data)?;
+        Ok((recorder, path))
+    }
+
+    pub fn record(&mut self, direction: RecordDirection, frame: &[u8]) -> Result<(), Error> {
+        let mut flags = 0u8;
+        let redacted_frame;
+        let frame = if self.redact_data {
+            match redact(frame) {
+                None => {
+                    // we cannot guarantee that an undecodable frame does not contain payload
+                    flags |= FLAG_REDACTED | FLAG_UNDECODABLE;
+                    &[][..]
+                }
+                Some((f, redacted)) => {
+                    if redacted {
+                        flags |= FLAG_REDACTED;
+                    }
+                    redacted_frame = f;
+                    &redacted_frame[..]
+                }
+            }
+        } else {
+            frame
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let direction = match direction {
+            RecordDirection::Sent => 0u8,
+            RecordDirection::Received => 1u8,
+        };
+
+        self.writer.write_all(&[direction])?;
+        self.writer.write_all(&timestamp.to_be_bytes())?;
+        self.writer.write_all(&[flags])?;
+        self.writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+        self.writer.write_all(frame)?;
+        // flush every record, the recording must survive a crash of the process
+        self.writer.flush()
+    }
+}
+
+// returns the frame without IdscpData payload and whether something has been removed
+fn redact(frame: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let mut msg = IdscpMessage::parse_from_bytes(frame).ok()?;
+    match &mut msg.message {
+        Some(IdscpMessage_oneof_message::idscpData(data)) => {
+            data.data = Bytes::new();
+            let mut raw = Vec::new();
+            msg.write_to_vec(&mut raw).ok()?;
+            Some((raw, true))
+        }
+        _ => Some((frame.to_vec(), false)),
+    }
+}
+
+pub struct SessionRecordReader<R: Read> {
+    reader: R,
+    peer_cert: X509,
+}
+
+impl SessionRecordReader<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        SessionRecordReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> SessionRecordReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "not an IDSCP session recording",
+            ));
+        }
+
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported recording format version {}", version[0]),
+            ));
+        }
+
+        let der = read_sized(&mut reader)?;
+        let peer_cert = X509::from_der(&der).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(SessionRecordReader { reader, peer_cert })
+    }
+
+    pub fn peer_certificate(&self) -> &X509 {
+        &self.peer_cert
+    }
+
+    fn read_record(&mut self) -> Result<Option<SessionRecord>, Error> {
+        let mut direction = [0u8; 1];
+        if self.reader.read(&mut direction)? == 0 {
+            // end of recording
+            return Ok(None);
+        }
+        let direction = match direction[0] {
+            0 => RecordDirection::Sent,
+            1 => RecordDirection::Received,
+            d => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid record direction {}", d),
+                ))
+            }
+        };
+
+        let mut timestamp = [0u8; 8];
+        self.reader.read_exact(&mut timestamp)?;
+        let timestamp = UNIX_EPOCH + Duration::from_micros(u64::from_be_bytes(timestamp));
+
+        let mut flags = [0u8; 1];
+        self.reader.read_exact(&mut flags)?;
+
+        let frame = read_sized(&mut self.reader)?;
+
+        Ok(Some(SessionRecord {
+            direction,
+            timestamp,
+            redacted: flags[0] & FLAG_REDACTED != 0,
+            frame,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for SessionRecordReader<R> {
+    type Item = Result<SessionRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn read_sized<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut size = [0u8; 4];
+    reader.read_exact(&mut size)?;
+    let size = u32::from_be_bytes(size) as usize;
+    if size > MAX_RECORD_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("record size {} exceeds limit", size),
+        ));
+    }
+    let mut buf = vec![0u8; size];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+// A secure channel wrapper that records every frame that is sent or received
+pub struct RecordingSecureChannel {
+    inner: Arc<dyn SecureChannel + Send + Sync>,
+    recorder: Mutex<SessionRecorder>,
+}
+
+impl RecordingSecureChannel {
+    pub fn new(inner: Arc<dyn SecureChannel + Send + Sync>, recorder: SessionRecorder) -> Self {
+        RecordingSecureChannel {
+            inner,
+            recorder: Mutex::new(recorder),
+        }
+    }
+
+    pub fn create(
+        inner: Arc<dyn SecureChannel + Send + Sync>,
+        config: &SessionRecordingConfig,
+    ) -> Result<Self, Error> {
+        let (recorder, path) =
+            SessionRecorder::create_in_directory(config, &inner.get_peer_certificate())?;
+        log::info!("Recording IDSCP2 session to {}", path.display());
+        Ok(RecordingSecureChannel::new(inner, recorder))
+    }
+
+    fn record(&self, direction: RecordDirection, frame: &[u8]) {
+        match self.recorder.lock() {
+            Err(e) => log::warn!("Cannot access session recorder: {}", e),
+            Ok(mut recorder) => {
+                if let Err(e) = recorder.record(direction, frame) {
+                    log::warn!("Cannot record frame: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl SecureChannel for RecordingSecureChannel {
+    fn send_msg(&self, data: Vec<u8>) -> Result<(), Error> {
+        self.record(RecordDirection::Sent, &data);
+        self.inner.send_msg(data)
+    }
+
+    fn recv_msg(&self) -> Result<Vec<u8>, Error> {
+        let data = self.inner.recv_msg()?;
+        self.record(RecordDirection::Received, &data);
+        Ok(data)
+    }
+
+    fn terminate(&self) {
+        self.inner.terminate()
+    }
+
+    fn get_peer_certificate(&self) -> X509 {
+        self.inner.get_peer_certificate()
+    }
+
+    fn get_channel_binding(&self) -> Option<Vec<u8>> {
+        self.inner.get_channel_binding()
+    }
+}
+
+// Secure channel client wrapper that records every established connection
+pub struct RecordingClient<SCC: SecureChannelClient> {
+    pub inner: SCC,
+    pub config: SessionRecordingConfig,
+}
+
+impl<SCC> SecureChannelClient for RecordingClient<SCC>
+where
+    SCC: SecureChannelClient,
+    SCC::SC: SecureChannel + Send + Sync,
+{
+    type SC = RecordingSecureChannel;
+    type AddrType = SCC::AddrType;
+
+    fn connect(&self, server_addr: &Self::AddrType) -> anyhow::Result<Self::SC> {
+        let sc = self.inner.connect(server_addr)?;
+        let sc = RecordingSecureChannel::create(Arc::new(sc), &self.config)?;
+        Ok(sc)
+    }
+}
+
+// Secure channel server wrapper that records every incoming connection
+pub struct RecordingServer<SCS: SecureChannelServer> {
+    pub inner: SCS,
+    pub config: SessionRecordingConfig,
+}
+
+impl<SCS> SecureChannelServer for RecordingServer<SCS>
+where
+    SCS: SecureChannelServer,
+{
+    type SC = RecordingSecureChannel;
+    type AddrType = SCS::AddrType;
+
+    fn listen(
+        &mut self,
+        addr: Self::AddrType,
+        callback: SecureChannelIncomingConnectionCallback,
+    ) -> Result<(), &'static str> {
+        let config = self.config.clone();
+        self.inner.listen(
+            addr,
+            Arc::new(Mutex::new(Box::new(move |sc| {
+                let sc: Arc<dyn SecureChannel + Send + Sync> =
+                    match RecordingSecureChannel::create(Arc::clone(&sc), &config) {
+                        Err(e) => {
+                            log::warn!("Cannot record incoming connection: {}", e);
+                            sc
+                        }
+                        Ok(recording) => Arc::new(recording),
+                    };
+                let callback_guard = callback.lock().unwrap();
+                (*callback_guard)(sc);
+            }))),
+        )
+    }
+
+    fn stop(&mut self) {
+        self.inner.stop()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fsm::alternating_bit::AlternatingBit;
+    use crate::fsm::test_support::create_cert;
+    use crate::messages::idscp_message_factory::{create_idscp_dat_exp, create_idscp_data};
+    use std::io::Cursor;
+
+    pub(crate) fn encode(msg: IdscpMessage) -> Vec<u8> {
+        let mut raw = Vec::new();
+        msg.write_to_vec(&mut raw).unwrap();
+        raw
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.rec", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_recording_roundtrip() {
+        let path = temp_path("idscp_recording_roundtrip");
+        let cert = create_cert();
+        let data = encode(create_idscp_data(
+            b"secret payload".to_vec(),
+            &AlternatingBit::One,
+        ));
+        let dat_exp = encode(create_idscp_dat_exp());
+
+        {
+            let mut recorder = SessionRecorder::create(&path, &cert, false).unwrap();
+            recorder.record(RecordDirection::Sent, &data).unwrap();
+            recorder
+                .record(RecordDirection::Received, &dat_exp)
+                .unwrap();
+        }
+
+        let reader = SessionRecordReader::open(&path).unwrap();
+        assert_eq!(
+            reader.peer_certificate().to_der().unwrap(),
+            cert.to_der().unwrap()
+        );
+        let records: Vec<SessionRecord> = reader.map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, RecordDirection::Sent);
+        assert_eq!(records[0].frame, data);
+        assert!(!records[0].redacted);
+        assert_eq!(records[1].direction, RecordDirection::Received);
+        assert!(records[1].decode().unwrap().has_idscpDatExpired());
+        assert!(records[0].timestamp <= records[1].timestamp);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_recording_redaction() {
+        let path = temp_path("idscp_recording_redaction");
+        let cert = create_cert();
+        let data = encode(create_idscp_data(
+            b"secret payload".to_vec(),
+            &AlternatingBit::One,
+        ));
+        let dat_exp = encode(create_idscp_dat_exp());
+
+        {
+            let mut recorder = SessionRecorder::create(&path, &cert, true).unwrap();
+            recorder.record(RecordDirection::Received, &data).unwrap();
+            recorder.record(RecordDirection::Sent, &dat_exp).unwrap();
+            recorder.record(RecordDirection::Sent, b"\xff\xff").unwrap();
+        }
+
+        let records: Vec<SessionRecord> = SessionRecordReader::open(&path)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+
+        assert!(records[0].redacted);
+        let msg = records[0].decode().unwrap();
+        assert!(msg.get_idscpData().data.is_empty());
+        assert!(msg.get_idscpData().alternating_bit);
+
+        assert!(!records[1].redacted);
+        assert_eq!(records[1].frame, dat_exp);
+
+        assert!(records[2].redacted);
+        assert!(records[2].frame.is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_invalid_recording() {
+        assert!(SessionRecordReader::new(Cursor::new(b"NOTAREC\x01".to_vec())).is_err());
+
+        // truncated record after a valid header
+        let der = create_cert().to_der().unwrap();
+        let mut raw = Vec::new();
+        raw.extend_from_slice(MAGIC);
+        raw.push(FORMAT_VERSION);
+        raw.extend_from_slice(&(der.len() as u32).to_be_bytes());
+        raw.extend_from_slice(&der);
+        raw.extend_from_slice(&[1, 0, 0]);
+        let mut reader = SessionRecordReader::new(Cursor::new(raw)).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Replay of recorded sessions: all frames received from the recorded peer are fed into a fresh
+// FiniteStateMachine in their original order, to reproduce the transition sequence of the session.
+
+use super::{RecordDirection, SessionRecordReader};
+use crate::api::idscp_configuration::Idscp2Configuration;
+use crate::drivers::secure_channel::SecureChannel;
+use crate::fsm::{FiniteStateMachine, HandshakeResult, UserEvent};
+use crate::messages::idscpv2_messages::IdscpMessage;
+use openssl::x509::X509;
+use protobuf::Message;
+use std::io::{Error, ErrorKind, Read};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Cannot read session recording")]
+    RecordingError(#[from] std::io::Error),
+}
+
+#[derive(Debug)]
+pub struct ReplayedTransition {
+    // timestamp of the recorded frame
+    pub timestamp: SystemTime,
+    // recorded message of the peer, None if the frame could not be decoded
+    pub message: Option<IdscpMessage>,
+    pub from_state: String,
+    pub to_state: String,
+    // Err contains the error that was returned by the fsm
+    pub result: Result<(), String>,
+    // messages the replaying fsm has sent as reaction to the recorded message
+    pub sent: Vec<IdscpMessage>,
+}
+
+// secure channel that collects all sent frames and never receives anything
+struct ReplaySecureChannel {
+    peer_cert: X509,
+    sent: Mutex<Vec<Vec<u8>>>,
+    terminated: (Mutex<bool>, Condvar),
+}
+
+impl ReplaySecureChannel {
+    fn take_sent(&self) -> Vec<IdscpMessage> {
+        let frames: Vec<Vec<u8>> = self.sent.lock().unwrap().drain(..).collect();
+        frames
+            .iter()
+            .filter_map(|f| IdscpMessage::parse_from_bytes(f).ok())
+            .collect()
+    }
+}
+
+impl SecureChannel for ReplaySecureChannel {
+    fn send_msg(&self, data: Vec<u8>) -> Result<(), Error> {
+        self.sent.lock().unwrap().push(data);
+        Ok(())
+    }
+
+    fn recv_msg(&self) -> Result<Vec<u8>, Error> {
+        // peer messages are fed into the fsm directly, so block until the channel is terminated
+        let (lock, cvar) = &self.terminated;
+        let mut terminated = lock.lock().unwrap();
+        while !*terminated {
+            terminated = cvar.wait(terminated).unwrap();
+        }
+        Err(Error::new(
+            ErrorKind::ConnectionAborted,
+            "Replay channel terminated",
+        ))
+    }
+
+    fn terminate(&self) {
+        let (lock, cvar) = &self.terminated;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+    }
+
+    fn get_peer_certificate(&self) -> X509 {
+        self.peer_cert.clone()
+    }
+}
+
+// Replay a recording into a fresh fsm that is configured via the given configuration.
+// If realtime is set, the original delays between the recorded frames are reproduced, which is
+// required when the recorded session contains timeouts.
+pub fn replay_session<R: Read>(
+    recording: SessionRecordReader<R>,
+    config: &Idscp2Configuration,
+    realtime: bool,
+) -> Result<Vec<ReplayedTransition>, ReplayError> {
+    let sc = Arc::new(ReplaySecureChannel {
+        peer_cert: recording.peer_certificate().clone(),
+        sent: Mutex::new(Vec::new()),
+        terminated: (Mutex::new(false), Condvar::new()),
+    });
+
+    let (mut fsm, queue) = FiniteStateMachine::create(
+        Arc::clone(&sc) as Arc<dyn SecureChannel + Send + Sync>,
+        config.prover_registry.clone(),
+        config.verifier_registry.clone(),
+        Arc::clone(&config.daps),
+        Arc::new((Mutex::new(HandshakeResult::NotAvailable), Condvar::new())),
+        config.handshake_timeout,
+        config.ack_timeout,
+        config.rat_config.clone(),
+    );
+
+    // there is no upper layer the replayed messages could be delivered to
+    fsm.set_connection(None);
+    fsm.set_observer(config.fsm_observer.clone());
+    if let Err(e) = fsm.feed_user_event(UserEvent::StartHandshake) {
+        log::warn!("Cannot start handshake for replay: {}", e);
+    }
+    // the own hello is not part of the replayed transitions
+    sc.take_sent();
+
+    let mut transitions = Vec::new();
+    let mut last_timestamp: Option<SystemTime> = None;
+
+    for record in recording {
+        let record = record?;
+        if record.direction != RecordDirection::Received {
+            continue;
+        }
+
+        if realtime {
+            if let Some(last) = last_timestamp {
+                std::thread::sleep(
+                    record
+                        .timestamp
+                        .duration_since(last)
+                        .unwrap_or(Duration::from_secs(0)),
+                );
+            }
+            last_timestamp = Some(record.timestamp);
+        }
+
+        // the replay drives the fsm itself, timeouts and rat driver results that occurred
+        // in the meantime are processed before the recorded message
+        queue.process_pending(&mut fsm);
+
+        let message = record.decode();
+        let from_state = fsm.state_name();
+        let result = match message.as_ref().and_then(|m| m.message.clone()) {
+            None => Err("Cannot decode recorded frame".to_string()),
+            Some(body) => fsm.feed_peer_message(body).map_err(|e| e.to_string()),
+        };
+        let to_state = fsm.state_name();
+
+        transitions.push(ReplayedTransition {
+            timestamp: record.timestamp,
+            message,
+            from_state,
+            to_state,
+            result,
+            sent: sc.take_sent(),
+        });
+    }
+
+    let _ = fsm.feed_user_event(UserEvent::Stop);
+    sc.terminate();
+
+    Ok(transitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::idscp_configuration::AttestationConfig;
+    use crate::drivers::rat_driver::RatRegistry;
+    use crate::fsm::test_support::{create_cert, TestDaps};
+    use crate::messages::idscp_message_factory::{create_idscp_close, create_idscp_hello};
+    use crate::messages::idscpv2_messages::IdscpClose_CloseCause;
+    use crate::recording::tests::encode;
+    use crate::recording::SessionRecorder;
+
+    #[test]
+    fn test_replay_transitions() {
+        let path = std::env::temp_dir().join(format!(
+            "idscp_replay_transitions_{}.rec",
+            std::process::id()
+        ));
+        {
+            let mut recorder = SessionRecorder::create(&path, &create_cert(), false).unwrap();
+            let suite = vec!["NullRat".to_string()];
+            let hello = encode(create_idscp_hello(b"valid".to_vec(), &suite, &suite));
+            recorder.record(RecordDirection::Sent, &hello).unwrap();
+            recorder.record(RecordDirection::Received, &hello).unwrap();
+            let close = encode(create_idscp_close(
+                IdscpClose_CloseCause::USER_SHUTDOWN,
+                "User Shutdown",
+            ));
+            recorder.record(RecordDirection::Received, &close).unwrap();
+            recorder
+                .record(RecordDirection::Received, b"\xff\xff")
+                .unwrap();
+        }
+
+        let config = Idscp2Configuration {
+            rat_config: AttestationConfig {
+                supported_attestation_suite: vec!["NullRat".to_string()],
+                expected_attestation_suite: vec!["NullRat".to_string()],
+                rat_timeout: Duration::from_secs(3600),
+                ..Default::default()
+            },
+            daps: Arc::new(TestDaps::default()),
+            dat_verifier: None,
+            prover_registry: RatRegistry::new(),
+            verifier_registry: RatRegistry::new(),
+            handshake_timeout: Duration::from_secs(5),
+            ack_timeout: Duration::from_secs(1),
+            fsm_observer: None,
+        };
+
+        let transitions =
+            replay_session(SessionRecordReader::open(&path).unwrap(), &config, false).unwrap();
+        assert_eq!(transitions.len(), 3);
+
+        // no rat drivers are registered, so the hello locks the fsm
+        assert_eq!(transitions[0].from_state, "WaitForHello");
+        assert_eq!(transitions[0].to_state, "Closed(Locked)");
+        assert!(transitions[0].message.as_ref().unwrap().has_idscpHello());
+        assert!(transitions[0].result.is_err());
+
+        assert_eq!(transitions[1].from_state, "Closed(Locked)");
+        assert!(transitions[1].message.as_ref().unwrap().has_idscpClose());
+
+        assert!(transitions[2].message.is_none());
+        assert!(transitions[2].result.is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,660 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Verification of DATs issued by a DAPS. The signature is checked against the keys of the DAPS,
+// which are given as JWKS (RFC 7517) or certificate. Afterwards the standard claims and the
+// security requirements on the peer connector are validated.
+
+use super::http;
+use super::json::JsonValue;
+use super::jwt::{self, Jwt};
+use super::unix_now;
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::daps_driver::{DapsError, DatClaims, TokenVerifier, VerifiedDat};
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::x509::{X509Ref, X509};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CLOCK_SKEW_LEEWAY: i64 = 10; // seconds, tolerated for nbf and iat
+const MIN_JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub enum KeySource {
+    // JWKS endpoint of the DAPS, e.g. https://daps.aisec.fraunhofer.de/.well-known/jwks.json.
+    // The keys are fetched on creation of the verifier and cached for cache_duration, unknown
+    // key ids trigger an earlier refresh. Refreshes run in the background, tokens are verified
+    // with the cached keys in the meantime
+    JwksUrl {
+        url: String,
+        // trusted CAs for https, None for the system trust store
+        trusted_ca_file_path: Option<PathBuf>,
+        cache_duration: Duration,
+    },
+    JwksFile(PathBuf),
+    // certificate of the DAPS, whose key signs the DATs
+    Certificate(PathBuf),
+    // certificates or public keys in PEM, each of them is accepted. Several files allow
+    // rotating the signing key without interruption
+    PemFiles(Vec<PathBuf>),
+}
+
+// security profiles of the IDS information model, ordered by their level of trust
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityProfile {
+    Base,
+    Trust,
+    TrustPlus,
+}
+
+impl SecurityProfile {
+    // accepts the short and the long names, with idsc: prefix or as IRI
+    pub fn parse(profile: &str) -> Option<SecurityProfile> {
+        let name = profile
+            .strip_prefix("idsc:")
+            .or_else(|| profile.strip_prefix("https://w3id.org/idsa/code/"))
+            .unwrap_or(profile);
+        match name {
+            "BASE_SECURITY_PROFILE" | "BASE_CONNECTOR_SECURITY_PROFILE" => {
+                Some(SecurityProfile::Base)
+            }
+            "TRUST_SECURITY_PROFILE" | "TRUSTED_CONNECTOR_SECURITY_PROFILE" => {
+                Some(SecurityProfile::Trust)
+            }
+            "TRUST_PLUS_SECURITY_PROFILE" | "TRUSTED_CONNECTOR_PLUS_SECURITY_PROFILE" => {
+                Some(SecurityProfile::TrustPlus)
+            }
+            _ => None,
+        }
+    }
+}
+
+// requirements on the peer connector, described by the claims of its DAT
+#[derive(Debug, Clone, Default)]
+pub struct SecurityRequirements {
+    pub min_security_profile: Option<SecurityProfile>,
+    pub require_referring_connector: bool,
+    // if not empty, the referringConnector claim must be one of these
+    pub allowed_referring_connectors: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct DatVerifierConfig {
+    pub key_source: KeySource,
+    // expected iss claim, None accepts every issuer that holds one of the keys
+    pub issuer: Option<String>,
+    // audience that must be contained in the aud claim, None skips the check
+    pub audience: Option<String>,
+    pub security_requirements: SecurityRequirements,
+    // reject DATs without transportCertsSha256 claim. If the claim is present, it must always
+    // contain the fingerprint of the peer's TLS certificate
+    pub require_cert_binding: bool,
+}
+
+impl Default for DatVerifierConfig {
+    fn default() -> Self {
+        DatVerifierConfig {
+            key_source: KeySource::Certificate(PathBuf::new()),
+            issuer: None,
+            audience: Some("idsc:IDS_CONNECTORS_ALL".to_string()),
+            security_requirements: SecurityRequirements::default(),
+            require_cert_binding: true,
+        }
+    }
+}
+
+struct Jwk {
+    kid: Option<String>,
+    key: PKey<Public>,
+}
+
+struct KeyCache {
+    keys: Vec<Jwk>,
+    fetched_at: Option<Instant>,
+    refreshing: bool,
+}
+
+pub struct DatVerifier {
+    config: DatVerifierConfig,
+    keys: Arc<Mutex<KeyCache>>,
+}
+
+// RSA keys of a JWKS, keys of other types and usages are skipped
+fn parse_jwks(document: &str) -> Result<Vec<Jwk>, Error> {
+    let jwks = JsonValue::parse(document)?;
+    let entries = match jwks.get("keys") {
+        Some(JsonValue::Array(entries)) => entries,
+        _ => return Err(anyhow!("JWKS contains no keys array")),
+    };
+
+    let mut keys = Vec::new();
+    for entry in entries {
+        let field = |name: &str| entry.get(name).and_then(|v| v.as_str());
+        if field("kty") != Some("RSA") || field("use").unwrap_or("sig") != "sig" {
+            continue;
+        }
+        let component = |name: &str| -> Result<BigNum, Error> {
+            let raw = field(name).ok_or_else(|| anyhow!("RSA JWK lacks '{}'", name))?;
+            Ok(BigNum::from_slice(&jwt::base64url_decode(raw)?)?)
+        };
+        let rsa = Rsa::from_public_components(component("n")?, component("e")?)?;
+        keys.push(Jwk {
+            kid: field("kid").map(|k| k.to_string()),
+            key: PKey::from_rsa(rsa)?,
+        });
+    }
+    Ok(keys)
+}
+
+fn fetch_jwks(url: &str, trusted_ca_file_path: Option<&Path>) -> Result<Vec<Jwk>, Error> {
+    log::debug!("Fetch JWKS of DAPS from {}", url);
+    let response = http::get(url, trusted_ca_file_path)?;
+    if response.status != 200 {
+        return Err(anyhow!("DAPS responded with status {}", response.status));
+    }
+    parse_jwks(std::str::from_utf8(&response.body)?)
+}
+
+// string claims may also be given as array of strings, e.g. aud
+fn string_list(value: Option<&JsonValue>) -> Vec<String> {
+    match value {
+        Some(JsonValue::String(s)) => vec![s.clone()],
+        Some(JsonValue::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// value of the transportCertsSha256 claim for a TLS certificate: lowercase hex SHA-256 of the DER
+pub fn transport_cert_sha256(cert: &X509Ref) -> Result<String, Error> {
+    let digest = cert.digest(MessageDigest::sha256())?;
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub(crate) fn dat_claims(claims: &JsonValue) -> DatClaims {
+    let string = |name: &str| {
+        claims
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let timestamp = |name: &str| claims.get(name).and_then(|v| v.as_i64());
+    DatClaims {
+        issuer: string("iss"),
+        subject: string("sub"),
+        audience: string_list(claims.get("aud")),
+        issued_at: timestamp("iat"),
+        not_before: timestamp("nbf"),
+        expires_at: timestamp("exp"),
+        security_profile: string("securityProfile"),
+        referring_connector: string("referringConnector"),
+        transport_certs_sha256: string_list(claims.get("transportCertsSha256")),
+        json: claims.to_string(),
+    }
+}
+
+impl DatVerifier {
+    // all key sources are loaded right away. An unavailable JWKS endpoint is not fatal, it is
+    // retried when the first token is verified
+    pub fn new(config: DatVerifierConfig) -> Result<DatVerifier, Error> {
+        let mut fetched_at = None;
+        let keys = match &config.key_source {
+            KeySource::JwksUrl {
+                url,
+                trusted_ca_file_path,
+                ..
+            } => match fetch_jwks(url, trusted_ca_file_path.as_deref()) {
+                Ok(keys) => {
+                    fetched_at = Some(Instant::now());
+                    keys
+                }
+                Err(e) => {
+                    log::warn!("Cannot fetch JWKS of DAPS: {}", e);
+                    Vec::new()
+                }
+            },
+            KeySource::JwksFile(path) => parse_jwks(&std::fs::read_to_string(path)?)?,
+            KeySource::Certificate(path) => vec![Jwk {
+                kid: None,
+                key: X509::from_pem(&std::fs::read(path)?)?.public_key()?,
+            }],
+            KeySource::PemFiles(paths) => paths
+                .iter()
+                .map(|path| {
+                    let pem = std::fs::read(path)?;
+                    let key = match X509::from_pem(&pem) {
+                        Ok(cert) => cert.public_key()?,
+                        Err(_) => PKey::public_key_from_pem(&pem)?,
+                    };
+                    Ok(Jwk { kid: None, key })
+                })
+                .collect::<Result<Vec<Jwk>, Error>>()?,
+        };
+        Ok(DatVerifier {
+            config,
+            keys: Arc::new(Mutex::new(KeyCache {
+                keys,
+                fetched_at,
+                refreshing: false,
+            })),
+        })
+    }
+
+    // tokens are verified by the fsm, which must not be blocked by the DAPS. Hence the JWKS is
+    // fetched by a separate thread, which replaces the cached keys once it has got them
+    fn refresh_keys(&self, cache: &mut KeyCache) {
+        if let KeySource::JwksUrl {
+            url,
+            trusted_ca_file_path,
+            ..
+        } = &self.config.key_source
+        {
+            if cache.refreshing {
+                return;
+            }
+            cache.refreshing = true;
+            // do not hammer the DAPS with tokens of unknown keys
+            cache.fetched_at = Some(Instant::now());
+
+            let (url, trusted_ca_file_path) = (url.clone(), trusted_ca_file_path.clone());
+            let keys = Arc::clone(&self.keys);
+            thread::spawn(move || {
+                let result = fetch_jwks(&url, trusted_ca_file_path.as_deref());
+                let mut cache = keys.lock().unwrap();
+                cache.refreshing = false;
+                match result {
+                    Ok(jwks) => cache.keys = jwks,
+                    Err(e) => log::warn!("Cannot fetch JWKS of DAPS: {}", e),
+                }
+            });
+        }
+    }
+
+    fn verify_signature(&self, jwt: &Jwt) -> Result<(), Error> {
+        let kid = jwt.header.get("kid").and_then(|k| k.as_str());
+        let mut cache = self.keys.lock().unwrap();
+
+        if let KeySource::JwksUrl { cache_duration, .. } = &self.config.key_source {
+            let age = cache.fetched_at.map(|t| t.elapsed());
+            let outdated = age.map_or(true, |a| a >= *cache_duration);
+            // the DAPS might have rotated its keys
+            let unknown_kid = kid.is_some()
+                && !cache.keys.iter().any(|k| k.kid.as_deref() == kid)
+                && age.map_or(true, |a| a >= MIN_JWKS_REFRESH_INTERVAL);
+            if outdated || unknown_kid {
+                self.refresh_keys(&mut cache);
+            }
+        }
+
+        let candidates: Vec<&Jwk> = match kid {
+            Some(kid) => cache
+                .keys
+                .iter()
+                .filter(|k| k.kid.is_none() || k.kid.as_deref() == Some(kid))
+                .collect(),
+            None => cache.keys.iter().collect(),
+        };
+        if candidates.is_empty() {
+            return Err(anyhow!("No key of the DAPS matches key id {:?}", kid));
+        }
+        if candidates
+            .iter()
+            .any(|k| jwt.verify_signature(&k.key).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid DAT signature"))
+        }
+    }
+
+    fn check_claims(&self, claims: &DatClaims) -> Result<Duration, Error> {
+        let now = unix_now();
+        let exp = claims
+            .expires_at
+            .ok_or_else(|| anyhow!("DAT has no exp claim"))?;
+        if exp <= now {
+            return Err(anyhow!("DAT has expired"));
+        }
+        let nbf = claims
+            .not_before
+            .ok_or_else(|| anyhow!("DAT has no nbf claim"))?;
+        if nbf > now + CLOCK_SKEW_LEEWAY {
+            return Err(anyhow!("DAT is not valid yet"));
+        }
+        let iat = claims
+            .issued_at
+            .ok_or_else(|| anyhow!("DAT has no iat claim"))?;
+        if iat > now + CLOCK_SKEW_LEEWAY {
+            return Err(anyhow!("DAT was issued in the future"));
+        }
+
+        if let Some(issuer) = &self.config.issuer {
+            if claims.issuer.as_ref() != Some(issuer) {
+                return Err(anyhow!("DAT was issued by {:?}", claims.issuer));
+            }
+        }
+        if let Some(audience) = &self.config.audience {
+            if !claims.audience.contains(audience) {
+                return Err(anyhow!("DAT is not intended for audience {}", audience));
+            }
+        }
+        Ok(Duration::from_secs((exp - now) as u64))
+    }
+
+    fn check_security_requirements(&self, claims: &DatClaims) -> Result<(), Error> {
+        let requirements = &self.config.security_requirements;
+
+        if let Some(min_profile) = requirements.min_security_profile {
+            let profile = claims
+                .security_profile
+                .as_deref()
+                .and_then(SecurityProfile::parse)
+                .ok_or_else(|| anyhow!("Unknown security profile {:?}", claims.security_profile))?;
+            if profile < min_profile {
+                return Err(anyhow!(
+                    "Security profile {:?} is below {:?}",
+                    profile,
+                    min_profile
+                ));
+            }
+        }
+
+        let allowed = &requirements.allowed_referring_connectors;
+        match &claims.referring_connector {
+            None if requirements.require_referring_connector || !allowed.is_empty() => {
+                Err(anyhow!("DAT has no referringConnector claim"))
+            }
+            Some(connector) if !allowed.is_empty() && !allowed.contains(connector) => {
+                Err(anyhow!("Referring connector {} is not allowed", connector))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_cert_binding(&self, claims: &DatClaims, peer_cert: &X509Ref) -> Result<(), Error> {
+        if claims.transport_certs_sha256.is_empty() && !self.config.require_cert_binding {
+            return Ok(());
+        }
+        let fingerprint = transport_cert_sha256(peer_cert)?;
+        if claims
+            .transport_certs_sha256
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(&fingerprint))
+        {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Fingerprint {} is not bound to the DAT",
+                fingerprint
+            ))
+        }
+    }
+
+    // peer_cert is the TLS certificate the token was received over, None skips the binding
+    pub fn verify(&self, token: &str, peer_cert: Option<&X509>) -> Result<VerifiedDat, DapsError> {
+        let invalid = |e: Error| DapsError::InvalidDat(e.to_string());
+        let jwt = Jwt::decode(token).map_err(invalid)?;
+        self.verify_signature(&jwt).map_err(invalid)?;
+
+        let claims = dat_claims(&jwt.claims);
+        let validity = self.check_claims(&claims).map_err(invalid)?;
+        if let Some(cert) = peer_cert {
+            if let Err(e) = self.check_cert_binding(&claims, cert) {
+                log::warn!("{}", e);
+                return Err(DapsError::CertificateBindingMismatch);
+            }
+        }
+        self.check_security_requirements(&claims)
+            .map_err(|e| DapsError::SecurityRequirementsNotMet(e.to_string()))?;
+
+        Ok(VerifiedDat { validity, claims })
+    }
+}
+
+impl TokenVerifier for DatVerifier {
+    fn verify_token(&self, token: &str, peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        self.verify(token, Some(peer_cert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock_daps::{create_driver, MockDaps, MockDapsConfig};
+    use super::*;
+    use idscp_core::drivers::daps_driver::DapsDriver;
+    use openssl::asn1::Asn1Time;
+    use openssl::pkey::Private;
+    use std::collections::BTreeMap;
+
+    fn jwks(keys: &[(&str, &PKey<Private>)]) -> String {
+        let entries = keys
+            .iter()
+            .map(|(kid, key)| {
+                let rsa = key.rsa().unwrap();
+                let mut jwk = BTreeMap::new();
+                jwk.insert("kty".to_string(), JsonValue::from("RSA"));
+                jwk.insert("kid".to_string(), JsonValue::from(*kid));
+                jwk.insert(
+                    "n".to_string(),
+                    jwt::base64url_encode(&rsa.n().to_vec()).into(),
+                );
+                jwk.insert(
+                    "e".to_string(),
+                    jwt::base64url_encode(&rsa.e().to_vec()).into(),
+                );
+                JsonValue::Object(jwk)
+            })
+            .collect();
+        let mut document = BTreeMap::new();
+        document.insert("keys".to_string(), JsonValue::Array(entries));
+        JsonValue::Object(document).to_string()
+    }
+
+    fn claims(profile: &str, connector: &str) -> BTreeMap<String, JsonValue> {
+        let now = unix_now();
+        let mut claims = BTreeMap::new();
+        claims.insert("iss".to_string(), "https://daps.test".into());
+        claims.insert("sub".to_string(), "A:keyid:B".into());
+        claims.insert(
+            "aud".to_string(),
+            JsonValue::Array(vec!["idsc:IDS_CONNECTORS_ALL".into()]),
+        );
+        claims.insert("iat".to_string(), now.into());
+        claims.insert("nbf".to_string(), now.into());
+        claims.insert("exp".to_string(), (now + 600).into());
+        claims.insert("securityProfile".to_string(), profile.into());
+        claims.insert("referringConnector".to_string(), connector.into());
+        claims
+    }
+
+    fn sign(claims: &BTreeMap<String, JsonValue>, key: &PKey<Private>, kid: &str) -> String {
+        jwt::sign_rs256(&JsonValue::Object(claims.clone()), key, Some(kid)).unwrap()
+    }
+
+    #[test]
+    fn test_dat_verifier_claims_and_requirements() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("idscp2_jwks_{}.json", std::process::id()));
+        std::fs::write(&path, jwks(&[("k1", &key)])).unwrap();
+        let verifier = DatVerifier::new(DatVerifierConfig {
+            key_source: KeySource::JwksFile(path.clone()),
+            issuer: Some("https://daps.test".to_string()),
+            security_requirements: SecurityRequirements {
+                min_security_profile: Some(SecurityProfile::Trust),
+                require_referring_connector: true,
+                allowed_referring_connectors: vec!["http://connector-a".to_string()],
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let valid = claims(
+            "idsc:TRUSTED_CONNECTOR_SECURITY_PROFILE",
+            "http://connector-a",
+        );
+        let verified = verifier.verify(&sign(&valid, &key, "k1"), None).unwrap();
+        assert!(verified.validity > Duration::from_secs(590));
+        assert_eq!(verified.claims.issuer.as_deref(), Some("https://daps.test"));
+        assert_eq!(
+            verified.claims.referring_connector.as_deref(),
+            Some("http://connector-a")
+        );
+
+        // unknown key ids and foreign keys
+        assert!(verifier.verify(&sign(&valid, &key, "k2"), None).is_err());
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        assert!(verifier.verify(&sign(&valid, &other, "k1"), None).is_err());
+
+        // standard claims
+        let now = unix_now();
+        let invalid_claims = [
+            ("exp", JsonValue::from(now - 1)),
+            ("nbf", JsonValue::from(now + 60)),
+            ("iat", JsonValue::from(now + 60)),
+            ("iss", JsonValue::from("https://other-daps.test")),
+            ("aud", JsonValue::from("idsc:OTHER_AUDIENCE")),
+        ];
+        for (name, value) in invalid_claims.iter() {
+            let mut c = valid.clone();
+            c.insert(name.to_string(), value.clone());
+            assert!(
+                matches!(
+                    verifier.verify(&sign(&c, &key, "k1"), None),
+                    Err(DapsError::InvalidDat(_))
+                ),
+                "{} not checked",
+                name
+            );
+            c.remove(*name);
+            assert!(verifier.verify(&sign(&c, &key, "k1"), None).is_err());
+        }
+
+        // security requirements
+        for (profile, connector) in [
+            ("idsc:BASE_SECURITY_PROFILE", "http://connector-a"),
+            (
+                "idsc:TRUSTED_CONNECTOR_SECURITY_PROFILE",
+                "http://connector-b",
+            ),
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                verifier.verify(&sign(&claims(profile, connector), &key, "k1"), None),
+                Err(DapsError::SecurityRequirementsNotMet(_))
+            ));
+        }
+        let mut plus = claims("idsc:TRUST_PLUS_SECURITY_PROFILE", "http://connector-a");
+        assert!(verifier.verify(&sign(&plus, &key, "k1"), None).is_ok());
+        plus.remove("referringConnector");
+        assert!(verifier.verify(&sign(&plus, &key, "k1"), None).is_err());
+    }
+
+    fn create_cert(key: &PKey<Private>) -> X509 {
+        let mut builder = X509::builder().unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        builder.sign(key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn test_dat_verifier_cert_binding() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("idscp2_binding_{}.pem", std::process::id()));
+        std::fs::write(&path, create_cert(&key).to_pem().unwrap()).unwrap();
+        let mut config = DatVerifierConfig {
+            key_source: KeySource::Certificate(path.clone()),
+            ..Default::default()
+        };
+        let verifier = DatVerifier::new(config.clone()).unwrap();
+        config.require_cert_binding = false;
+        let lenient = DatVerifier::new(config).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let peer_cert = create_cert(&key);
+        let other_cert = create_cert(&PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap());
+        let mut dat = claims("idsc:BASE_SECURITY_PROFILE", "http://connector-a");
+        let unbound = sign(&dat, &key, "k1");
+        dat.insert(
+            "transportCertsSha256".to_string(),
+            transport_cert_sha256(&peer_cert).unwrap().into(),
+        );
+        let bound = sign(&dat, &key, "k1");
+
+        assert!(verifier.verify(&bound, Some(&peer_cert)).is_ok());
+        assert_eq!(
+            verifier.verify(&bound, Some(&other_cert)),
+            Err(DapsError::CertificateBindingMismatch)
+        );
+        assert_eq!(
+            verifier.verify(&unbound, Some(&peer_cert)),
+            Err(DapsError::CertificateBindingMismatch)
+        );
+        // present bindings are checked even if they are not required
+        assert!(lenient.verify(&unbound, Some(&peer_cert)).is_ok());
+        assert!(lenient.verify(&bound, Some(&other_cert)).is_err());
+        // the binding of own tokens is not checked
+        assert!(verifier.verify(&unbound, None).is_ok());
+    }
+
+    #[test]
+    fn test_dat_verifier_jwks_rotation() {
+        // short lived dats are not cached by the driver
+        let mock = MockDaps::start(MockDapsConfig {
+            token_lifetime: Duration::from_secs(20),
+            ..Default::default()
+        })
+        .unwrap();
+        let daps = create_driver(&mock, "test_client");
+        let verifier = DatVerifier::new(mock.verifier_config()).unwrap();
+        let requests = mock.jwks_requests();
+
+        // the JWKS is fetched on creation and cached afterwards
+        let old = daps.get_token().unwrap().token;
+        assert!(verifier.verify(&old, None).is_ok());
+        assert!(verifier.verify(&old, None).is_ok());
+        assert_eq!(mock.jwks_requests(), requests);
+
+        // a new key id triggers a refresh, but not more often than the minimum interval
+        mock.rotate_key().unwrap();
+        let new = daps.get_token().unwrap().token;
+        assert!(verifier.verify(&new, None).is_err());
+        assert_eq!(mock.jwks_requests(), requests);
+        verifier.keys.lock().unwrap().fetched_at = Some(Instant::now() - MIN_JWKS_REFRESH_INTERVAL);
+        // the refresh does not block the verification, the token is rejected until it is done
+        assert!(verifier.verify(&new, None).is_err());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while verifier.keys.lock().unwrap().refreshing {
+            assert!(Instant::now() < deadline, "JWKS was not refreshed");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(verifier.verify(&new, None).is_ok());
+        assert!(verifier.verify(&old, None).is_ok());
+        assert_eq!(mock.jwks_requests(), requests + 1);
+    }
+}
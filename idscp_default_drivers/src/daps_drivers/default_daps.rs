@@ -0,0 +1,277 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// DAPS driver for the IDS Dynamic Attribute Provisioning Service. The DAT is requested via the
+// OAuth2 client credentials flow, authenticated by a JWT client assertion that is signed with
+// the private key of the connector
+
+use super::dat_verifier::{DatVerifier, DatVerifierConfig};
+use super::http;
+use super::json::JsonValue;
+use super::jwt::{self, Jwt};
+use super::unix_now;
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::daps_driver::{DapsDriver, DapsError, Dat, TokenVerifier, VerifiedDat};
+use openssl::pkey::{PKey, Private};
+use openssl::x509::{X509Ref, X509};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+const CLIENT_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+const CLIENT_ASSERTION_LIFETIME: i64 = 60; // seconds
+
+pub struct DefaultDapsConfig {
+    // token endpoint of the DAPS, e.g. https://daps.aisec.fraunhofer.de/v2/token
+    pub token_url: String,
+    // private key and certificate of the connector, the connector id is derived from the
+    // certificate's SKI and AKI
+    pub key_file_path: PathBuf,
+    pub cert_file_path: PathBuf,
+    // keys of the DAPS, claims and security requirements for verifying received dats
+    pub verifier: DatVerifierConfig,
+    // trusted CAs for https connections to the DAPS, None for the system trust store
+    pub trusted_ca_file_path: Option<PathBuf>,
+    // the cached dat is renewed this margin before it expires
+    pub renewal_margin: Duration,
+    pub audience: String,
+    pub scope: String,
+}
+
+impl Default for DefaultDapsConfig {
+    fn default() -> Self {
+        DefaultDapsConfig {
+            token_url: String::new(),
+            key_file_path: PathBuf::new(),
+            cert_file_path: PathBuf::new(),
+            verifier: DatVerifierConfig::default(),
+            trusted_ca_file_path: None,
+            renewal_margin: Duration::from_secs(30),
+            audience: "idsc:IDS_CONNECTORS_ALL".to_string(),
+            scope: "idsc:IDS_CONNECTOR_ATTRIBUTES_ALL".to_string(),
+        }
+    }
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: i64, // unix timestamp
+}
+
+pub struct DefaultDaps {
+    config: DefaultDapsConfig,
+    key: PKey<Private>,
+    connector_id: String,
+    verifier: DatVerifier,
+    cached_token: Mutex<Option<CachedToken>>,
+}
+
+// connector id as registered at the DAPS: "<SKI>:keyid:<AKI>" with colon separated hex bytes
+pub fn connector_id(cert: &X509Ref) -> Result<String, Error> {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<String>>()
+            .join(":")
+    };
+    let ski = cert
+        .subject_key_id()
+        .ok_or_else(|| anyhow!("Certificate has no subject key identifier"))?;
+    let aki = cert
+        .authority_key_id()
+        .ok_or_else(|| anyhow!("Certificate has no authority key identifier"))?;
+    Ok(format!(
+        "{}:keyid:{}",
+        hex(ski.as_slice()),
+        hex(aki.as_slice())
+    ))
+}
+
+impl DefaultDaps {
+    pub fn new(config: DefaultDapsConfig) -> Result<DefaultDaps, Error> {
+        let key = PKey::private_key_from_pem(&std::fs::read(&config.key_file_path)?)?;
+        let cert = X509::from_pem(&std::fs::read(&config.cert_file_path)?)?;
+        let verifier = DatVerifier::new(config.verifier.clone())?;
+
+        Ok(DefaultDaps {
+            connector_id: connector_id(&cert)?,
+            verifier,
+            key,
+            config,
+            cached_token: Mutex::new(None),
+        })
+    }
+
+    fn client_assertion(&self) -> Result<String, Error> {
+        let now = unix_now();
+        let mut claims = BTreeMap::new();
+        claims.insert(
+            "@context".to_string(),
+            "https://w3id.org/idsa/contexts/context.jsonld".into(),
+        );
+        claims.insert("@type".to_string(), "ids:DatRequestToken".into());
+        claims.insert("iss".to_string(), self.connector_id.as_str().into());
+        claims.insert("sub".to_string(), self.connector_id.as_str().into());
+        claims.insert("aud".to_string(), self.config.audience.as_str().into());
+        claims.insert("iat".to_string(), now.into());
+        claims.insert("nbf".to_string(), now.into());
+        claims.insert("exp".to_string(), (now + CLIENT_ASSERTION_LIFETIME).into());
+        jwt::sign_rs256(&JsonValue::Object(claims), &self.key, None)
+    }
+
+    fn request_token(&self) -> Result<CachedToken, Error> {
+        log::debug!("Request new DAT from DAPS at {}", self.config.token_url);
+        let assertion = self.client_assertion()?;
+        let response = http::post_form(
+            &self.config.token_url,
+            &[
+                ("grant_type", "client_credentials"),
+                ("client_assertion_type", CLIENT_ASSERTION_TYPE),
+                ("client_assertion", &assertion),
+                ("scope", &self.config.scope),
+            ],
+            self.config.trusted_ca_file_path.as_deref(),
+        )?;
+        if response.status != 200 {
+            return Err(anyhow!("DAPS responded with status {}", response.status));
+        }
+
+        let body = JsonValue::parse(std::str::from_utf8(&response.body)?)?;
+        let token = body
+            .get("access_token")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| anyhow!("DAPS response contains no access_token"))?
+            .to_string();
+
+        // prefer the expiry of the token itself over the one announced by the DAPS
+        let expires_at = Jwt::decode(&token)
+            .ok()
+            .and_then(|jwt| jwt.claims.get("exp").and_then(|e| e.as_i64()))
+            .or_else(|| {
+                body.get("expires_in")
+                    .and_then(|e| e.as_i64())
+                    .map(|e| unix_now() + e)
+            })
+            .ok_or_else(|| anyhow!("Expiry of the DAT is unknown"))?;
+
+        Ok(CachedToken { token, expires_at })
+    }
+}
+
+impl CachedToken {
+    fn to_dat(&self) -> Dat {
+        Dat {
+            token: self.token.clone(),
+            expires_at: Some(UNIX_EPOCH + Duration::from_secs(self.expires_at.max(0) as u64)),
+        }
+    }
+}
+
+impl DapsDriver for DefaultDaps {
+    fn get_token(&self) -> Result<Dat, DapsError> {
+        let mut cache = self.cached_token.lock().unwrap();
+        let renew_at = unix_now() + self.config.renewal_margin.as_secs() as i64;
+        if let Some(cached) = &*cache {
+            if cached.expires_at > renew_at {
+                return Ok(cached.to_dat());
+            }
+        }
+
+        match self.request_token() {
+            Ok(fresh) => {
+                let dat = fresh.to_dat();
+                *cache = Some(fresh);
+                Ok(dat)
+            }
+            Err(e) => {
+                log::error!("Cannot get DAT from DAPS: {}", e);
+                // fall back to the cached token as long as it has not expired
+                match &*cache {
+                    Some(cached) if cached.expires_at > unix_now() => Ok(cached.to_dat()),
+                    _ => Err(DapsError::Unavailable(e.to_string())),
+                }
+            }
+        }
+    }
+}
+
+impl TokenVerifier for DefaultDaps {
+    fn verify_token(&self, token: &str, peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        self.verifier.verify_token(token, peer_cert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mock_daps::{create_driver, MockDaps, MockDapsConfig, MockDapsFault};
+    use super::*;
+    use openssl::rsa::Rsa;
+
+    fn start_mock_daps(lifetime: u64) -> MockDaps {
+        MockDaps::start(MockDapsConfig {
+            token_lifetime: Duration::from_secs(lifetime),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_daps_token_caching() {
+        let mock = start_mock_daps(3600);
+        let daps = create_driver(&mock, "test_client");
+
+        let token = daps.get_token().unwrap().token;
+        assert_eq!(daps.get_token().unwrap().token, token);
+        assert_eq!(mock.issued_tokens(), 1);
+        let validity = daps.verifier.verify(&token, None).unwrap().validity;
+        assert!(validity > Duration::from_secs(3500) && validity <= Duration::from_secs(3600));
+
+        // tampered claims and foreign signatures are rejected
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged_claims = jwt::base64url_encode(b"{\"exp\":99999999999}");
+        let forged = format!("{}.{}.{}", parts[0], forged_claims, parts[2]);
+        assert!(daps.verifier.verify(&forged, None).is_err());
+        let mut claims = BTreeMap::new();
+        claims.insert("exp".to_string(), (unix_now() + 60).into());
+        let other_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let foreign = jwt::sign_rs256(&JsonValue::Object(claims), &other_key, None).unwrap();
+        assert!(daps.verifier.verify(&foreign, None).is_err());
+
+        // expired dats of the daps are rejected as well
+        mock.set_fault(Some(MockDapsFault::ExpiredToken));
+        let expired = create_driver(&mock, "test_client").get_token().unwrap();
+        assert!(daps.verifier.verify(&expired.token, None).is_err());
+    }
+
+    #[test]
+    fn test_default_daps_token_renewal() {
+        // dats that expire within the renewal margin are requested again
+        let mock = start_mock_daps(20);
+        let daps = create_driver(&mock, "test_client");
+        let token = daps.get_token().unwrap().token;
+        assert!(daps.verifier.verify(&token, None).is_ok());
+        let _ = daps.get_token();
+        assert_eq!(mock.issued_tokens(), 2);
+    }
+
+    #[test]
+    fn test_default_daps_unavailable() {
+        let mock = start_mock_daps(3600);
+        let mut daps = create_driver(&mock, "test_client");
+        daps.config.token_url = "http://127.0.0.1:1/v2/token".to_string();
+        assert!(matches!(daps.get_token(), Err(DapsError::Unavailable(_))));
+    }
+}
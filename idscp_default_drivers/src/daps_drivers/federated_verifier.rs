@@ -0,0 +1,164 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Verification of DATs issued by several trusted DAPS instances. Each DAT is routed by its iss
+// claim to the verifier of its DAPS, such that every DAPS has its own keys and security
+// requirements. DATs of other issuers are rejected
+
+use super::dat_verifier::{DatVerifier, DatVerifierConfig};
+use super::jwt::Jwt;
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::daps_driver::{DapsError, TokenVerifier, VerifiedDat};
+use openssl::x509::X509;
+use std::collections::HashMap;
+
+pub struct FederatedVerifier {
+    verifiers: HashMap<String, DatVerifier>, // by issuer
+}
+
+impl FederatedVerifier {
+    // every config must name the issuer of its DAPS
+    pub fn new(trusted_daps: Vec<DatVerifierConfig>) -> Result<FederatedVerifier, Error> {
+        let mut verifiers = HashMap::new();
+        for config in trusted_daps {
+            let issuer = config
+                .issuer
+                .clone()
+                .ok_or_else(|| anyhow!("Trusted DAPS has no issuer"))?;
+            if verifiers.contains_key(&issuer) {
+                return Err(anyhow!("DAPS {} is configured twice", issuer));
+            }
+            verifiers.insert(issuer, DatVerifier::new(config)?);
+        }
+        Ok(FederatedVerifier { verifiers })
+    }
+
+    // peer_cert is the TLS certificate the token was received over, None skips the binding
+    pub fn verify(&self, token: &str, peer_cert: Option<&X509>) -> Result<VerifiedDat, DapsError> {
+        let jwt = Jwt::decode(token).map_err(|e| DapsError::InvalidDat(e.to_string()))?;
+        // the issuer is not trusted yet, but the verifier of the issuer checks the signature
+        let issuer = jwt.claims.get("iss").and_then(|i| i.as_str());
+        match issuer.and_then(|i| self.verifiers.get(i)) {
+            Some(verifier) => verifier.verify(token, peer_cert),
+            None => Err(DapsError::InvalidDat(format!(
+                "DAT was issued by untrusted DAPS {:?}",
+                issuer
+            ))),
+        }
+    }
+}
+
+impl TokenVerifier for FederatedVerifier {
+    fn verify_token(&self, token: &str, peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        self.verify(token, Some(peer_cert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dat_verifier::{KeySource, SecurityProfile, SecurityRequirements};
+    use super::super::json::JsonValue;
+    use super::super::jwt;
+    use super::*;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn dat(issuer: &str, profile: &str, key: &PKey<Private>) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut claims = BTreeMap::new();
+        claims.insert("iss".to_string(), JsonValue::from(issuer));
+        claims.insert("aud".to_string(), "idsc:IDS_CONNECTORS_ALL".into());
+        claims.insert("iat".to_string(), now.into());
+        claims.insert("nbf".to_string(), now.into());
+        claims.insert("exp".to_string(), (now + 600).into());
+        claims.insert("securityProfile".to_string(), profile.into());
+        jwt::sign_rs256(&JsonValue::Object(claims), key, None).unwrap()
+    }
+
+    fn trusted_daps(
+        issuer: &str,
+        key_file: PathBuf,
+        min_profile: Option<SecurityProfile>,
+    ) -> DatVerifierConfig {
+        DatVerifierConfig {
+            key_source: KeySource::PemFiles(vec![key_file]),
+            issuer: Some(issuer.to_string()),
+            security_requirements: SecurityRequirements {
+                min_security_profile: min_profile,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_federated_verifier() {
+        let key_a = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let key_b = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let dir = std::env::temp_dir().join(format!("idscp2_federated_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.pem"), key_a.public_key_to_pem().unwrap()).unwrap();
+        std::fs::write(dir.join("b.pem"), key_b.public_key_to_pem().unwrap()).unwrap();
+
+        // daps b only vouches for trusted connectors
+        let verifier = FederatedVerifier::new(vec![
+            trusted_daps("https://daps-a.test", dir.join("a.pem"), None),
+            trusted_daps(
+                "https://daps-b.test",
+                dir.join("b.pem"),
+                Some(SecurityProfile::Trust),
+            ),
+        ])
+        .unwrap();
+        assert!(FederatedVerifier::new(vec![DatVerifierConfig {
+            key_source: KeySource::PemFiles(vec![dir.join("a.pem")]),
+            ..Default::default()
+        }])
+        .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let base = "idsc:BASE_SECURITY_PROFILE";
+        let trust = "idsc:TRUSTED_CONNECTOR_SECURITY_PROFILE";
+        let verified = verifier
+            .verify(&dat("https://daps-a.test", base, &key_a), None)
+            .unwrap();
+        assert_eq!(
+            verified.claims.issuer.as_deref(),
+            Some("https://daps-a.test")
+        );
+        assert!(verifier
+            .verify(&dat("https://daps-b.test", trust, &key_b), None)
+            .is_ok());
+
+        // each daps applies its own keys and requirements
+        assert!(matches!(
+            verifier.verify(&dat("https://daps-b.test", base, &key_b), None),
+            Err(DapsError::SecurityRequirementsNotMet(_))
+        ));
+        assert!(matches!(
+            verifier.verify(&dat("https://daps-b.test", trust, &key_a), None),
+            Err(DapsError::InvalidDat(_))
+        ));
+        assert!(matches!(
+            verifier.verify(&dat("https://daps-c.test", base, &key_a), None),
+            Err(DapsError::InvalidDat(_))
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Minimal HTTP/1.1 client for the DAPS endpoints. Every request uses its own connection,
+// https is provided by openssl
+
+use anyhow::{anyhow, Error};
+use openssl::ssl::{SslConnector, SslMethod};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024; // byte
+
+pub(crate) struct HttpResponse {
+    pub(crate) status: u16,
+    pub(crate) body: Vec<u8>,
+}
+
+struct Url<'a> {
+    https: bool,
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+fn parse_url(url: &str) -> Result<Url<'_>, Error> {
+    let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (false, rest)
+    } else {
+        return Err(anyhow!("Unsupported url scheme: {}", url));
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rfind(':') {
+        Some(i) => (
+            &authority[..i],
+            authority[i + 1..]
+                .parse()
+                .map_err(|_| anyhow!("Invalid port in url: {}", url))?,
+        ),
+        None => (authority, if https { 443 } else { 80 }),
+    };
+    if host.is_empty() {
+        return Err(anyhow!("Missing host in url: {}", url));
+    }
+
+    Ok(Url {
+        https,
+        host,
+        port,
+        path,
+    })
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+// GET a JSON document. trusted_ca_file replaces the default trust store for https
+pub(crate) fn get(url: &str, trusted_ca_file: Option<&Path>) -> Result<HttpResponse, Error> {
+    let url = parse_url(url)?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+        url.path, url.host
+    );
+    send(&url, request.as_bytes(), trusted_ca_file)
+}
+
+// POST an application/x-www-form-urlencoded form. trusted_ca_file replaces the default trust
+// store for https
+pub(crate) fn post_form(
+    url: &str,
+    form: &[(&str, &str)],
+    trusted_ca_file: Option<&Path>,
+) -> Result<HttpResponse, Error> {
+    let body = form
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+        .collect::<Vec<String>>()
+        .join("&");
+    let url = parse_url(url)?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+         Content-Length: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        url.host,
+        body.len(),
+        body
+    );
+    send(&url, request.as_bytes(), trusted_ca_file)
+}
+
+// the connect timeout of the OS is up to several minutes, which is too long for a DAT request
+fn connect(url: &Url) -> Result<TcpStream, Error> {
+    let mut last_error = None;
+    for addr in (url.host, url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, IO_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        None => anyhow!("Cannot resolve {}", url.host),
+        Some(e) => anyhow!("Cannot connect to {}: {}", url.host, e),
+    })
+}
+
+fn send(url: &Url, request: &[u8], trusted_ca_file: Option<&Path>) -> Result<HttpResponse, Error> {
+    let stream = connect(url)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let raw = if url.https {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        if let Some(ca) = trusted_ca_file {
+            builder.set_ca_file(ca)?;
+        }
+        let mut tls_stream = builder
+            .build()
+            .connect(url.host, stream)
+            .map_err(|e| anyhow!("TLS handshake with {} failed: {}", url.host, e))?;
+        exchange(&mut tls_stream, request)?
+    } else {
+        let mut stream = stream;
+        exchange(&mut stream, request)?
+    };
+
+    parse_response(&raw)
+}
+
+fn exchange<S: Read + Write>(stream: &mut S, request: &[u8]) -> Result<Vec<u8>, Error> {
+    stream.write_all(request)?;
+    stream.flush()?;
+    let mut raw = Vec::new();
+    stream.take(MAX_RESPONSE_SIZE).read_to_end(&mut raw)?;
+    Ok(raw)
+}
+
+fn parse_response(raw: &[u8]) -> Result<HttpResponse, Error> {
+    let header_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Incomplete http response"))?;
+    let head = std::str::from_utf8(&raw[..header_end])?;
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|l| l.split(' ').nth(1))
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Invalid http status line"))?;
+
+    let mut chunked = false;
+    let mut content_length = None;
+    for line in lines {
+        if let Some(colon) = line.find(':') {
+            let name = &line[..colon];
+            let value = line[colon + 1..].trim();
+            if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            } else if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = &raw[header_end + 4..];
+    let body = if chunked {
+        let mut decoded = Vec::new();
+        loop {
+            let line_end = body
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .ok_or_else(|| anyhow!("Invalid chunked encoding"))?;
+            let size_line = std::str::from_utf8(&body[..line_end])?;
+            let size = usize::from_str_radix(size_line.split(';').next().unwrap().trim(), 16)
+                .map_err(|_| anyhow!("Invalid chunk size"))?;
+            body = &body[line_end + 2..];
+            if size == 0 {
+                break;
+            }
+            if body.len() < size {
+                return Err(anyhow!("Chunk was truncated"));
+            }
+            decoded.extend_from_slice(&body[..size]);
+            body = body.get(size + 2..).unwrap_or(&[]);
+        }
+        decoded
+    } else {
+        match content_length {
+            Some(len) if len <= body.len() => body[..len].to_vec(),
+            Some(_) => return Err(anyhow!("Http response was truncated")),
+            None => body.to_vec(),
+        }
+    };
+
+    Ok(HttpResponse { status, body })
+}
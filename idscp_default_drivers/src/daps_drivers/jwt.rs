@@ -0,0 +1,113 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// JSON Web Tokens in compact serialization, as used for DATs and DAPS client assertions.
+// Only RS256 is supported
+
+use super::json::JsonValue;
+use anyhow::{anyhow, Error};
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{HasPublic, PKeyRef, Private};
+use openssl::sign::{Signer, Verifier};
+use std::collections::BTreeMap;
+
+pub(crate) fn base64url_encode(data: &[u8]) -> String {
+    base64::encode_block(data)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+pub(crate) fn base64url_decode(data: &str) -> Result<Vec<u8>, Error> {
+    if data.contains(&['+', '/', '='][..]) {
+        return Err(anyhow!("Invalid base64url encoding"));
+    }
+    let mut padded = data.replace('-', "+").replace('_', "/");
+    let padding = (4 - padded.len() % 4) % 4;
+    padded.push_str(&"=".repeat(padding));
+    base64::decode_block(&padded).map_err(|_| anyhow!("Invalid base64url encoding"))
+}
+
+// kid names the signing key within the JWKS of the issuer
+pub(crate) fn sign_rs256(
+    claims: &JsonValue,
+    key: &PKeyRef<Private>,
+    kid: Option<&str>,
+) -> Result<String, Error> {
+    let mut header = BTreeMap::new();
+    header.insert("alg".to_string(), JsonValue::from("RS256"));
+    header.insert("typ".to_string(), JsonValue::from("JWT"));
+    if let Some(kid) = kid {
+        header.insert("kid".to_string(), JsonValue::from(kid));
+    }
+    let signing_input = format!(
+        "{}.{}",
+        base64url_encode(JsonValue::Object(header).to_string().as_bytes()),
+        base64url_encode(claims.to_string().as_bytes())
+    );
+
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    signer.update(signing_input.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+    Ok(format!(
+        "{}.{}",
+        signing_input,
+        base64url_encode(&signature)
+    ))
+}
+
+// a decoded, not yet verified jwt
+pub(crate) struct Jwt {
+    pub(crate) header: JsonValue,
+    pub(crate) claims: JsonValue,
+    signing_input: String,
+    signature: Vec<u8>,
+}
+
+impl Jwt {
+    pub(crate) fn decode(token: &str) -> Result<Jwt, Error> {
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 {
+            return Err(anyhow!("JWT must consist of three parts"));
+        }
+        let parse = |part: &str| -> Result<JsonValue, Error> {
+            let raw = base64url_decode(part)?;
+            JsonValue::parse(std::str::from_utf8(&raw)?)
+        };
+
+        Ok(Jwt {
+            header: parse(parts[0])?,
+            claims: parse(parts[1])?,
+            signing_input: format!("{}.{}", parts[0], parts[1]),
+            signature: base64url_decode(parts[2])?,
+        })
+    }
+
+    pub(crate) fn verify_signature<T: HasPublic>(&self, key: &PKeyRef<T>) -> Result<(), Error> {
+        // never trust the algorithm the token claims for itself beyond the supported one
+        match self.header.get("alg").and_then(|a| a.as_str()) {
+            Some("RS256") => {}
+            alg => return Err(anyhow!("Unsupported JWT algorithm {:?}", alg)),
+        }
+
+        let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
+        verifier.update(self.signing_input.as_bytes())?;
+        if verifier.verify(&self.signature)? {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid JWT signature"))
+        }
+    }
+}
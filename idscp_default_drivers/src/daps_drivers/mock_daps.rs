@@ -0,0 +1,598 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Local DAPS for tests. It serves the token endpoint and the JWKS endpoint via plain http and
+// issues real JWT DATs, signed by a key that is generated at startup and certified by the
+// test_pki CA. Lifetime and claims of the DATs can be changed and faults injected at runtime
+
+use super::dat_verifier::{transport_cert_sha256, DatVerifierConfig, KeySource};
+use super::default_daps::connector_id;
+#[cfg(test)]
+use super::default_daps::{DefaultDaps, DefaultDapsConfig};
+use super::json::JsonValue;
+use super::jwt::{self, Jwt};
+use super::unix_now;
+use anyhow::{anyhow, Error};
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::x509::extension::{AuthorityKeyIdentifier, SubjectKeyIdentifier};
+use openssl::x509::{X509Name, X509};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const TOKEN_PATH: &str = "/v2/token";
+pub const JWKS_PATH: &str = "/.well-known/jwks.json";
+const KEY_ID_PREFIX: &str = "mock-daps";
+const MAX_REQUEST_SIZE: usize = 64 * 1024; // byte
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MockDapsFault {
+    // DATs that have already expired when they are issued
+    ExpiredToken,
+    // DATs that are signed by a key which is not part of the JWKS
+    WrongSignature,
+    // the token endpoint responds with 500 Internal Server Error
+    ServerError,
+}
+
+pub struct MockDapsConfig {
+    // CA that certifies the signing key of the DAPS
+    pub ca_key_file_path: PathBuf,
+    pub ca_cert_file_path: PathBuf,
+    pub issuer: String,
+    pub token_lifetime: Duration,
+    // additional claims of every DAT, these replace the generated claims of the same name
+    pub claims: BTreeMap<String, JsonValue>,
+    // connectors that may request DATs, their fingerprints are bound to the DATs via
+    // transportCertsSha256. Empty to accept every client assertion without checking it
+    pub clients: Vec<X509>,
+}
+
+impl Default for MockDapsConfig {
+    fn default() -> Self {
+        let root_ca = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../test_pki/resources/openssl/root-ca"
+        );
+        MockDapsConfig {
+            ca_key_file_path: PathBuf::from(format!("{}/private/rootCA.key", root_ca)),
+            ca_cert_file_path: PathBuf::from(format!("{}/certs/rootCA.crt", root_ca)),
+            issuer: "https://mock-daps.idscp-test.de".to_string(),
+            token_lifetime: Duration::from_secs(3600),
+            claims: BTreeMap::new(),
+            clients: Vec::new(),
+        }
+    }
+}
+
+struct State {
+    // signing keys and their key ids, the last one signs the DATs. All of them are published
+    keys: Vec<(String, PKey<Private>)>,
+    token_lifetime: Duration,
+    claims: BTreeMap<String, JsonValue>,
+    fault: Option<MockDapsFault>,
+    issued_tokens: usize,
+    jwks_requests: usize,
+}
+
+struct Shared {
+    issuer: String,
+    // signs the DATs of MockDapsFault::WrongSignature
+    foreign_key: PKey<Private>,
+    clients: Vec<(String, X509)>, // connector id and certificate
+    state: Mutex<State>,
+    stopped: AtomicBool,
+}
+
+pub struct MockDaps {
+    url: String,
+    certificate: X509,
+    shared: Arc<Shared>,
+}
+
+// signing certificate of the DAPS, issued by the CA
+fn certify(key: &PKey<Private>, ca_key: &PKey<Private>, ca_cert: &X509) -> Result<X509, Error> {
+    let mut name = X509Name::builder()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, "mock-daps.idscp-test.de")?;
+    let name = name.build();
+    let mut serial = BigNum::new()?;
+    serial.rand(64, MsbOption::MAYBE_ZERO, false)?;
+
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    let serial = serial.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(1)?;
+    builder.set_serial_number(&serial)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(ca_cert.subject_name())?;
+    builder.set_pubkey(key)?;
+    builder.set_not_before(&not_before)?;
+    builder.set_not_after(&not_after)?;
+    let ski = SubjectKeyIdentifier::new().build(&builder.x509v3_context(Some(ca_cert), None))?;
+    builder.append_extension(ski)?;
+    let aki = AuthorityKeyIdentifier::new()
+        .keyid(true)
+        .build(&builder.x509v3_context(Some(ca_cert), None))?;
+    builder.append_extension(aki)?;
+    builder.sign(ca_key, MessageDigest::sha256())?;
+    Ok(builder.build())
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut decoded = Vec::new();
+    let mut bytes = value.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, Error> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some(colon) = line.find(':') {
+            if line[..colon].eq_ignore_ascii_case("content-length") {
+                content_length = line[colon + 1..].trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_SIZE {
+        return Err(anyhow!("Request body is too large"));
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request { method, path, body })
+}
+
+fn error_body(error: &str) -> String {
+    let mut body = BTreeMap::new();
+    body.insert("error".to_string(), JsonValue::from(error));
+    JsonValue::Object(body).to_string()
+}
+
+impl Shared {
+    fn jwks(&self) -> Result<String, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.jwks_requests += 1;
+        let mut keys = Vec::new();
+        for (kid, key) in state.keys.iter() {
+            let rsa = key.rsa()?;
+            let mut jwk = BTreeMap::new();
+            jwk.insert("kty".to_string(), JsonValue::from("RSA"));
+            jwk.insert("use".to_string(), JsonValue::from("sig"));
+            jwk.insert("alg".to_string(), JsonValue::from("RS256"));
+            jwk.insert("kid".to_string(), JsonValue::from(kid.as_str()));
+            jwk.insert(
+                "n".to_string(),
+                jwt::base64url_encode(&rsa.n().to_vec()).into(),
+            );
+            jwk.insert(
+                "e".to_string(),
+                jwt::base64url_encode(&rsa.e().to_vec()).into(),
+            );
+            keys.push(JsonValue::Object(jwk));
+        }
+        let mut document = BTreeMap::new();
+        document.insert("keys".to_string(), JsonValue::Array(keys));
+        Ok(JsonValue::Object(document).to_string())
+    }
+
+    // connector id and bound fingerprint of the client that authenticated by the assertion
+    fn authenticate(&self, assertion: &str) -> Result<(String, Option<String>), Error> {
+        let assertion = Jwt::decode(assertion)?;
+        let subject = assertion
+            .claims
+            .get("sub")
+            .and_then(|s| s.as_str())
+            .ok_or_else(|| anyhow!("Client assertion has no sub claim"))?
+            .to_string();
+        if self.clients.is_empty() {
+            return Ok((subject, None));
+        }
+
+        let (_, cert) = self
+            .clients
+            .iter()
+            .find(|(id, _)| *id == subject)
+            .ok_or_else(|| anyhow!("Unknown client {}", subject))?;
+        let public_key = cert.public_key()?;
+        assertion.verify_signature(&public_key)?;
+        let exp = assertion.claims.get("exp").and_then(|e| e.as_i64());
+        if exp.map_or(true, |exp| exp <= unix_now()) {
+            return Err(anyhow!("Client assertion has expired"));
+        }
+        Ok((subject, Some(transport_cert_sha256(cert)?)))
+    }
+
+    fn issue_token(&self, form: &BTreeMap<String, String>) -> (u16, String) {
+        let mut state = self.state.lock().unwrap();
+        if state.fault == Some(MockDapsFault::ServerError) {
+            return (500, error_body("server_error"));
+        }
+
+        if form.get("grant_type").map(|g| g.as_str()) != Some("client_credentials") {
+            return (400, error_body("unsupported_grant_type"));
+        }
+        let (subject, fingerprint) = match form
+            .get("client_assertion")
+            .ok_or_else(|| anyhow!("Request contains no client assertion"))
+            .and_then(|a| self.authenticate(a))
+        {
+            Ok(client) => client,
+            Err(e) => {
+                log::warn!("Mock DAPS rejects token request: {}", e);
+                return (401, error_body("invalid_client"));
+            }
+        };
+
+        let lifetime = state.token_lifetime.as_secs() as i64;
+        let now = match state.fault {
+            Some(MockDapsFault::ExpiredToken) => unix_now() - lifetime - 1,
+            _ => unix_now(),
+        };
+        let scope = form
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| "idsc:IDS_CONNECTOR_ATTRIBUTES_ALL".to_string());
+        let mut claims = BTreeMap::new();
+        claims.insert(
+            "@context".to_string(),
+            "https://w3id.org/idsa/contexts/context.jsonld".into(),
+        );
+        claims.insert("@type".to_string(), "ids:DatPayload".into());
+        claims.insert("iss".to_string(), self.issuer.as_str().into());
+        claims.insert("sub".to_string(), subject.into());
+        claims.insert(
+            "aud".to_string(),
+            JsonValue::Array(vec!["idsc:IDS_CONNECTORS_ALL".into()]),
+        );
+        claims.insert("iat".to_string(), now.into());
+        claims.insert("nbf".to_string(), now.into());
+        claims.insert("exp".to_string(), (now + lifetime).into());
+        claims.insert("scope".to_string(), scope.into());
+        claims.insert(
+            "securityProfile".to_string(),
+            "idsc:BASE_SECURITY_PROFILE".into(),
+        );
+        if let Some(fingerprint) = fingerprint {
+            claims.insert("transportCertsSha256".to_string(), fingerprint.into());
+        }
+        for (name, value) in state.claims.iter() {
+            claims.insert(name.clone(), value.clone());
+        }
+
+        let (kid, key) = state.keys.last().unwrap();
+        let key = match state.fault {
+            Some(MockDapsFault::WrongSignature) => &self.foreign_key,
+            _ => key,
+        };
+        let token = match jwt::sign_rs256(&JsonValue::Object(claims), key, Some(kid)) {
+            Ok(token) => token,
+            Err(e) => {
+                log::error!("Mock DAPS cannot sign DAT: {}", e);
+                return (500, error_body("server_error"));
+            }
+        };
+        state.issued_tokens += 1;
+
+        let mut body = BTreeMap::new();
+        body.insert("access_token".to_string(), JsonValue::from(token));
+        body.insert("token_type".to_string(), JsonValue::from("bearer"));
+        body.insert("expires_in".to_string(), JsonValue::from(lifetime));
+        body.insert(
+            "scope".to_string(),
+            JsonValue::from("idsc:IDS_CONNECTOR_ATTRIBUTES_ALL"),
+        );
+        (200, JsonValue::Object(body).to_string())
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> Result<(), Error> {
+        let request = read_request(&stream)?;
+        let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", JWKS_PATH) => (200, self.jwks()?),
+            ("POST", TOKEN_PATH) => {
+                let form = std::str::from_utf8(&request.body)?
+                    .split('&')
+                    .filter_map(|pair| pair.find('=').map(|i| (&pair[..i], &pair[i + 1..])))
+                    .filter_map(|(k, v)| Some((percent_decode(k)?, percent_decode(v)?)))
+                    .collect();
+                self.issue_token(&form)
+            }
+            _ => (404, error_body("not_found")),
+        };
+
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{}",
+            status,
+            reason,
+            body.len(),
+            body
+        )?;
+        Ok(())
+    }
+}
+
+impl MockDaps {
+    // binds to a free port on localhost and serves requests on a background thread until the
+    // MockDaps is dropped
+    pub fn start(config: MockDapsConfig) -> Result<MockDaps, Error> {
+        let ca_key = PKey::private_key_from_pem(&std::fs::read(&config.ca_key_file_path)?)?;
+        let ca_cert = X509::from_pem(&std::fs::read(&config.ca_cert_file_path)?)?;
+        let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+        let certificate = certify(&key, &ca_key, &ca_cert)?;
+        let clients = config
+            .clients
+            .into_iter()
+            .map(|cert| Ok((connector_id(&cert)?, cert)))
+            .collect::<Result<Vec<(String, X509)>, Error>>()?;
+
+        let shared = Arc::new(Shared {
+            issuer: config.issuer,
+            foreign_key: PKey::from_rsa(Rsa::generate(2048)?)?,
+            clients,
+            state: Mutex::new(State {
+                keys: vec![(format!("{}-1", KEY_ID_PREFIX), key)],
+                token_lifetime: config.token_lifetime,
+                claims: config.claims,
+                fault: None,
+                issued_tokens: 0,
+                jwks_requests: 0,
+            }),
+            stopped: AtomicBool::new(false),
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        log::info!("Mock DAPS is listening at {}", url);
+        let shared_clone = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shared_clone.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let result = stream
+                    .map_err(Error::from)
+                    .and_then(|s| shared_clone.handle(s));
+                if let Err(e) = result {
+                    log::warn!("Mock DAPS cannot handle request: {}", e);
+                }
+            }
+        });
+
+        Ok(MockDaps {
+            url,
+            certificate,
+            shared,
+        })
+    }
+
+    pub fn token_url(&self) -> String {
+        format!("{}{}", self.url, TOKEN_PATH)
+    }
+
+    pub fn jwks_url(&self) -> String {
+        format!("{}{}", self.url, JWKS_PATH)
+    }
+
+    // certificate of the initial signing key, issued by the CA of the config
+    pub fn certificate(&self) -> &X509 {
+        &self.certificate
+    }
+
+    // verifier config that fetches the keys from the JWKS endpoint and expects the issuer
+    pub fn verifier_config(&self) -> DatVerifierConfig {
+        DatVerifierConfig {
+            key_source: KeySource::JwksUrl {
+                url: self.jwks_url(),
+                trusted_ca_file_path: None,
+                cache_duration: Duration::from_secs(3600),
+            },
+            issuer: Some(self.shared.issuer.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_token_lifetime(&self, lifetime: Duration) {
+        self.shared.state.lock().unwrap().token_lifetime = lifetime;
+    }
+
+    pub fn set_claim(&self, name: &str, value: JsonValue) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.claims.insert(name.to_string(), value);
+    }
+
+    // the fault applies to all following token requests, None for correct DATs
+    pub fn set_fault(&self, fault: Option<MockDapsFault>) {
+        self.shared.state.lock().unwrap().fault = fault;
+    }
+
+    pub fn issued_tokens(&self) -> usize {
+        self.shared.state.lock().unwrap().issued_tokens
+    }
+
+    // DATs are signed by a new key from now on, which is added to the JWKS under a new key id
+    pub fn rotate_key(&self) -> Result<(), Error> {
+        let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+        let mut state = self.shared.state.lock().unwrap();
+        let kid = format!("{}-{}", KEY_ID_PREFIX, state.keys.len() + 1);
+        state.keys.push((kid, key));
+        Ok(())
+    }
+
+    pub fn jwks_requests(&self) -> usize {
+        self.shared.state.lock().unwrap().jwks_requests
+    }
+}
+
+impl Drop for MockDaps {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // unblock the listener, which checks the flag on the next connection
+        let _ = TcpStream::connect(self.url.trim_start_matches("http://"));
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_pki(file: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/../test_pki/resources/openssl/out/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        file
+    ))
+}
+
+// DefaultDaps of a test_pki identity. Dats with a lifetime below 30 seconds are requested anew
+// on every get_token, longer lived dats are cached
+#[cfg(test)]
+pub(crate) fn create_driver(mock: &MockDaps, name: &str) -> DefaultDaps {
+    DefaultDaps::new(DefaultDapsConfig {
+        token_url: mock.token_url(),
+        key_file_path: test_pki(&format!("{}.key", name)),
+        cert_file_path: test_pki(&format!("{}.crt", name)),
+        verifier: mock.verifier_config(),
+        renewal_margin: Duration::from_secs(30),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dat_verifier::DatVerifier;
+    use super::*;
+    use idscp_core::drivers::daps_driver::{DapsDriver, DapsError};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_mock_daps() {
+        let client_cert =
+            X509::from_pem(&std::fs::read(test_pki("test_client.crt")).unwrap()).unwrap();
+        let mut claims = BTreeMap::new();
+        claims.insert(
+            "referringConnector".to_string(),
+            JsonValue::from("http://test-client.idscp-test.de"),
+        );
+        let mock = MockDaps::start(MockDapsConfig {
+            token_lifetime: Duration::from_secs(20),
+            claims,
+            clients: vec![client_cert.clone()],
+            ..Default::default()
+        })
+        .unwrap();
+        let ca_cert =
+            X509::from_pem(&std::fs::read(MockDapsConfig::default().ca_cert_file_path).unwrap())
+                .unwrap();
+        assert!(mock
+            .certificate()
+            .verify(&ca_cert.public_key().unwrap())
+            .unwrap());
+
+        let daps = create_driver(&mock, "test_client");
+        let verifier = DatVerifier::new(mock.verifier_config()).unwrap();
+
+        mock.set_fault(Some(MockDapsFault::ServerError));
+        assert!(matches!(daps.get_token(), Err(DapsError::Unavailable(_))));
+
+        mock.set_fault(None);
+        let dat = daps.get_token().unwrap();
+        let verified = verifier.verify(&dat.token, Some(&client_cert)).unwrap();
+        assert!(verified.validity <= Duration::from_secs(20));
+        assert_eq!(
+            verified.claims.referring_connector.as_deref(),
+            Some("http://test-client.idscp-test.de")
+        );
+        assert_eq!(
+            verified.claims.subject,
+            Some(connector_id(&client_cert).unwrap())
+        );
+
+        mock.set_claim("securityProfile", "idsc:TRUST_SECURITY_PROFILE".into());
+        let dat = daps.get_token().unwrap();
+        let verified = verifier.verify(&dat.token, Some(&client_cert)).unwrap();
+        assert_eq!(
+            verified.claims.security_profile.as_deref(),
+            Some("idsc:TRUST_SECURITY_PROFILE")
+        );
+
+        mock.set_fault(Some(MockDapsFault::WrongSignature));
+        let dat = daps.get_token().unwrap();
+        assert!(matches!(
+            verifier.verify(&dat.token, Some(&client_cert)),
+            Err(DapsError::InvalidDat(_))
+        ));
+
+        mock.set_fault(Some(MockDapsFault::ExpiredToken));
+        let dat = daps.get_token().unwrap();
+        assert!(dat.expires_at.unwrap() < SystemTime::now());
+        assert!(matches!(
+            verifier.verify(&dat.token, Some(&client_cert)),
+            Err(DapsError::InvalidDat(_))
+        ));
+        assert_eq!(mock.issued_tokens(), 4);
+
+        // connectors that are not registered get no dat
+        mock.set_fault(None);
+        let unknown = create_driver(&mock, "test_server");
+        assert!(matches!(
+            unknown.get_token(),
+            Err(DapsError::Unavailable(_))
+        ));
+        assert_eq!(mock.issued_tokens(), 4);
+    }
+}
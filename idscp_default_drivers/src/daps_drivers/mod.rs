@@ -12,4 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod dat_verifier;
+pub mod default_daps;
+pub mod federated_verifier;
+mod http;
+pub mod json;
+pub(crate) mod jwt;
+#[cfg(any(test, feature = "mock_daps"))]
+pub mod mock_daps;
 pub mod null_daps;
+pub mod static_key_daps;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// seconds since the unix epoch, the unit of the time claims of JWTs
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
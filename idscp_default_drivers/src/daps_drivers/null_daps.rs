@@ -12,17 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use idscp_core::drivers::daps_driver::DapsDriver;
+use idscp_core::drivers::daps_driver::{
+    DapsDriver, DapsError, Dat, DatClaims, TokenVerifier, VerifiedDat,
+};
+use openssl::x509::X509;
 use std::time::Duration;
 
 pub struct NullDaps;
 
 impl DapsDriver for NullDaps {
-    fn get_token(&self) -> String {
-        "This dummy token is generated by 'NullDAPS' driver! Do not depend on it!".to_string()
+    fn get_token(&self) -> Result<Dat, DapsError> {
+        Ok(Dat {
+            token: "This dummy token is generated by 'NullDAPS' driver! Do not depend on it!"
+                .to_string(),
+            expires_at: None,
+        })
     }
+}
 
-    fn verify_token(&self, _token: &String) -> Option<Duration> {
-        Some(Duration::from_secs(24 * 60 * 60)) // valid for 24 hours
+impl TokenVerifier for NullDaps {
+    fn verify_token(&self, _token: &str, _peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        Ok(VerifiedDat {
+            validity: Duration::from_secs(24 * 60 * 60), // valid for 24 hours
+            claims: DatClaims::default(),
+        })
     }
 }
@@ -0,0 +1,258 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// DAPS driver for deployments without DAPS. Each connector issues its own short-lived DATs,
+// signed with its local key, and accepts the DATs of peers whose keys are configured as trusted
+
+use super::dat_verifier::{transport_cert_sha256, DatVerifier, DatVerifierConfig, KeySource};
+use super::default_daps::connector_id;
+use super::json::JsonValue;
+use super::jwt;
+use super::unix_now;
+use anyhow::Error;
+use idscp_core::drivers::daps_driver::{DapsDriver, DapsError, Dat, TokenVerifier, VerifiedDat};
+use openssl::pkey::{PKey, Private};
+use openssl::x509::X509;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub struct StaticKeyDapsConfig {
+    // signing key and certificate of the connector. The connector id is derived from the
+    // certificate, which must also be the TLS certificate the DATs are bound to
+    pub key_file_path: PathBuf,
+    pub cert_file_path: PathBuf,
+    pub token_lifetime: Duration,
+    // the issued dat is renewed this margin before it expires
+    pub renewal_margin: Duration,
+    // additional claims of the issued DATs, e.g. securityProfile or referringConnector
+    pub claims: BTreeMap<String, JsonValue>,
+    // keys of the trusted peers, usually KeySource::PemFiles with their certificates
+    pub verifier: DatVerifierConfig,
+}
+
+impl Default for StaticKeyDapsConfig {
+    fn default() -> Self {
+        StaticKeyDapsConfig {
+            key_file_path: PathBuf::new(),
+            cert_file_path: PathBuf::new(),
+            token_lifetime: Duration::from_secs(300),
+            renewal_margin: Duration::from_secs(30),
+            claims: BTreeMap::new(),
+            verifier: DatVerifierConfig {
+                key_source: KeySource::PemFiles(Vec::new()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+pub struct StaticKeyDaps {
+    config: StaticKeyDapsConfig,
+    key: PKey<Private>,
+    connector_id: String,
+    fingerprint: String,
+    verifier: DatVerifier,
+    issued_token: Mutex<Option<(String, i64)>>, // token and its expiry as unix timestamp
+}
+
+impl StaticKeyDaps {
+    pub fn new(config: StaticKeyDapsConfig) -> Result<StaticKeyDaps, Error> {
+        let key = PKey::private_key_from_pem(&std::fs::read(&config.key_file_path)?)?;
+        let cert = X509::from_pem(&std::fs::read(&config.cert_file_path)?)?;
+        let verifier = DatVerifier::new(config.verifier.clone())?;
+
+        Ok(StaticKeyDaps {
+            connector_id: connector_id(&cert)?,
+            fingerprint: transport_cert_sha256(&cert)?,
+            verifier,
+            key,
+            config,
+            issued_token: Mutex::new(None),
+        })
+    }
+
+    fn issue_token(&self) -> Result<(String, i64), Error> {
+        let now = unix_now();
+        let exp = now + self.config.token_lifetime.as_secs() as i64;
+        let mut claims = BTreeMap::new();
+        claims.insert(
+            "@context".to_string(),
+            "https://w3id.org/idsa/contexts/context.jsonld".into(),
+        );
+        claims.insert("@type".to_string(), "ids:DatPayload".into());
+        claims.insert("iss".to_string(), self.connector_id.as_str().into());
+        claims.insert("sub".to_string(), self.connector_id.as_str().into());
+        claims.insert(
+            "aud".to_string(),
+            JsonValue::Array(vec!["idsc:IDS_CONNECTORS_ALL".into()]),
+        );
+        claims.insert("iat".to_string(), now.into());
+        claims.insert("nbf".to_string(), now.into());
+        claims.insert("exp".to_string(), exp.into());
+        claims.insert(
+            "transportCertsSha256".to_string(),
+            self.fingerprint.as_str().into(),
+        );
+        for (name, value) in self.config.claims.iter() {
+            claims.insert(name.clone(), value.clone());
+        }
+
+        let token = jwt::sign_rs256(&JsonValue::Object(claims), &self.key, None)?;
+        Ok((token, exp))
+    }
+}
+
+impl DapsDriver for StaticKeyDaps {
+    fn get_token(&self) -> Result<Dat, DapsError> {
+        let mut issued = self.issued_token.lock().unwrap();
+        let renew_at = unix_now() + self.config.renewal_margin.as_secs() as i64;
+        let (token, exp) = match &*issued {
+            Some((token, exp)) if *exp > renew_at => (token.clone(), *exp),
+            _ => {
+                let fresh = self.issue_token().map_err(|e| {
+                    log::error!("Cannot issue DAT: {}", e);
+                    DapsError::Unavailable(e.to_string())
+                })?;
+                *issued = Some(fresh.clone());
+                fresh
+            }
+        };
+
+        Ok(Dat {
+            token,
+            expires_at: Some(UNIX_EPOCH + Duration::from_secs(exp.max(0) as u64)),
+        })
+    }
+}
+
+impl TokenVerifier for StaticKeyDaps {
+    fn verify_token(&self, token: &str, peer_cert: &X509) -> Result<VerifiedDat, DapsError> {
+        self.verifier.verify_token(token, peer_cert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::rsa::Rsa;
+    use openssl::x509::extension::{AuthorityKeyIdentifier, SubjectKeyIdentifier};
+    use openssl::x509::X509Name;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    // writes key.pem and cert.pem of a new self-signed connector identity to dir
+    fn create_identity(dir: &Path, cn: &str) -> X509 {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, cn).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let ski = SubjectKeyIdentifier::new()
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(ski).unwrap();
+        let aki = AuthorityKeyIdentifier::new()
+            .keyid(true)
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(aki).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = builder.build();
+
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("key.pem"), key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        std::fs::write(dir.join("cert.pem"), cert.to_pem().unwrap()).unwrap();
+        std::fs::write(dir.join("pubkey.pem"), key.public_key_to_pem().unwrap()).unwrap();
+        cert
+    }
+
+    fn create_driver(dir: &Path, trusted: Vec<PathBuf>, lifetime: u64) -> StaticKeyDaps {
+        StaticKeyDaps::new(StaticKeyDapsConfig {
+            key_file_path: dir.join("key.pem"),
+            cert_file_path: dir.join("cert.pem"),
+            token_lifetime: Duration::from_secs(lifetime),
+            verifier: DatVerifierConfig {
+                key_source: KeySource::PemFiles(trusted),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_static_key_daps() {
+        let dir = std::env::temp_dir().join(format!("idscp2_static_daps_{}", std::process::id()));
+        let old_cert = create_identity(&dir.join("old"), "connector-a");
+        let new_cert = create_identity(&dir.join("new"), "connector-a");
+        let other_cert = create_identity(&dir.join("other"), "connector-b");
+        create_identity(&dir.join("peer"), "connector-c");
+
+        // the peer trusts the old and the new key during the rotation, as certificate or key
+        let old = create_driver(&dir.join("old"), vec![], 300);
+        let new = create_driver(&dir.join("new"), vec![], 300);
+        let other = create_driver(&dir.join("other"), vec![], 300);
+        let peer = create_driver(
+            &dir.join("peer"),
+            vec![dir.join("old/cert.pem"), dir.join("new/pubkey.pem")],
+            300,
+        );
+        let short_lived = create_driver(&dir.join("old"), vec![], 20);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dat = old.get_token().unwrap();
+        assert_eq!(old.get_token().unwrap(), dat);
+        let verified = peer.verify_token(&dat.token, &old_cert).unwrap();
+        assert!(verified.validity > Duration::from_secs(290));
+        assert_eq!(
+            verified.claims.subject,
+            Some(connector_id(&old_cert).unwrap())
+        );
+        assert!(peer
+            .verify_token(&new.get_token().unwrap().token, &new_cert)
+            .is_ok());
+
+        // untrusted keys and tokens bound to another certificate are rejected
+        assert!(matches!(
+            peer.verify_token(&other.get_token().unwrap().token, &other_cert),
+            Err(DapsError::InvalidDat(_))
+        ));
+        assert_eq!(
+            peer.verify_token(&dat.token, &new_cert),
+            Err(DapsError::CertificateBindingMismatch)
+        );
+
+        // dats that expire within the renewal margin are issued again
+        let dat = short_lived.get_token().unwrap();
+        assert!(dat.expires_at.unwrap() <= SystemTime::now() + Duration::from_secs(20));
+        std::thread::sleep(Duration::from_millis(1100));
+        assert_ne!(short_lived.get_token().unwrap(), dat);
+    }
+}
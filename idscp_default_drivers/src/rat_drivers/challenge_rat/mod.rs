@@ -0,0 +1,231 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Challenge-response RAT drivers for devices without TPM. The verifier sends a nonce, the prover
+// signs it, bound to the secure channel, together with the sha256 digests of a configured set of
+// local files (e.g. binary and configuration) using its device attestation key. The verifier
+// checks the signature against its trusted keys and the digests against its reference values.
+//
+// As the key is not protected by hardware, this proves possession of the key and integrity of
+// the files only as long as the device itself is not compromised.
+
+use crate::rat_drivers::codec::{read_field, recv_raw, write_field};
+use anyhow::{anyhow, Error};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use idscp_core::drivers::rat_driver::RatMessage;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::mpsc::Receiver;
+
+pub mod prover;
+pub mod verifier;
+
+pub const CHALLENGE_RAT_ID: &str = "ChallengeResponse";
+
+const CHALLENGE: u8 = 1;
+const EVIDENCE: u8 = 2;
+const RESULT: u8 = 3;
+const MAX_MEASUREMENTS: u32 = 256;
+
+#[derive(Debug, PartialEq, Clone)]
+enum ChallengeMessage {
+    Challenge {
+        nonce: Vec<u8>,
+    },
+    // sha256 digest per measured file and the signature over them, see signed_data
+    Evidence {
+        measurements: BTreeMap<String, Vec<u8>>,
+        signature: Vec<u8>,
+    },
+    // from verifier, whether the evidence was accepted
+    Result {
+        ok: bool,
+    },
+}
+
+fn write_measurements(buf: &mut Vec<u8>, measurements: &BTreeMap<String, Vec<u8>>) {
+    buf.write_u32::<BigEndian>(measurements.len() as u32)
+        .unwrap();
+    for (name, digest) in measurements {
+        write_field(buf, name.as_bytes());
+        write_field(buf, digest);
+    }
+}
+
+// data signed by the prover, where bound_nonce is the nonce bound to the secure channel
+fn signed_data(bound_nonce: &[u8], measurements: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_field(&mut buf, bound_nonce);
+    write_measurements(&mut buf, measurements);
+    buf
+}
+
+impl ChallengeMessage {
+    fn recv(rx: &Receiver<RatMessage>) -> Option<Result<ChallengeMessage, Error>> {
+        recv_raw(rx).map(|data| ChallengeMessage::decode(&data?))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            ChallengeMessage::Challenge { nonce } => {
+                buf.push(CHALLENGE);
+                write_field(&mut buf, nonce);
+            }
+            ChallengeMessage::Evidence {
+                measurements,
+                signature,
+            } => {
+                buf.push(EVIDENCE);
+                write_measurements(&mut buf, measurements);
+                write_field(&mut buf, signature);
+            }
+            ChallengeMessage::Result { ok } => {
+                buf.push(RESULT);
+                buf.push(*ok as u8);
+            }
+        }
+        buf
+    }
+
+    fn decode(data: &[u8]) -> Result<ChallengeMessage, Error> {
+        let mut cursor = Cursor::new(data);
+        let msg = match cursor.read_u8()? {
+            CHALLENGE => ChallengeMessage::Challenge {
+                nonce: read_field(&mut cursor)?,
+            },
+            EVIDENCE => {
+                let count = cursor.read_u32::<BigEndian>()?;
+                if count > MAX_MEASUREMENTS {
+                    return Err(anyhow!("Invalid number of measurements {}", count));
+                }
+                let mut measurements = BTreeMap::new();
+                for _ in 0..count {
+                    let name = String::from_utf8(read_field(&mut cursor)?)?;
+                    measurements.insert(name, read_field(&mut cursor)?);
+                }
+                ChallengeMessage::Evidence {
+                    measurements,
+                    signature: read_field(&mut cursor)?,
+                }
+            }
+            RESULT => ChallengeMessage::Result {
+                ok: cursor.read_u8()? == 1,
+            },
+            t => return Err(anyhow!("Unknown challenge message type {}", t)),
+        };
+
+        if cursor.position() as usize != data.len() {
+            return Err(anyhow!("Trailing data after challenge message"));
+        }
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prover::{ChallengeProver, ChallengeProverConfig};
+    use super::verifier::{ChallengeVerifier, ChallengeVerifierConfig};
+    use crate::rat_drivers::hex;
+    use crate::rat_drivers::policy::{ReferencePolicy, ReferenceValue};
+    use crate::rat_drivers::test_util::{context, run_drivers};
+    use idscp_core::drivers::rat_driver::RatIcm;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::{hash, MessageDigest};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    // writes key.pem and pubkey.pem of a new device attestation key to dir
+    fn create_key(dir: &Path, name: &str) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        std::fs::write(
+            dir.join(format!("{}.pem", name)),
+            key.private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(format!("{}.pub.pem", name)),
+            key.public_key_to_pem().unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_challenge_rat() {
+        let dir = std::env::temp_dir().join(format!("idscp2_challenge_rat_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        create_key(&dir, "device");
+        create_key(&dir, "other");
+        std::fs::write(dir.join("config"), b"mode = production").unwrap();
+
+        let mut measured_files = BTreeMap::new();
+        measured_files.insert("config".to_string(), dir.join("config"));
+        let prover = |key: &str| {
+            Arc::new(
+                ChallengeProver::new(ChallengeProverConfig {
+                    key_file_path: dir.join(format!("{}.pem", key)),
+                    measured_files: measured_files.clone(),
+                })
+                .unwrap(),
+            )
+        };
+        let mut reference_values = BTreeMap::new();
+        let digest = hash(MessageDigest::sha256(), b"mode = production").unwrap();
+        let reference = ReferenceValue {
+            digest: digest.to_vec(),
+            ..Default::default()
+        };
+        reference_values.insert("config".to_string(), vec![reference]);
+        let verifier = Arc::new(
+            ChallengeVerifier::new(ChallengeVerifierConfig {
+                trusted_key_files: vec![dir.join("device.pub.pem")],
+                policy: Arc::new(ReferencePolicy::from_reference_values(reference_values)),
+            })
+            .unwrap(),
+        );
+        let attest = |key: &str, prover_binding: &[u8]| {
+            run_drivers(
+                prover(key),
+                verifier.clone(),
+                context(1, prover_binding),
+                context(2, b"channel-1"),
+            )
+        };
+
+        match attest("device", b"channel-1") {
+            (RatIcm::OK(_), RatIcm::OK(result)) => {
+                assert_eq!(result.claims["config"], hex(&digest));
+                assert_eq!(result.claims["policy"], "accepted");
+                assert!(result.claims.contains_key("attestationKeySha256"));
+            }
+            results => panic!("Attestation failed: {:?}", results),
+        }
+
+        // evidence of another channel, of an unknown key or of a modified file is rejected
+        let rejected =
+            |results: (RatIcm, RatIcm)| matches!(results, (RatIcm::Failed(_), RatIcm::Failed(_)));
+        assert!(rejected(attest("device", b"channel-2")));
+        assert!(rejected(attest("other", b"channel-1")));
+        std::fs::write(dir.join("config"), b"mode = debug").unwrap();
+        assert_eq!(
+            attest("device", b"channel-1").1,
+            RatIcm::Failed("Component config mismatch".to_string())
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
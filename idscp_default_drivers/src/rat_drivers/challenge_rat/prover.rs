@@ -0,0 +1,113 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{signed_data, ChallengeMessage, CHALLENGE_RAT_ID};
+use anyhow::Error;
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+
+pub struct ChallengeProverConfig {
+    // device attestation key (PEM), RSA or EC
+    pub key_file_path: PathBuf,
+    // files that are measured on each attestation by their name, e.g. "binary" or "config"
+    pub measured_files: BTreeMap<String, PathBuf>,
+}
+
+pub struct ChallengeProver {
+    key: PKey<Private>,
+    measured_files: BTreeMap<String, PathBuf>,
+}
+
+impl ChallengeProver {
+    pub fn new(config: ChallengeProverConfig) -> Result<ChallengeProver, Error> {
+        let key = PKey::private_key_from_pem(&std::fs::read(&config.key_file_path)?)?;
+        Ok(ChallengeProver {
+            key,
+            measured_files: config.measured_files,
+        })
+    }
+
+    fn measure(&self) -> Result<BTreeMap<String, Vec<u8>>, Error> {
+        let mut measurements = BTreeMap::new();
+        for (name, path) in self.measured_files.iter() {
+            let digest = hash(MessageDigest::sha256(), &std::fs::read(path)?)?;
+            measurements.insert(name.clone(), digest.to_vec());
+        }
+        Ok(measurements)
+    }
+
+    fn evidence(&self, bound_nonce: &[u8]) -> Result<ChallengeMessage, Error> {
+        let measurements = self.measure()?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        let signature = signer.sign_oneshot_to_vec(&signed_data(bound_nonce, &measurements))?;
+        Ok(ChallengeMessage::Evidence {
+            measurements,
+            signature,
+        })
+    }
+}
+
+fn fail(tx: &Sender<RatMessage>, reason: String) {
+    log::warn!("ChallengeProver failed: {}", reason);
+    let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+}
+
+impl RatDriver for ChallengeProver {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext) {
+        let nonce = match ChallengeMessage::recv(&rx) {
+            None => return,
+            Some(Ok(ChallengeMessage::Challenge { nonce })) => nonce,
+            Some(Ok(msg)) => return fail(&tx, format!("Expected challenge, got {:?}", msg)),
+            Some(Err(e)) => return fail(&tx, format!("Invalid challenge: {}", e)),
+        };
+
+        let evidence = match self.evidence(&ctx.bind(&nonce)) {
+            Ok(evidence) => evidence.encode(),
+            Err(e) => return fail(&tx, format!("Cannot create evidence: {}", e)),
+        };
+        if tx.send(RatMessage::RawData(evidence.clone())).is_err() {
+            return;
+        }
+
+        match ChallengeMessage::recv(&rx) {
+            None => {}
+            Some(Ok(ChallengeMessage::Result { ok: true })) => {
+                let result = RatResult {
+                    evidence,
+                    ..Default::default()
+                };
+                if tx
+                    .send(RatMessage::ControlMessage(RatIcm::OK(result)))
+                    .is_err()
+                {
+                    log::warn!("Prover was terminated from fsm");
+                }
+            }
+            Some(Ok(ChallengeMessage::Result { ok: false })) => {
+                fail(&tx, "Verifier rejected the evidence".to_string())
+            }
+            Some(Ok(msg)) => fail(&tx, format!("Expected result, got {:?}", msg)),
+            Some(Err(e)) => fail(&tx, format!("Invalid result: {}", e)),
+        }
+    }
+
+    fn get_id(&self) -> &'static str {
+        CHALLENGE_RAT_ID
+    }
+}
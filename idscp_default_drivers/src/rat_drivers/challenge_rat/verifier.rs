@@ -0,0 +1,147 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{signed_data, ChallengeMessage, CHALLENGE_RAT_ID};
+use crate::rat_drivers::policy::{peer_name, ReferencePolicy};
+use crate::rat_drivers::{hex, load_public_keys};
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Public};
+use openssl::sign::Verifier;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+
+pub struct ChallengeVerifierConfig {
+    // public keys or certificates (PEM) of the allow-listed device attestation keys
+    pub trusted_key_files: Vec<PathBuf>,
+    // reference values of the measured files, the prover must measure exactly these files
+    pub policy: Arc<ReferencePolicy>,
+}
+
+pub struct ChallengeVerifier {
+    keys: Vec<PKey<Public>>,
+    policy: Arc<ReferencePolicy>,
+}
+
+impl ChallengeVerifier {
+    pub fn new(config: ChallengeVerifierConfig) -> Result<ChallengeVerifier, Error> {
+        if config.trusted_key_files.is_empty() {
+            return Err(anyhow!("No trusted attestation keys configured"));
+        }
+        let keys = load_public_keys(&config.trusted_key_files)?;
+
+        Ok(ChallengeVerifier {
+            keys,
+            policy: config.policy,
+        })
+    }
+
+    // checks the evidence, where bound_nonce is the own nonce bound to the secure channel
+    fn verify_evidence(
+        &self,
+        peer: Option<&str>,
+        bound_nonce: &[u8],
+        measurements: &BTreeMap<String, Vec<u8>>,
+        signature: &[u8],
+    ) -> Result<RatResult, String> {
+        let data = signed_data(bound_nonce, measurements);
+        let key = self
+            .keys
+            .iter()
+            .find(|key| {
+                Verifier::new(MessageDigest::sha256(), key)
+                    .and_then(|mut verifier| verifier.verify_oneshot(signature, &data))
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| {
+                "Evidence is not signed by a trusted key for this connection".to_string()
+            })?;
+
+        let evaluation = self.policy.evaluate(peer, measurements);
+        if let Some(reason) = evaluation.failure_reason() {
+            return Err(reason);
+        }
+
+        let mut claims: BTreeMap<String, String> = measurements
+            .iter()
+            .map(|(name, digest)| (name.clone(), hex(digest)))
+            .collect();
+        claims.extend(evaluation.claims());
+        let key_digest = key
+            .public_key_to_der()
+            .and_then(|der| hash(MessageDigest::sha256(), &der))
+            .map_err(|e| format!("Cannot compute key fingerprint: {}", e))?;
+        claims.insert("attestationKeySha256".to_string(), hex(&key_digest));
+        Ok(RatResult {
+            claims,
+            evidence: data,
+        })
+    }
+}
+
+fn fail(tx: &Sender<RatMessage>, reason: String) {
+    log::warn!("ChallengeVerifier failed: {}", reason);
+    let _ = tx.send(RatMessage::RawData(
+        ChallengeMessage::Result { ok: false }.encode(),
+    ));
+    let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+}
+
+impl RatDriver for ChallengeVerifier {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext) {
+        let challenge = ChallengeMessage::Challenge {
+            nonce: ctx.nonce.clone(),
+        };
+        if tx.send(RatMessage::RawData(challenge.encode())).is_err() {
+            return;
+        }
+
+        let result = match ChallengeMessage::recv(&rx) {
+            None => return,
+            Some(Ok(ChallengeMessage::Evidence {
+                measurements,
+                signature,
+            })) => self.verify_evidence(
+                peer_name(&ctx.peer_cert).as_deref(),
+                &ctx.bind(&ctx.nonce),
+                &measurements,
+                &signature,
+            ),
+            Some(Ok(msg)) => Err(format!("Expected evidence, got {:?}", msg)),
+            Some(Err(e)) => Err(format!("Invalid evidence: {}", e)),
+        };
+
+        match result {
+            Ok(result) => {
+                let _ = tx.send(RatMessage::RawData(
+                    ChallengeMessage::Result { ok: true }.encode(),
+                ));
+                if tx
+                    .send(RatMessage::ControlMessage(RatIcm::OK(result)))
+                    .is_err()
+                {
+                    log::warn!("Verifier was terminated from fsm");
+                }
+            }
+            Err(reason) => fail(&tx, reason),
+        }
+    }
+
+    fn get_id(&self) -> &'static str {
+        CHALLENGE_RAT_ID
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// helpers for the messages rat drivers exchange as RatMessage::RawData
+
+use anyhow::{anyhow, Error};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use idscp_core::drivers::rat_driver::RatMessage;
+use std::io::{Cursor, Read};
+use std::sync::mpsc::Receiver;
+
+const MAX_FIELD_SIZE: usize = 64 * 1024; // byte
+
+// writes a u32 length-prefixed byte string
+pub(crate) fn write_field(buf: &mut Vec<u8>, field: &[u8]) {
+    buf.write_u32::<BigEndian>(field.len() as u32).unwrap();
+    buf.extend_from_slice(field);
+}
+
+pub(crate) fn read_field(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, Error> {
+    let size = cursor.read_u32::<BigEndian>()? as usize;
+    if size > MAX_FIELD_SIZE {
+        return Err(anyhow!("Invalid field size {}", size));
+    }
+    let mut field = vec![0u8; size];
+    cursor.read_exact(&mut field)?;
+    Ok(field)
+}
+
+// receives the next raw message of the peer driver, None if the driver was stopped
+pub(crate) fn recv_raw(rx: &Receiver<RatMessage>) -> Option<Result<Vec<u8>, Error>> {
+    match rx.recv() {
+        Err(_) => None,
+        Ok(RatMessage::RawData(data)) => Some(Ok(data)),
+        Ok(RatMessage::ControlMessage(_)) => Some(Err(anyhow!("Unexpected control message"))),
+    }
+}
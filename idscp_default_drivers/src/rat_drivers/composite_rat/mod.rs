@@ -0,0 +1,340 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// RAT driver that combines several drivers, e.g. a TPM quote and a software integrity check. The
+// messages of the drivers are multiplexed over the single channel of the composite driver, each
+// prefixed by the index of its driver. The composite driver is registered under an id that names
+// its drivers, e.g. "Tpm2Quote+ChallengeResponse", such that peers only negotiate it if they use
+// the same drivers in the same order.
+
+use crate::rat_drivers::codec::write_field;
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
+use openssl::hash::{hash, MessageDigest};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompositeMode {
+    // each driver is started after the previous one finished
+    Sequential,
+    Parallel,
+}
+
+pub struct CompositeRatDriver {
+    id: &'static str,
+    drivers: Vec<Arc<dyn RatDriver + Send + Sync>>,
+    mode: CompositeMode,
+    quorum: usize,
+}
+
+enum Event {
+    Peer(Option<RatMessage>),
+    Driver(usize, Option<RatMessage>),
+}
+
+// forwards the messages of rx as events until the channel is closed
+fn forward<F: Fn(Option<RatMessage>) -> Event + Send + 'static>(
+    rx: Receiver<RatMessage>,
+    events: Sender<Event>,
+    event: F,
+) {
+    thread::spawn(move || loop {
+        let msg = rx.recv().ok();
+        let closed = msg.is_none();
+        if events.send(event(msg)).is_err() || closed {
+            return;
+        }
+    });
+}
+
+impl CompositeRatDriver {
+    // succeeds if at least quorum drivers succeed, all if None
+    pub fn new(
+        id: &'static str,
+        drivers: Vec<Arc<dyn RatDriver + Send + Sync>>,
+        mode: CompositeMode,
+        quorum: Option<usize>,
+    ) -> Result<CompositeRatDriver, Error> {
+        if drivers.is_empty() || drivers.len() > u8::MAX as usize {
+            return Err(anyhow!("Invalid number of rat drivers {}", drivers.len()));
+        }
+        let quorum = quorum.unwrap_or(drivers.len());
+        if quorum == 0 || quorum > drivers.len() {
+            return Err(anyhow!("Invalid quorum {}", quorum));
+        }
+
+        if id.is_empty() {
+            return Err(anyhow!("Invalid rat driver id"));
+        }
+        Ok(CompositeRatDriver {
+            id,
+            drivers,
+            mode,
+            quorum,
+        })
+    }
+
+    // each driver gets its own nonce derived from the nonce of the composite driver
+    fn driver_context(ctx: &RatContext, index: usize) -> RatContext {
+        let mut data = ctx.nonce.clone();
+        data.push(index as u8);
+        RatContext {
+            nonce: hash(MessageDigest::sha256(), &data)
+                .map(|digest| digest.to_vec())
+                .expect("sha256 should be available"),
+            ..ctx.clone()
+        }
+    }
+
+    fn start_driver(
+        &self,
+        index: usize,
+        rx: Receiver<RatMessage>,
+        events: &Sender<Event>,
+        ctx: &RatContext,
+    ) {
+        let (driver_tx, from_driver) = channel();
+        let driver = Arc::clone(&self.drivers[index]);
+        let ctx = Self::driver_context(ctx, index);
+        thread::spawn(move || driver.execute(driver_tx, rx, ctx));
+        forward(from_driver, events.clone(), move |msg| {
+            Event::Driver(index, msg)
+        });
+    }
+
+    fn combine(&self, results: Vec<Result<RatResult, String>>) -> RatIcm {
+        let succeeded = results.iter().filter(|r| r.is_ok()).count();
+        let failures: Vec<String> = results
+            .iter()
+            .zip(self.drivers.iter())
+            .filter_map(|(r, d)| r.as_ref().err().map(|e| format!("{}: {}", d.get_id(), e)))
+            .collect();
+        if succeeded < self.quorum {
+            return RatIcm::Failed(failures.join("; "));
+        }
+
+        let mut combined = RatResult::default();
+        for (result, driver) in results.into_iter().zip(self.drivers.iter()) {
+            let id = driver.get_id();
+            match result {
+                Ok(result) => {
+                    combined.claims.insert(id.to_string(), "ok".to_string());
+                    for (name, value) in result.claims {
+                        combined.claims.insert(format!("{}.{}", id, name), value);
+                    }
+                    write_field(&mut combined.evidence, &result.evidence);
+                }
+                Err(reason) => {
+                    combined.claims.insert(id.to_string(), reason);
+                    write_field(&mut combined.evidence, &[]);
+                }
+            }
+        }
+        RatIcm::OK(combined)
+    }
+}
+
+impl RatDriver for CompositeRatDriver {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext) {
+        let (events_tx, events) = channel();
+        forward(rx, events_tx.clone(), Event::Peer);
+
+        // channels to the drivers are created upfront, such that messages of the peer are buffered
+        // until their driver is started
+        let (mut to_drivers, mut driver_rxs) = (Vec::new(), Vec::new());
+        for _ in 0..self.drivers.len() {
+            let (to_driver, driver_rx) = channel();
+            to_drivers.push(to_driver);
+            driver_rxs.push(Some(driver_rx));
+        }
+        let started = match self.mode {
+            CompositeMode::Sequential => 1,
+            CompositeMode::Parallel => self.drivers.len(),
+        };
+        for (index, rx) in driver_rxs.iter_mut().enumerate().take(started) {
+            self.start_driver(index, rx.take().unwrap(), &events_tx, &ctx);
+        }
+
+        let mut results: Vec<Option<Result<RatResult, String>>> = vec![None; self.drivers.len()];
+        loop {
+            let (index, result) = match events.recv() {
+                // the composite driver was stopped, which closes the channels to the drivers
+                Err(_) | Ok(Event::Peer(None)) => return,
+                Ok(Event::Peer(Some(RatMessage::RawData(data)))) => {
+                    match data.split_first() {
+                        Some((index, data)) if (*index as usize) < to_drivers.len() => {
+                            let _ = to_drivers[*index as usize]
+                                .send(RatMessage::RawData(data.to_vec()));
+                        }
+                        _ => log::warn!("Composite rat driver received message for unknown driver"),
+                    }
+                    continue;
+                }
+                Ok(Event::Peer(Some(RatMessage::ControlMessage(_)))) => continue,
+                Ok(Event::Driver(index, Some(RatMessage::RawData(data)))) => {
+                    let mut msg = vec![index as u8];
+                    msg.extend_from_slice(&data);
+                    if tx.send(RatMessage::RawData(msg)).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Ok(Event::Driver(index, Some(RatMessage::ControlMessage(RatIcm::OK(r))))) => {
+                    (index, Ok(r))
+                }
+                Ok(Event::Driver(index, Some(RatMessage::ControlMessage(RatIcm::Failed(e))))) => {
+                    (index, Err(e))
+                }
+                Ok(Event::Driver(index, None)) => {
+                    (index, Err("Terminated without result".to_string()))
+                }
+            };
+            if results[index].is_some() {
+                continue;
+            }
+            log::debug!(
+                "Rat driver {} finished: {:?}",
+                self.drivers[index].get_id(),
+                result
+            );
+            results[index] = Some(result);
+
+            let failed = results.iter().filter(|r| matches!(r, Some(Err(_)))).count();
+            let finished = results.iter().all(|r| r.is_some());
+            if finished || failed > self.drivers.len() - self.quorum {
+                let results = results
+                    .into_iter()
+                    .map(|r| r.unwrap_or_else(|| Err("Not finished".to_string())))
+                    .collect();
+                if tx
+                    .send(RatMessage::ControlMessage(self.combine(results)))
+                    .is_err()
+                {
+                    log::warn!("Composite rat driver was terminated from fsm");
+                }
+                return;
+            }
+
+            if self.mode == CompositeMode::Sequential {
+                if let Some(next) = driver_rxs.iter().position(|rx| rx.is_some()) {
+                    self.start_driver(next, driver_rxs[next].take().unwrap(), &events_tx, &ctx);
+                }
+            }
+        }
+    }
+
+    fn get_id(&self) -> &'static str {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rat_drivers::null_rat::{NullRatProver, NullRatVerifier};
+    use crate::rat_drivers::test_util::{context, run_drivers};
+
+    // exchanges one message and fails
+    struct FailingProver {}
+    struct FailingVerifier {}
+
+    impl RatDriver for FailingProver {
+        fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _ctx: RatContext) {
+            let _ = tx.send(RatMessage::RawData(b"evidence".to_vec()));
+            if let Ok(RatMessage::RawData(data)) = rx.recv() {
+                let reason = String::from_utf8_lossy(&data).to_string();
+                let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+            }
+        }
+
+        fn get_id(&self) -> &'static str {
+            "Failing"
+        }
+    }
+
+    impl RatDriver for FailingVerifier {
+        fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _ctx: RatContext) {
+            if let Ok(RatMessage::RawData(data)) = rx.recv() {
+                assert_eq!(data, b"evidence");
+                let _ = tx.send(RatMessage::RawData(b"rejected".to_vec()));
+                let reason = "invalid evidence".to_string();
+                let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+            }
+        }
+
+        fn get_id(&self) -> &'static str {
+            "Failing"
+        }
+    }
+
+    fn attest(mode: CompositeMode, quorum: Option<usize>) -> (RatIcm, RatIcm) {
+        let prover = CompositeRatDriver::new(
+            "NullRat+Failing",
+            vec![Arc::new(NullRatProver {}), Arc::new(FailingProver {})],
+            mode,
+            quorum,
+        )
+        .unwrap();
+        let verifier = CompositeRatDriver::new(
+            "NullRat+Failing",
+            vec![Arc::new(NullRatVerifier {}), Arc::new(FailingVerifier {})],
+            mode,
+            quorum,
+        )
+        .unwrap();
+        assert_eq!(prover.get_id(), "NullRat+Failing");
+        run_drivers(
+            Arc::new(prover),
+            Arc::new(verifier),
+            context(1, b"channel"),
+            context(2, b"channel"),
+        )
+    }
+
+    #[test]
+    fn test_composite_rat() {
+        for mode in &[CompositeMode::Sequential, CompositeMode::Parallel] {
+            // all drivers must succeed by default
+            match attest(*mode, None) {
+                (RatIcm::Failed(p), RatIcm::Failed(v)) => {
+                    assert_eq!(p, "Failing: rejected");
+                    assert_eq!(v, "Failing: invalid evidence");
+                }
+                results => panic!("Unexpected results {:?}", results),
+            }
+
+            // unless a quorum is configured
+            match attest(*mode, Some(1)) {
+                (RatIcm::OK(_), RatIcm::OK(result)) => {
+                    assert_eq!(result.claims["NullRat"], "ok");
+                    assert_eq!(result.claims["Failing"], "invalid evidence");
+                }
+                results => panic!("Unexpected results {:?}", results),
+            }
+        }
+
+        assert!(CompositeRatDriver::new("NullRat", vec![], CompositeMode::Parallel, None).is_err());
+        let drivers: Vec<Arc<dyn RatDriver + Send + Sync>> = vec![Arc::new(NullRatProver {})];
+        assert!(CompositeRatDriver::new(
+            "NullRat",
+            drivers.clone(),
+            CompositeMode::Parallel,
+            Some(2)
+        )
+        .is_err());
+        assert!(CompositeRatDriver::new("", drivers, CompositeMode::Parallel, None).is_err());
+    }
+}
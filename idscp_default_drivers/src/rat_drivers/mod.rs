@@ -12,4 +12,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use anyhow::Error;
+use openssl::pkey::{PKey, Public};
+use openssl::x509::X509;
+use std::path::PathBuf;
+
+pub mod challenge_rat;
+mod codec;
+pub mod composite_rat;
 pub mod null_rat;
+pub mod policy;
+#[cfg(test)]
+mod test_util;
+pub mod tpm_rat;
+
+// loads trusted keys of verifiers, each file contains a certificate or a public key (PEM)
+fn load_public_keys(files: &[PathBuf]) -> Result<Vec<PKey<Public>>, Error> {
+    files
+        .iter()
+        .map(|path| {
+            let pem = std::fs::read(path)?;
+            match X509::from_pem(&pem) {
+                Ok(cert) => Ok(cert.public_key()?),
+                Err(_) => Ok(PKey::public_key_from_pem(&pem)?),
+            }
+        })
+        .collect()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -12,17 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use idscp_core::drivers::rat_driver::{RatDriver, RatIcm, RatMessage};
-use openssl::x509::X509;
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
 use std::sync::mpsc::{Receiver, Sender};
 
 pub struct NullRatProver {}
 pub struct NullRatVerifier {}
 impl RatDriver for NullRatProver {
-    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _peer_cert: X509) {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _ctx: RatContext) {
         tx.send(RatMessage::RawData(b"".to_vec())).unwrap();
         rx.recv().unwrap();
-        if tx.send(RatMessage::ControlMessage(RatIcm::OK)).is_err() {
+        if tx
+            .send(RatMessage::ControlMessage(RatIcm::OK(RatResult::default())))
+            .is_err()
+        {
             log::warn!("Prover was terminated from fsm");
         }
     }
@@ -33,10 +35,13 @@ impl RatDriver for NullRatProver {
 }
 
 impl RatDriver for NullRatVerifier {
-    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _peer_cert: X509) {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, _ctx: RatContext) {
         rx.recv().unwrap();
         tx.send(RatMessage::RawData(b"".to_vec())).unwrap();
-        if tx.send(RatMessage::ControlMessage(RatIcm::OK)).is_err() {
+        if tx
+            .send(RatMessage::ControlMessage(RatIcm::OK(RatResult::default())))
+            .is_err()
+        {
             log::warn!("Prover was terminated from fsm");
         }
     }
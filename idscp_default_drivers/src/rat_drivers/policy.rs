@@ -0,0 +1,370 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Reference values for RAT verifiers. A policy maps the names of measured components, e.g.
+// "config" or "pcr0", to their allowed sha256 digests. Single components can be overridden for
+// peers, which are identified by the common name of their certificate. Each reference value may
+// be restricted to a validity period, e.g. to phase out a former release.
+//
+// Policies are distributed as JWT (RS256) signed by the policy authority:
+//   {"exp": 1735689600,
+//    "components": {"config": ["ab01..", {"digest": "cd23..", "notAfter": 1704067200}]},
+//    "peers": {"connector-b": {"config": ["ef45.."]}}}
+
+use super::{hex, load_public_keys};
+use crate::daps_drivers::json::JsonValue;
+use crate::daps_drivers::jwt::Jwt;
+use crate::daps_drivers::unix_now;
+use anyhow::{anyhow, Error};
+use openssl::nid::Nid;
+use openssl::x509::X509;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub struct PolicyConfig {
+    // signed policy (JWT)
+    pub policy_file: PathBuf,
+    // certificates or public keys (PEM) of the policy authority
+    pub trusted_key_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReferenceValue {
+    pub digest: Vec<u8>,
+    // unix timestamps, the value is accepted from not_before until not_after
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+}
+
+impl ReferenceValue {
+    fn is_valid_at(&self, now: i64) -> bool {
+        self.not_before.map_or(true, |t| t <= now) && self.not_after.map_or(true, |t| now <= t)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentStatus {
+    Match,
+    Mismatch,
+    // the digest matches a reference value outside of its validity period
+    Expired,
+    // reference value without measurement
+    Missing,
+    // measurement without reference value
+    Unknown,
+}
+
+impl ComponentStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            ComponentStatus::Match => "match",
+            ComponentStatus::Mismatch => "mismatch",
+            ComponentStatus::Expired => "expired",
+            ComponentStatus::Missing => "missing",
+            ComponentStatus::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyEvaluation {
+    pub components: BTreeMap<String, ComponentStatus>,
+    // the policy itself is not valid anymore
+    pub policy_expired: bool,
+}
+
+impl PolicyEvaluation {
+    pub fn accepted(&self) -> bool {
+        !self.policy_expired
+            && self
+                .components
+                .values()
+                .all(|status| *status == ComponentStatus::Match)
+    }
+
+    pub fn failure_reason(&self) -> Option<String> {
+        if self.policy_expired {
+            return Some("Reference policy expired".to_string());
+        }
+        self.components
+            .iter()
+            .find(|(_, status)| **status != ComponentStatus::Match)
+            .map(|(name, status)| format!("Component {} {}", name, status.name()))
+    }
+
+    // evaluation as claims of a RatResult, such that it is available on the connection
+    pub fn claims(&self) -> BTreeMap<String, String> {
+        let mut claims: BTreeMap<String, String> = self
+            .components
+            .iter()
+            .map(|(name, status)| (format!("policy.{}", name), status.name().to_string()))
+            .collect();
+        let verdict = if self.accepted() {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        claims.insert("policy".to_string(), verdict.to_string());
+        claims
+    }
+}
+
+type ReferenceValues = BTreeMap<String, Vec<ReferenceValue>>;
+
+pub struct ReferencePolicy {
+    components: ReferenceValues,
+    peers: BTreeMap<String, ReferenceValues>,
+    expires_at: Option<i64>,
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, Error> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(anyhow!("Invalid hex digest {}", s));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| anyhow!("Invalid hex digest {}", s))
+        })
+        .collect()
+}
+
+fn parse_reference_value(value: &JsonValue) -> Result<ReferenceValue, Error> {
+    match value {
+        JsonValue::String(digest) => Ok(ReferenceValue {
+            digest: from_hex(digest)?,
+            ..Default::default()
+        }),
+        JsonValue::Object(_) => {
+            let digest = value
+                .get("digest")
+                .and_then(|d| d.as_str())
+                .ok_or_else(|| anyhow!("Reference value without digest"))?;
+            Ok(ReferenceValue {
+                digest: from_hex(digest)?,
+                not_before: value.get("notBefore").and_then(|t| t.as_i64()),
+                not_after: value.get("notAfter").and_then(|t| t.as_i64()),
+            })
+        }
+        _ => Err(anyhow!("Invalid reference value {}", value)),
+    }
+}
+
+fn parse_components(value: Option<&JsonValue>) -> Result<ReferenceValues, Error> {
+    let members = match value {
+        None => return Ok(BTreeMap::new()),
+        Some(JsonValue::Object(members)) => members,
+        Some(v) => return Err(anyhow!("Invalid components {}", v)),
+    };
+    let mut components = BTreeMap::new();
+    for (name, values) in members {
+        let values = match values {
+            JsonValue::Array(values) => values
+                .iter()
+                .map(parse_reference_value)
+                .collect::<Result<Vec<_>, _>>()?,
+            v => vec![parse_reference_value(v)?],
+        };
+        components.insert(name.clone(), values);
+    }
+    Ok(components)
+}
+
+// common name of the peer certificate, which identifies the peer within policies
+pub fn peer_name(cert: &X509) -> Option<String> {
+    cert.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .find_map(|cn| String::from_utf8(cn.data().as_slice().to_vec()).ok())
+}
+
+impl ReferencePolicy {
+    // loads the policy file and checks its signature
+    pub fn load(config: &PolicyConfig) -> Result<ReferencePolicy, Error> {
+        let keys = load_public_keys(&config.trusted_key_files)?;
+        let token = std::fs::read_to_string(&config.policy_file)?;
+        let jwt = Jwt::decode(token.trim())?;
+        if !keys.iter().any(|key| jwt.verify_signature(key).is_ok()) {
+            return Err(anyhow!(
+                "Policy is not signed by a trusted policy authority"
+            ));
+        }
+
+        let mut peers = BTreeMap::new();
+        match jwt.claims.get("peers") {
+            None => {}
+            Some(JsonValue::Object(members)) => {
+                for (peer, components) in members {
+                    peers.insert(peer.clone(), parse_components(Some(components))?);
+                }
+            }
+            Some(v) => return Err(anyhow!("Invalid peers {}", v)),
+        }
+        Ok(ReferencePolicy {
+            components: parse_components(jwt.claims.get("components"))?,
+            peers,
+            expires_at: jwt.claims.get("exp").and_then(|exp| exp.as_i64()),
+        })
+    }
+
+    // static policy that is not loaded from a signed file
+    pub fn from_reference_values(components: BTreeMap<String, Vec<ReferenceValue>>) -> Self {
+        ReferencePolicy {
+            components,
+            peers: BTreeMap::new(),
+            expires_at: None,
+        }
+    }
+
+    // compares the measured sha256 digests to the reference values for the given peer
+    pub fn evaluate(
+        &self,
+        peer: Option<&str>,
+        measurements: &BTreeMap<String, Vec<u8>>,
+    ) -> PolicyEvaluation {
+        self.evaluate_at(peer, measurements, unix_now())
+    }
+
+    fn evaluate_at(
+        &self,
+        peer: Option<&str>,
+        measurements: &BTreeMap<String, Vec<u8>>,
+        now: i64,
+    ) -> PolicyEvaluation {
+        let mut references = self.components.clone();
+        if let Some(overrides) = peer.and_then(|peer| self.peers.get(peer)) {
+            references.extend(overrides.clone());
+        }
+
+        let mut components = BTreeMap::new();
+        for (name, values) in references.iter() {
+            let status = match measurements.get(name) {
+                None => ComponentStatus::Missing,
+                Some(digest) => {
+                    let matching: Vec<&ReferenceValue> =
+                        values.iter().filter(|v| v.digest == *digest).collect();
+                    if matching.is_empty() {
+                        ComponentStatus::Mismatch
+                    } else if matching.iter().any(|v| v.is_valid_at(now)) {
+                        ComponentStatus::Match
+                    } else {
+                        ComponentStatus::Expired
+                    }
+                }
+            };
+            components.insert(name.clone(), status);
+        }
+        for name in measurements.keys() {
+            if !references.contains_key(name) {
+                components.insert(name.clone(), ComponentStatus::Unknown);
+            }
+        }
+
+        log::debug!(
+            "Evaluated measurements {:?} of peer {:?}: {:?}",
+            measurements
+                .iter()
+                .map(|(name, digest)| format!("{}={}", name, hex(digest)))
+                .collect::<Vec<_>>(),
+            peer,
+            components
+        );
+        PolicyEvaluation {
+            components,
+            policy_expired: self.expires_at.map_or(false, |exp| exp < now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daps_drivers::jwt::sign_rs256;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+
+    fn measurements(entries: &[(&str, &str)]) -> BTreeMap<String, Vec<u8>> {
+        entries
+            .iter()
+            .map(|(name, digest)| (name.to_string(), from_hex(digest).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_reference_policy() {
+        let dir = std::env::temp_dir().join(format!("idscp2_rat_policy_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let authority = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        std::fs::write(
+            dir.join("authority.pem"),
+            authority.public_key_to_pem().unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("other.pem"), other.public_key_to_pem().unwrap()).unwrap();
+
+        let claims = JsonValue::parse(
+            r#"{"exp": 2000,
+                "components": {
+                    "binary": ["aa01", {"digest": "aa00", "notAfter": 1000}],
+                    "config": "bb01"},
+                "peers": {"connector-b": {"config": ["bb02"]}}}"#,
+        )
+        .unwrap();
+        let token = sign_rs256(&claims, &authority, None).unwrap();
+        std::fs::write(dir.join("policy.jwt"), token).unwrap();
+        let config = |key: &str| PolicyConfig {
+            policy_file: dir.join("policy.jwt"),
+            trusted_key_files: vec![dir.join(key)],
+        };
+        let policy = ReferencePolicy::load(&config("authority.pem")).unwrap();
+        assert!(ReferencePolicy::load(&config("other.pem")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let current = measurements(&[("binary", "aa01"), ("config", "bb01")]);
+        let evaluation = policy.evaluate_at(None, &current, 1500);
+        assert!(evaluation.accepted());
+        assert_eq!(evaluation.claims()["policy.binary"], "match");
+
+        // the former release is only accepted until its reference value expired
+        let former = measurements(&[("binary", "aa00"), ("config", "bb01")]);
+        assert!(policy.evaluate_at(None, &former, 900).accepted());
+        let evaluation = policy.evaluate_at(None, &former, 1500);
+        assert_eq!(evaluation.components["binary"], ComponentStatus::Expired);
+        assert_eq!(
+            evaluation.failure_reason(),
+            Some("Component binary expired".to_string())
+        );
+        assert_eq!(evaluation.claims()["policy"], "rejected");
+
+        // peers can have their own reference values
+        let peer_b = measurements(&[("binary", "aa01"), ("config", "bb02")]);
+        assert!(policy
+            .evaluate_at(Some("connector-b"), &peer_b, 1500)
+            .accepted());
+        let evaluation = policy.evaluate_at(Some("connector-c"), &peer_b, 1500);
+        assert_eq!(evaluation.components["config"], ComponentStatus::Mismatch);
+
+        // all references must be measured and all measurements must be referenced
+        let evaluation = policy.evaluate_at(None, &measurements(&[("binary", "aa01")]), 1500);
+        assert_eq!(evaluation.components["config"], ComponentStatus::Missing);
+        let mut extra = current.clone();
+        extra.insert("plugin".to_string(), vec![1]);
+        let evaluation = policy.evaluate_at(None, &extra, 1500);
+        assert_eq!(evaluation.components["plugin"], ComponentStatus::Unknown);
+
+        let evaluation = policy.evaluate_at(None, &current, 2500);
+        assert!(evaluation.policy_expired);
+        assert!(!evaluation.accepted());
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// runs rat drivers against each other without fsms
+
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage};
+use openssl::x509::X509;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) fn context(nonce: u8, channel_binding: &[u8]) -> RatContext {
+    let cert = std::fs::read(format!(
+        "{}/../test_pki/resources/openssl/out/test_client.crt",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    RatContext {
+        peer_cert: X509::from_pem(&cert).unwrap(),
+        channel_binding: Some(channel_binding.to_vec()),
+        nonce: vec![nonce; 32],
+    }
+}
+
+// connects prover and verifier, returns the results of prover and verifier
+pub(crate) fn run_drivers(
+    prover: Arc<dyn RatDriver + Send + Sync>,
+    verifier: Arc<dyn RatDriver + Send + Sync>,
+    prover_ctx: RatContext,
+    verifier_ctx: RatContext,
+) -> (RatIcm, RatIcm) {
+    let (to_prover, prover_rx) = channel();
+    let (to_verifier, verifier_rx) = channel();
+    let (prover_tx, from_prover) = channel();
+    let (verifier_tx, from_verifier) = channel();
+    thread::spawn(move || prover.execute(prover_tx, prover_rx, prover_ctx));
+    thread::spawn(move || verifier.execute(verifier_tx, verifier_rx, verifier_ctx));
+
+    // forward raw data like the fsms would do, until both drivers finished
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let (mut prover_result, mut verifier_result) = (None, None);
+    while prover_result.is_none() || verifier_result.is_none() {
+        assert!(
+            Instant::now() < deadline,
+            "Attestation did not finish, prover result: {:?}",
+            prover_result
+        );
+        if let Ok(msg) = from_prover.try_recv() {
+            match msg {
+                RatMessage::RawData(data) => {
+                    let _ = to_verifier.send(RatMessage::RawData(data));
+                }
+                RatMessage::ControlMessage(icm) => prover_result = Some(icm),
+            }
+        }
+        if let Ok(msg) = from_verifier.try_recv() {
+            match msg {
+                RatMessage::RawData(data) => {
+                    let _ = to_prover.send(RatMessage::RawData(data));
+                }
+                RatMessage::ControlMessage(icm) => verifier_result = Some(icm),
+            }
+        }
+        thread::yield_now();
+    }
+    (prover_result.unwrap(), verifier_result.unwrap())
+}
@@ -0,0 +1,123 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// parser of the TPMS_ATTEST structure a TPM 2.0 signs for a quote, see TPM 2.0 Part 2, 10.12
+
+use anyhow::{anyhow, Error};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+const TPM_ALG_SHA256: u16 = 0x000b;
+const PCR_SELECT_MAX: usize = 4; // byte, 24 pcrs per bank need 3
+
+#[derive(Debug, PartialEq)]
+pub(super) struct QuoteInfo {
+    pub extra_data: Vec<u8>,
+    pub reset_count: u32,
+    // quoted pcrs of the sha256 bank in ascending order
+    pub pcrs: Vec<u8>,
+    pub pcr_digest: Vec<u8>,
+}
+
+fn read_tpm2b(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, Error> {
+    let size = cursor.read_u16::<BigEndian>()? as usize;
+    let mut buf = vec![0u8; size];
+    cursor.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+pub(super) fn parse_quote(attest: &[u8]) -> Result<QuoteInfo, Error> {
+    let mut cursor = Cursor::new(attest);
+    if cursor.read_u32::<BigEndian>()? != TPM_GENERATED_VALUE {
+        return Err(anyhow!("Attestation was not generated by a TPM"));
+    }
+    if cursor.read_u16::<BigEndian>()? != TPM_ST_ATTEST_QUOTE {
+        return Err(anyhow!("Attestation is not a quote"));
+    }
+    let _qualified_signer = read_tpm2b(&mut cursor)?;
+    let extra_data = read_tpm2b(&mut cursor)?;
+    let _clock = cursor.read_u64::<BigEndian>()?;
+    let reset_count = cursor.read_u32::<BigEndian>()?;
+    let _restart_count = cursor.read_u32::<BigEndian>()?;
+    let _safe = cursor.read_u8()?;
+    let _firmware_version = cursor.read_u64::<BigEndian>()?;
+
+    let mut pcrs = Vec::new();
+    for _ in 0..cursor.read_u32::<BigEndian>()? {
+        let hash = cursor.read_u16::<BigEndian>()?;
+        let mut select = vec![0u8; cursor.read_u8()? as usize];
+        if select.len() > PCR_SELECT_MAX {
+            return Err(anyhow!("Invalid size of pcr selection {}", select.len()));
+        }
+        cursor.read_exact(&mut select)?;
+        let selected: Vec<u8> = (0..select.len() * 8)
+            .filter(|i| select[i / 8] & (1 << (i % 8)) != 0)
+            .map(|i| i as u8)
+            .collect();
+        match hash {
+            TPM_ALG_SHA256 => pcrs.extend(selected),
+            // the digest would cover pcrs of other banks that are not checked
+            _ if !selected.is_empty() => {
+                return Err(anyhow!("Quote covers pcrs of unsupported bank {}", hash))
+            }
+            _ => {}
+        }
+    }
+    pcrs.sort_unstable();
+    pcrs.dedup();
+    let pcr_digest = read_tpm2b(&mut cursor)?;
+
+    if cursor.position() as usize != attest.len() {
+        return Err(anyhow!("Trailing data after quote"));
+    }
+    Ok(QuoteInfo {
+        extra_data,
+        reset_count,
+        pcrs,
+        pcr_digest,
+    })
+}
+
+// TPMS_ATTEST of a quote as a TPM would create it
+#[cfg(test)]
+pub(super) fn build_quote(extra_data: &[u8], pcrs: &[u8], pcr_digest: &[u8]) -> Vec<u8> {
+    use byteorder::WriteBytesExt;
+
+    let mut buf = Vec::new();
+    buf.write_u32::<BigEndian>(TPM_GENERATED_VALUE).unwrap();
+    buf.write_u16::<BigEndian>(TPM_ST_ATTEST_QUOTE).unwrap();
+    buf.write_u16::<BigEndian>(2).unwrap();
+    buf.extend_from_slice(&[0x00, 0x0b]);
+    buf.write_u16::<BigEndian>(extra_data.len() as u16).unwrap();
+    buf.extend_from_slice(extra_data);
+    buf.write_u64::<BigEndian>(1000).unwrap();
+    buf.write_u32::<BigEndian>(1).unwrap();
+    buf.write_u32::<BigEndian>(0).unwrap();
+    buf.push(1);
+    buf.write_u64::<BigEndian>(0x2019_1023).unwrap();
+
+    let mut select = [0u8; 3];
+    for pcr in pcrs {
+        select[*pcr as usize / 8] |= 1 << (pcr % 8);
+    }
+    buf.write_u32::<BigEndian>(1).unwrap();
+    buf.write_u16::<BigEndian>(TPM_ALG_SHA256).unwrap();
+    buf.push(select.len() as u8);
+    buf.extend_from_slice(&select);
+    buf.write_u16::<BigEndian>(pcr_digest.len() as u16).unwrap();
+    buf.extend_from_slice(pcr_digest);
+    buf
+}
@@ -0,0 +1,118 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// messages between tpm prover and verifier, exchanged as RatMessage::RawData. Each message starts
+// with its type, followed by its fields as u32 length-prefixed byte strings
+
+use crate::rat_drivers::codec::{read_field, recv_raw, write_field};
+use anyhow::{anyhow, Error};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use idscp_core::drivers::rat_driver::RatMessage;
+use std::io::Cursor;
+use std::sync::mpsc::Receiver;
+
+const CHALLENGE: u8 = 1;
+const QUOTE: u8 = 2;
+const RESULT: u8 = 3;
+
+#[derive(Debug, PartialEq, Clone)]
+pub(super) enum TpmMessage {
+    // from verifier, the pcrs of the sha256 bank that must be quoted
+    Challenge {
+        nonce: Vec<u8>,
+        pcrs: Vec<u8>,
+    },
+    // from prover, the pcr values are in ascending order of the pcrs
+    Quote {
+        attest: Vec<u8>,
+        signature: Vec<u8>,
+        pcr_values: Vec<Vec<u8>>,
+    },
+    // from verifier, whether the quote was accepted
+    Result {
+        ok: bool,
+    },
+}
+
+impl TpmMessage {
+    // receives the next message from the peer driver, None if the driver was stopped
+    pub(super) fn recv(rx: &Receiver<RatMessage>) -> Option<Result<TpmMessage, Error>> {
+        recv_raw(rx).map(|data| TpmMessage::decode(&data?))
+    }
+
+    pub(super) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            TpmMessage::Challenge { nonce, pcrs } => {
+                buf.push(CHALLENGE);
+                write_field(&mut buf, nonce);
+                write_field(&mut buf, pcrs);
+            }
+            TpmMessage::Quote {
+                attest,
+                signature,
+                pcr_values,
+            } => {
+                buf.push(QUOTE);
+                write_field(&mut buf, attest);
+                write_field(&mut buf, signature);
+                buf.write_u32::<BigEndian>(pcr_values.len() as u32).unwrap();
+                for value in pcr_values {
+                    write_field(&mut buf, value);
+                }
+            }
+            TpmMessage::Result { ok } => {
+                buf.push(RESULT);
+                buf.push(*ok as u8);
+            }
+        }
+        buf
+    }
+
+    pub(super) fn decode(data: &[u8]) -> Result<TpmMessage, Error> {
+        let mut cursor = Cursor::new(data);
+        let msg = match cursor.read_u8()? {
+            CHALLENGE => TpmMessage::Challenge {
+                nonce: read_field(&mut cursor)?,
+                pcrs: read_field(&mut cursor)?,
+            },
+            QUOTE => {
+                let attest = read_field(&mut cursor)?;
+                let signature = read_field(&mut cursor)?;
+                let count = cursor.read_u32::<BigEndian>()?;
+                // a bank has at most 24 pcrs
+                if count > 24 {
+                    return Err(anyhow!("Invalid number of pcr values {}", count));
+                }
+                let pcr_values = (0..count)
+                    .map(|_| read_field(&mut cursor))
+                    .collect::<Result<Vec<_>, _>>()?;
+                TpmMessage::Quote {
+                    attest,
+                    signature,
+                    pcr_values,
+                }
+            }
+            RESULT => TpmMessage::Result {
+                ok: cursor.read_u8()? == 1,
+            },
+            t => return Err(anyhow!("Unknown tpm message type {}", t)),
+        };
+
+        if cursor.position() as usize != data.len() {
+            return Err(anyhow!("Trailing data after tpm message"));
+        }
+        Ok(msg)
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// RAT drivers based on TPM 2.0 quotes. The verifier sends a nonce together with the PCRs it
+// expects, the prover quotes these PCRs with its attestation key (AK) over the nonce bound to the
+// secure channel and the verifier checks signature, nonce and PCR values against its references.
+//
+// The prover uses the tpm2-tools, such that it works with hardware TPMs as well as with a local
+// swtpm simulator. The verifier does not need a TPM. Setup of a simulator with a persistent AK:
+//
+//   swtpm socket --tpm2 --tpmstate dir=/tmp/swtpm --server type=tcp,port=2321 \
+//       --ctrl type=tcp,port=2322 --flags not-need-init,startup-clear &
+//   export TPM2TOOLS_TCTI=swtpm:host=localhost,port=2321
+//   tpm2_createek -c ek.ctx -G rsa -u ek.pub
+//   tpm2_createak -C ek.ctx -c ak.ctx -G rsa -g sha256 -s rsassa -u ak.pem -f pem -n ak.name
+//   tpm2_evictcontrol -c ak.ctx 0x81010002
+
+mod attest;
+mod messages;
+pub mod prover;
+pub mod verifier;
+
+pub const TPM_RAT_ID: &str = "Tpm2Quote";
+
+#[cfg(test)]
+mod tests {
+    use super::prover::{TpmProver, TpmProverConfig};
+    use super::verifier::{pcr_name, TpmVerifier, TpmVerifierConfig};
+    use crate::rat_drivers::policy::{ReferencePolicy, ReferenceValue};
+    use crate::rat_drivers::test_util::{context, run_drivers};
+    use idscp_core::drivers::rat_driver::RatIcm;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn attest(verifier: TpmVerifier) -> (RatIcm, RatIcm) {
+        run_drivers(
+            Arc::new(TpmProver::new(TpmProverConfig::default())),
+            Arc::new(verifier),
+            context(1, b"channel-binding"),
+            context(2, b"channel-binding"),
+        )
+    }
+    // requires a running swtpm with an AK at 0x81010002, see the setup above, and the public key
+    // of the AK in the file given by TPM_AK_PEM
+    #[ignore]
+    #[test]
+    fn test_tpm_rat_swtpm() {
+        let ak_file = PathBuf::from(std::env::var("TPM_AK_PEM").unwrap_or("ak.pem".to_string()));
+        let verifier = |reference: u8| {
+            // pcrs 0 to 15 of a fresh simulator are zero
+            let mut reference_values = BTreeMap::new();
+            for (pcr, digest) in &[(0, 0u8), (1, reference)] {
+                let value = ReferenceValue {
+                    digest: vec![*digest; 32],
+                    ..Default::default()
+                };
+                reference_values.insert(pcr_name(*pcr), vec![value]);
+            }
+            TpmVerifier::new(TpmVerifierConfig {
+                trusted_ak_files: vec![ak_file.clone()],
+                pcrs: vec![0, 1],
+                policy: Arc::new(ReferencePolicy::from_reference_values(reference_values)),
+            })
+            .unwrap()
+        };
+
+        let (prover_result, verifier_result) = attest(verifier(0));
+        assert!(matches!(prover_result, RatIcm::OK(_)));
+        match verifier_result {
+            RatIcm::OK(result) => assert_eq!(result.claims["pcr1"], "00".repeat(32)),
+            RatIcm::Failed(reason) => panic!("Attestation failed: {}", reason),
+        }
+
+        let (prover_result, verifier_result) = attest(verifier(1));
+        assert!(matches!(prover_result, RatIcm::Failed(_)));
+        assert_eq!(
+            verifier_result,
+            RatIcm::Failed("Component pcr1 mismatch".to_string())
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::messages::TpmMessage;
+use super::TPM_RAT_ID;
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+
+static WORK_DIR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub struct TpmProverConfig {
+    // tpm2-tools tcti of the TPM, e.g. "device:/dev/tpmrm0" for a hardware TPM
+    pub tcti: String,
+    // persistent handle or context file of the attestation key, which must be a restricted
+    // RSASSA signing key with sha256
+    pub ak_context: String,
+}
+
+impl Default for TpmProverConfig {
+    fn default() -> Self {
+        TpmProverConfig {
+            tcti: "swtpm:host=localhost,port=2321".to_string(),
+            ak_context: "0x81010002".to_string(),
+        }
+    }
+}
+
+pub struct TpmProver {
+    config: TpmProverConfig,
+}
+
+// temporary directory for the files of the tpm2-tools, removed on drop
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn create() -> Result<WorkDir, Error> {
+        let dir = std::env::temp_dir().join(format!(
+            "idscp2_tpm_{}_{}",
+            std::process::id(),
+            WORK_DIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(WorkDir(dir))
+    }
+
+    fn file(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn path_arg(path: &Path) -> &str {
+    path.to_str().unwrap_or_default()
+}
+
+impl TpmProver {
+    pub fn new(config: TpmProverConfig) -> TpmProver {
+        TpmProver { config }
+    }
+
+    fn tpm2(&self, tool: &str, args: &[&str]) -> Result<(), Error> {
+        let output = Command::new(tool)
+            .args(args)
+            .env("TPM2TOOLS_TCTI", &self.config.tcti)
+            .output()
+            .map_err(|e| anyhow!("Cannot run {}: {}", tool, e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed: {}",
+                tool,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    // quotes the pcrs of the sha256 bank over the qualifying data
+    fn quote(&self, qualifying_data: &[u8], pcrs: &[u8]) -> Result<TpmMessage, Error> {
+        if pcrs.is_empty() || pcrs.iter().any(|pcr| *pcr > 23) {
+            return Err(anyhow!("Invalid pcr selection {:?}", pcrs));
+        }
+        let mut pcrs = pcrs.to_vec();
+        pcrs.sort_unstable();
+        pcrs.dedup();
+        let selection = format!(
+            "sha256:{}",
+            pcrs.iter()
+                .map(|pcr| pcr.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let qualifying_data: String = qualifying_data
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        let dir = WorkDir::create()?;
+        let (values, attest, signature) = (
+            dir.file("pcrs.bin"),
+            dir.file("attest.bin"),
+            dir.file("signature.bin"),
+        );
+        // the quote covers the pcrs at quote time, so values that changed meanwhile are rejected
+        // by the verifier due to the pcr digest
+        self.tpm2("tpm2_pcrread", &[&selection, "-o", path_arg(&values)])?;
+        self.tpm2(
+            "tpm2_quote",
+            &[
+                "-c",
+                &self.config.ak_context,
+                "-l",
+                &selection,
+                "-q",
+                &qualifying_data,
+                "-g",
+                "sha256",
+                "-m",
+                path_arg(&attest),
+                "-s",
+                path_arg(&signature),
+                "-f",
+                "plain",
+            ],
+        )?;
+
+        let values = std::fs::read(values)?;
+        if values.len() != pcrs.len() * 32 {
+            return Err(anyhow!("Unexpected size of pcr values {}", values.len()));
+        }
+        Ok(TpmMessage::Quote {
+            attest: std::fs::read(attest)?,
+            signature: std::fs::read(signature)?,
+            pcr_values: values.chunks(32).map(|v| v.to_vec()).collect(),
+        })
+    }
+}
+
+fn fail(tx: &Sender<RatMessage>, reason: String) {
+    log::warn!("TpmProver failed: {}", reason);
+    let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+}
+
+impl RatDriver for TpmProver {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext) {
+        let (nonce, pcrs) = match TpmMessage::recv(&rx) {
+            None => return,
+            Some(Ok(TpmMessage::Challenge { nonce, pcrs })) => (nonce, pcrs),
+            Some(Ok(msg)) => return fail(&tx, format!("Expected challenge, got {:?}", msg)),
+            Some(Err(e)) => return fail(&tx, format!("Invalid challenge: {}", e)),
+        };
+
+        // the verifier expects the nonce bound to the secure channel
+        let quote = match self.quote(&ctx.bind(&nonce), &pcrs) {
+            Ok(quote) => quote,
+            Err(e) => return fail(&tx, format!("Cannot create quote: {}", e)),
+        };
+        let evidence = match &quote {
+            TpmMessage::Quote { attest, .. } => attest.clone(),
+            _ => unreachable!(),
+        };
+        if tx.send(RatMessage::RawData(quote.encode())).is_err() {
+            return;
+        }
+
+        match TpmMessage::recv(&rx) {
+            None => {}
+            Some(Ok(TpmMessage::Result { ok: true })) => {
+                let result = RatResult {
+                    evidence,
+                    ..Default::default()
+                };
+                if tx
+                    .send(RatMessage::ControlMessage(RatIcm::OK(result)))
+                    .is_err()
+                {
+                    log::warn!("Prover was terminated from fsm");
+                }
+            }
+            Some(Ok(TpmMessage::Result { ok: false })) => {
+                fail(&tx, "Verifier rejected the quote".to_string())
+            }
+            Some(Ok(msg)) => fail(&tx, format!("Expected result, got {:?}", msg)),
+            Some(Err(e)) => fail(&tx, format!("Invalid result: {}", e)),
+        }
+    }
+
+    fn get_id(&self) -> &'static str {
+        TPM_RAT_ID
+    }
+}
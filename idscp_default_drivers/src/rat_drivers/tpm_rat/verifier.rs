@@ -0,0 +1,256 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::attest::parse_quote;
+use super::messages::TpmMessage;
+use super::TPM_RAT_ID;
+use crate::rat_drivers::policy::{peer_name, ReferencePolicy};
+use crate::rat_drivers::{hex, load_public_keys};
+use anyhow::{anyhow, Error};
+use idscp_core::drivers::rat_driver::{RatContext, RatDriver, RatIcm, RatMessage, RatResult};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Public};
+use openssl::sign::Verifier;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+
+pub struct TpmVerifierConfig {
+    // public keys or certificates (PEM) of the accepted attestation keys
+    pub trusted_ak_files: Vec<PathBuf>,
+    // quoted pcrs
+    pub pcrs: Vec<u8>,
+    // sha256 reference values of the quoted pcrs as components "pcr0", "pcr1", .., see pcr_name
+    pub policy: Arc<ReferencePolicy>,
+}
+
+pub struct TpmVerifier {
+    keys: Vec<PKey<Public>>,
+    pcrs: Vec<u8>,
+    policy: Arc<ReferencePolicy>,
+}
+
+// component of the reference policy that contains the reference values of the pcr
+pub fn pcr_name(pcr: u8) -> String {
+    format!("pcr{}", pcr)
+}
+
+impl TpmVerifier {
+    pub fn new(config: TpmVerifierConfig) -> Result<TpmVerifier, Error> {
+        // the quote contains the pcrs in ascending order
+        let mut pcrs = config.pcrs;
+        pcrs.sort_unstable();
+        pcrs.dedup();
+        if pcrs.is_empty() || pcrs.iter().any(|pcr| *pcr > 23) {
+            return Err(anyhow!("Invalid pcr selection"));
+        }
+        let keys = load_public_keys(&config.trusted_ak_files)?;
+
+        Ok(TpmVerifier {
+            keys,
+            pcrs,
+            policy: config.policy,
+        })
+    }
+
+    fn challenge(&self, nonce: &[u8]) -> TpmMessage {
+        TpmMessage::Challenge {
+            nonce: nonce.to_vec(),
+            pcrs: self.pcrs.clone(),
+        }
+    }
+
+    // checks the quote, where qualifying_data is the nonce bound to the secure channel
+    fn verify_quote(
+        &self,
+        peer: Option<&str>,
+        qualifying_data: &[u8],
+        attest: &[u8],
+        signature: &[u8],
+        pcr_values: &[Vec<u8>],
+    ) -> Result<RatResult, String> {
+        let signed_by_trusted_ak = self.keys.iter().any(|key| {
+            Verifier::new(MessageDigest::sha256(), key)
+                .and_then(|mut verifier| verifier.verify_oneshot(signature, attest))
+                .unwrap_or(false)
+        });
+        if !signed_by_trusted_ak {
+            return Err("Quote is not signed by a trusted attestation key".to_string());
+        }
+
+        let quote = parse_quote(attest).map_err(|e| format!("Invalid quote: {}", e))?;
+        if quote.extra_data != qualifying_data {
+            return Err("Quote does not contain the nonce of this connection".to_string());
+        }
+        if quote.pcrs != self.pcrs || pcr_values.len() != self.pcrs.len() {
+            return Err(format!("Quoted pcrs {:?} were not requested", quote.pcrs));
+        }
+        let digest = hash(MessageDigest::sha256(), &pcr_values.concat())
+            .map_err(|e| format!("Cannot compute pcr digest: {}", e))?;
+        if *digest != *quote.pcr_digest {
+            return Err("Pcr values do not match the quote".to_string());
+        }
+
+        let measurements: BTreeMap<String, Vec<u8>> = self
+            .pcrs
+            .iter()
+            .zip(pcr_values)
+            .map(|(pcr, value)| (pcr_name(*pcr), value.clone()))
+            .collect();
+        let evaluation = self.policy.evaluate(peer, &measurements);
+        if let Some(reason) = evaluation.failure_reason() {
+            return Err(reason);
+        }
+
+        let mut claims: BTreeMap<String, String> = measurements
+            .iter()
+            .map(|(name, value)| (name.clone(), hex(value)))
+            .collect();
+        claims.extend(evaluation.claims());
+        claims.insert("resetCount".to_string(), quote.reset_count.to_string());
+        Ok(RatResult {
+            claims,
+            evidence: attest.to_vec(),
+        })
+    }
+}
+
+fn fail(tx: &Sender<RatMessage>, reason: String) {
+    log::warn!("TpmVerifier failed: {}", reason);
+    let _ = tx.send(RatMessage::RawData(
+        TpmMessage::Result { ok: false }.encode(),
+    ));
+    let _ = tx.send(RatMessage::ControlMessage(RatIcm::Failed(reason)));
+}
+
+impl RatDriver for TpmVerifier {
+    fn execute(&self, tx: Sender<RatMessage>, rx: Receiver<RatMessage>, ctx: RatContext) {
+        if tx
+            .send(RatMessage::RawData(self.challenge(&ctx.nonce).encode()))
+            .is_err()
+        {
+            return;
+        }
+
+        let result = match TpmMessage::recv(&rx) {
+            None => return,
+            Some(Ok(TpmMessage::Quote {
+                attest,
+                signature,
+                pcr_values,
+            })) => self.verify_quote(
+                peer_name(&ctx.peer_cert).as_deref(),
+                &ctx.bind(&ctx.nonce),
+                &attest,
+                &signature,
+                &pcr_values,
+            ),
+            Some(Ok(msg)) => Err(format!("Expected quote, got {:?}", msg)),
+            Some(Err(e)) => Err(format!("Invalid quote: {}", e)),
+        };
+
+        match result {
+            Ok(result) => {
+                let _ = tx.send(RatMessage::RawData(
+                    TpmMessage::Result { ok: true }.encode(),
+                ));
+                if tx
+                    .send(RatMessage::ControlMessage(RatIcm::OK(result)))
+                    .is_err()
+                {
+                    log::warn!("Verifier was terminated from fsm");
+                }
+            }
+            Err(reason) => fail(&tx, reason),
+        }
+    }
+
+    fn get_id(&self) -> &'static str {
+        TPM_RAT_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::attest::build_quote;
+    use super::*;
+    use crate::rat_drivers::policy::ReferenceValue;
+    use openssl::pkey::Private;
+    use openssl::rsa::Rsa;
+    use openssl::sign::Signer;
+
+    fn sign(key: &PKey<Private>, attest: &[u8]) -> Vec<u8> {
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer.sign_oneshot_to_vec(attest).unwrap()
+    }
+
+    #[test]
+    fn test_verify_quote() {
+        let ak = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other_ak = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let ak_file =
+            std::env::temp_dir().join(format!("idscp2_tpm_ak_{}.pem", std::process::id()));
+        std::fs::write(&ak_file, ak.public_key_to_pem().unwrap()).unwrap();
+
+        let values = vec![vec![0u8; 32], vec![7u8; 32]];
+        let mut reference_values = BTreeMap::new();
+        for (pcr, value) in [0, 7].iter().zip(values.iter()) {
+            let reference = ReferenceValue {
+                digest: value.clone(),
+                ..Default::default()
+            };
+            reference_values.insert(pcr_name(*pcr), vec![reference]);
+        }
+        let verifier = TpmVerifier::new(TpmVerifierConfig {
+            trusted_ak_files: vec![ak_file.clone()],
+            pcrs: vec![7, 0],
+            policy: Arc::new(ReferencePolicy::from_reference_values(reference_values)),
+        })
+        .unwrap();
+        std::fs::remove_file(&ak_file).unwrap();
+
+        let nonce = vec![1u8; 32];
+        let digest = hash(MessageDigest::sha256(), &values.concat()).unwrap();
+        let attest = build_quote(&nonce, &[0, 7], &digest);
+        let result = verifier
+            .verify_quote(None, &nonce, &attest, &sign(&ak, &attest), &values)
+            .unwrap();
+        assert_eq!(result.claims.get("pcr7"), Some(&"07".repeat(32)));
+        assert_eq!(result.claims.get("policy.pcr7"), Some(&"match".to_string()));
+        assert_eq!(result.evidence, attest);
+
+        // quotes of other keys, connections or pcr values are rejected
+        assert!(verifier
+            .verify_quote(None, &nonce, &attest, &sign(&other_ak, &attest), &values)
+            .is_err());
+        assert!(verifier
+            .verify_quote(None, &[2u8; 32], &attest, &sign(&ak, &attest), &values)
+            .is_err());
+        let modified = vec![vec![0u8; 32], vec![8u8; 32]];
+        assert!(verifier
+            .verify_quote(None, &nonce, &attest, &sign(&ak, &attest), &modified)
+            .is_err());
+        let digest = hash(MessageDigest::sha256(), &modified.concat()).unwrap();
+        let attest = build_quote(&nonce, &[0, 7], &digest);
+        assert_eq!(
+            verifier.verify_quote(None, &nonce, &attest, &sign(&ak, &attest), &modified),
+            Err("Component pcr7 mismatch".to_string())
+        );
+        let attest = build_quote(&nonce, &[0], &digest);
+        assert!(verifier
+            .verify_quote(None, &nonce, &attest, &sign(&ak, &attest), &modified)
+            .is_err());
+    }
+}
@@ -30,12 +30,41 @@ pub mod client;
 pub mod server;
 
 const LENGTH_PREFIX_SIZE: usize = 4; // byte
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024; // byte
+
+// tls-exporter channel binding of RFC 9266
+const CHANNEL_BINDING_LABEL: &str = "EXPORTER-Channel-Binding";
+const CHANNEL_BINDING_SIZE: usize = 32; // byte
+
+// Reads a single length-prefixed frame. The buffer grows with the received data, such that a
+// forged length prefix cannot trigger a large allocation.
+pub fn read_frame<R: Read>(stream: &mut R) -> Result<Vec<u8>, Error> {
+    let mut size_buf = [0u8; LENGTH_PREFIX_SIZE];
+    stream.read_exact(&mut size_buf)?;
+
+    //should be u32 but java requires i32
+    let size = BigEndian::read_i32(&size_buf);
+    if size < 0 || size as usize > MAX_FRAME_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid frame size {}", size),
+        ));
+    }
+
+    let mut buf = Vec::new();
+    stream.take(size as u64).read_to_end(&mut buf)?;
+    if buf.len() != size as usize {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "Frame was truncated"));
+    }
+    Ok(buf)
+}
 
 pub struct OpensslChannel {
     // must be mutex to share safely between threads
     to_remote: Mutex<Sender<ScMessage>>,
     from_remote: Mutex<mpsc::Receiver<ScMessage>>,
     peer_certificate: X509,
+    channel_binding: Option<Vec<u8>>,
 }
 
 pub struct OpensslAddr {
@@ -65,6 +94,18 @@ impl OpensslChannel {
         let (to_remote, from_upper) = calloop::channel::channel();
         let (to_upper, from_remote) = mpsc::channel::<ScMessage>();
         let peer_cert = stream.ssl().peer_certificate().unwrap();
+        let mut channel_binding = vec![0u8; CHANNEL_BINDING_SIZE];
+        let channel_binding = match stream.ssl().export_keying_material(
+            &mut channel_binding,
+            CHANNEL_BINDING_LABEL,
+            Some(&[]),
+        ) {
+            Ok(()) => Some(channel_binding),
+            Err(e) => {
+                log::warn!("Cannot export channel binding from TLS session: {}", e);
+                None
+            }
+        };
 
         let _ = thread::spawn(move || {
             let mut event_loop = EventLoop::try_new().unwrap();
@@ -84,34 +125,19 @@ impl OpensslChannel {
                             log::warn!("Secure connection received an error");
                             *closed = true;
                         } else if event.readable {
-                            let mut size_buf = [0u8; LENGTH_PREFIX_SIZE];
-
-                            //should be u32 but java requires i32
-                            let size = match stream.read_exact(&mut size_buf) {
-                                Err(e) => {
-                                    if BigEndian::read_i32(&size_buf) <= 0 {
-                                        log::debug!("Secure Channel received EOF");
-                                    } else {
-                                        log::warn!("Cannot read size of data: {}", e);
-                                    }
-                                    *closed = true;
-                                    return Ok(());
-                                }
-
-                                Ok(_) => BigEndian::read_i32(&size_buf),
-                            };
-
-                            let mut buf = vec![0u8; size as usize];
-
-                            match stream.read_exact(&mut buf) {
-                                Ok(_) => {
+                            match read_frame(stream) {
+                                Ok(buf) => {
                                     if to_upper.send(ScMessage::Data(buf)).is_err() {
                                         // upper layer not available anymore, terminate this
                                         *closed = true;
                                     }
                                 }
                                 Err(e) => {
-                                    log::warn!("Cannot read data from stream: {}", e);
+                                    if e.kind() == ErrorKind::UnexpectedEof {
+                                        log::debug!("Secure Channel received EOF");
+                                    } else {
+                                        log::warn!("Cannot read frame from stream: {}", e);
+                                    }
                                     *closed = true;
                                 }
                             }
@@ -167,6 +193,7 @@ impl OpensslChannel {
             to_remote: Mutex::new(to_remote),
             from_remote: Mutex::new(from_remote),
             peer_certificate: peer_cert,
+            channel_binding,
         }
     }
 }
@@ -229,6 +256,10 @@ impl SecureChannel for OpensslChannel {
     fn get_peer_certificate(&self) -> X509 {
         self.peer_certificate.clone()
     }
+
+    fn get_channel_binding(&self) -> Option<Vec<u8>> {
+        self.channel_binding.clone()
+    }
 }
 
 #[cfg(test)]
@@ -246,6 +277,36 @@ mod tests {
     use std::thread::sleep;
     use std::time::Duration;
 
+    #[test]
+    fn test_read_frame() {
+        let mut stream = std::io::Cursor::new(vec![0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0]);
+        assert_eq!(read_frame(&mut stream).unwrap(), vec![1, 2, 3]);
+        assert!(read_frame(&mut stream).unwrap().is_empty());
+        assert_eq!(
+            read_frame(&mut stream).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        // truncated frame
+        let mut stream = std::io::Cursor::new(vec![0, 0, 0, 3, 1, 2]);
+        assert_eq!(
+            read_frame(&mut stream).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        // negative and oversized length prefixes
+        let mut stream = std::io::Cursor::new(vec![0xff, 0xff, 0xff, 0xff, 1]);
+        assert_eq!(
+            read_frame(&mut stream).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        let mut stream = std::io::Cursor::new(vec![0x7f, 0xff, 0xff, 0xff, 1]);
+        assert_eq!(
+            read_frame(&mut stream).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
     #[ignore]
     #[test]
     fn test_calloop_deadlock() {
@@ -551,7 +612,8 @@ mod tests {
                 addr.clone(),
                 Arc::new(Mutex::new(Box::new(move |sc| {
                     println!("server received new connection");
-                    (*sc).send_msg(b"hello".to_vec()).unwrap();
+                    // both peers derive the same channel binding from the tls session
+                    (*sc).send_msg(sc.get_channel_binding().unwrap()).unwrap();
                     let msg = (*sc).recv_msg().unwrap();
                     println!("server received message: {:?}", msg);
                 }))),
@@ -585,6 +647,8 @@ mod tests {
         println!("client connected");
         let msg = secure_channel.recv_msg().unwrap();
         println!("client received msg: {:?}", msg);
+        assert_eq!(msg.len(), CHANNEL_BINDING_SIZE);
+        assert_eq!(secure_channel.get_channel_binding(), Some(msg));
         secure_channel.send_msg(b"world".to_vec()).unwrap();
         secure_channel.terminate();
         server.stop();
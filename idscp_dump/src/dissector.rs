@@ -0,0 +1,300 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use idscp_core::messages::idscpv2_messages::{IdscpMessage, IdscpMessage_oneof_message};
+use protobuf::Message;
+use std::fmt::Write;
+
+// number of payload bytes that are shown when payloads are printed
+const PAYLOAD_PREVIEW: usize = 64;
+
+// Splits a byte stream into frames that are prefixed by a 4 byte big endian length, as they are
+// sent by the openssl secure channel. A truncated frame ends the iteration with an error.
+pub struct FrameSplitter<'a> {
+    data: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> FrameSplitter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        FrameSplitter {
+            data,
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Iterator for FrameSplitter<'a> {
+    type Item = Result<&'a [u8], String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.failed {
+            return None;
+        }
+        if self.data.len() < 4 {
+            self.failed = true;
+            return Some(Err(format!(
+                "truncated length prefix ({} trailing bytes)",
+                self.data.len()
+            )));
+        }
+
+        let mut size_buf = [0u8; 4];
+        size_buf.copy_from_slice(&self.data[..4]);
+        let size = u32::from_be_bytes(size_buf) as usize;
+        let rest = &self.data[4..];
+        if size > rest.len() {
+            self.failed = true;
+            return Some(Err(format!(
+                "truncated frame: length prefix announces {} bytes, but only {} bytes are left",
+                size,
+                rest.len()
+            )));
+        }
+
+        let (frame, rest) = rest.split_at(size);
+        self.data = rest;
+        Some(Ok(frame))
+    }
+}
+
+// Returns a human readable description of a raw IdscpMessage frame. Frames that cannot be decoded
+// are described as well, they never cause an error.
+pub fn dissect_frame(frame: &[u8], show_payload: bool) -> String {
+    match IdscpMessage::parse_from_bytes(frame) {
+        Err(e) => format!(
+            "malformed IdscpMessage ({} bytes): {}\n    raw: {}",
+            frame.len(),
+            e,
+            hex_preview(frame)
+        ),
+        Ok(msg) => describe_message(&msg, show_payload),
+    }
+}
+
+pub fn describe_message(msg: &IdscpMessage, show_payload: bool) -> String {
+    let mut out = String::new();
+    match &msg.message {
+        None => out.push_str("empty IdscpMessage (no message type set)"),
+
+        Some(IdscpMessage_oneof_message::idscpHello(hello)) => {
+            let _ = writeln!(out, "IdscpHello");
+            let _ = writeln!(out, "    version: {}", hello.get_version());
+            let _ = writeln!(
+                out,
+                "    supported RAT suite: [{}]",
+                hello.get_supportedRatSuite().join(", ")
+            );
+            let _ = writeln!(
+                out,
+                "    expected RAT suite: [{}]",
+                hello.get_expectedRatSuite().join(", ")
+            );
+            if hello.has_dynamicAttributeToken() {
+                out.push_str(&describe_dat(hello.get_dynamicAttributeToken().get_token()));
+            } else {
+                out.push_str("    DAT: <missing>");
+            }
+        }
+
+        Some(IdscpMessage_oneof_message::idscpClose(close)) => {
+            let _ = writeln!(
+                out,
+                "IdscpClose\n    cause code: {:?} ({})",
+                close.get_cause_code(),
+                close.get_cause_code() as i32
+            );
+            let _ = write!(out, "    cause message: {:?}", close.get_cause_msg());
+        }
+
+        Some(IdscpMessage_oneof_message::idscpDatExpired(_)) => out.push_str("IdscpDatExpired"),
+
+        Some(IdscpMessage_oneof_message::idscpDat(dat)) => {
+            let _ = writeln!(out, "IdscpDat");
+            out.push_str(&describe_dat(dat.get_token()));
+        }
+
+        Some(IdscpMessage_oneof_message::idscpReRat(re_rat)) => {
+            let _ = write!(out, "IdscpReRat\n    cause: {:?}", re_rat.get_cause());
+        }
+
+        Some(IdscpMessage_oneof_message::idscpRatProver(prover)) => {
+            let _ = write!(
+                out,
+                "IdscpRatProver\n    size: {} bytes",
+                prover.get_data().len()
+            );
+            if show_payload {
+                let _ = write!(out, "\n    data: {}", hex_preview(prover.get_data()));
+            }
+        }
+
+        Some(IdscpMessage_oneof_message::idscpRatVerifier(verifier)) => {
+            let _ = write!(
+                out,
+                "IdscpRatVerifier\n    size: {} bytes",
+                verifier.get_data().len()
+            );
+            if show_payload {
+                let _ = write!(out, "\n    data: {}", hex_preview(verifier.get_data()));
+            }
+        }
+
+        Some(IdscpMessage_oneof_message::idscpData(data)) => {
+            let _ = write!(
+                out,
+                "IdscpData\n    size: {} bytes\n    alternating bit: {}",
+                data.get_data().len(),
+                data.get_alternating_bit() as u8
+            );
+            if show_payload {
+                let _ = write!(out, "\n    data: {}", hex_preview(data.get_data()));
+            }
+        }
+
+        Some(IdscpMessage_oneof_message::idscpAck(ack)) => {
+            let _ = write!(
+                out,
+                "IdscpAck\n    alternating bit: {}",
+                ack.get_alternating_bit() as u8
+            );
+        }
+    }
+    out.trim_end().to_string()
+}
+
+// describes a DAT, tokens in JWT format are decoded
+fn describe_dat(token: &[u8]) -> String {
+    let token_str = match std::str::from_utf8(token) {
+        Err(_) => {
+            return format!(
+                "    DAT: {} bytes (not UTF-8): {}",
+                token.len(),
+                hex_preview(token)
+            )
+        }
+        Ok(s) => s,
+    };
+
+    match decode_jwt(token_str) {
+        None => format!("    DAT: {:?}", token_str),
+        Some((header, payload)) => format!(
+            "    DAT: JWT ({} bytes)\n        header: {}\n        payload: {}",
+            token.len(),
+            header,
+            payload
+        ),
+    }
+}
+
+// decodes header and payload of a JWT, the signature is not verified
+fn decode_jwt(token: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let header = decode_base64_url(parts[0])?;
+    let payload = decode_base64_url(parts[1])?;
+    Some((
+        String::from_utf8(header).ok()?,
+        String::from_utf8(payload).ok()?,
+    ))
+}
+
+fn decode_base64_url(input: &str) -> Option<Vec<u8>> {
+    let mut b64: String = input
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    let padding = (4 - b64.len() % 4) % 4;
+    b64.push_str(&"==="[..padding]);
+    openssl::base64::decode_block(&b64).ok()
+}
+
+fn hex_preview(data: &[u8]) -> String {
+    let mut out = String::new();
+    for b in data.iter().take(PAYLOAD_PREVIEW) {
+        let _ = write!(out, "{:02x}", b);
+    }
+    if data.len() > PAYLOAD_PREVIEW {
+        let _ = write!(out, "... ({} more bytes)", data.len() - PAYLOAD_PREVIEW);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use idscp_core::messages::idscpv2_messages::{IdscpClose, IdscpClose_CloseCause, IdscpData};
+
+    fn encode(msg: &IdscpMessage) -> Vec<u8> {
+        let mut raw = Vec::new();
+        msg.write_to_vec(&mut raw).unwrap();
+        raw
+    }
+
+    #[test]
+    fn test_frame_splitter() {
+        let mut stream = vec![0, 0, 0, 2, 0xaa, 0xbb, 0, 0, 0, 0, 0, 0, 0, 5, 1];
+        let frames: Vec<Result<&[u8], String>> = FrameSplitter::new(&stream).collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].as_ref().unwrap(), &[0xaa, 0xbb]);
+        assert!(frames[1].as_ref().unwrap().is_empty());
+        assert!(frames[2].is_err());
+
+        stream.truncate(2);
+        let frames: Vec<Result<&[u8], String>> = FrameSplitter::new(&stream).collect();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_err());
+    }
+
+    #[test]
+    fn test_dissect_messages() {
+        let mut close = IdscpClose::new();
+        close.cause_code = IdscpClose_CloseCause::NO_VALID_DAT;
+        close.cause_msg = "DAT expired".to_string();
+        let mut msg = IdscpMessage::new();
+        msg.set_idscpClose(close);
+        let out = dissect_frame(&encode(&msg), false);
+        assert!(out.contains("NO_VALID_DAT (3)"));
+        assert!(out.contains("DAT expired"));
+
+        let mut data = IdscpData::new();
+        data.data = bytes::Bytes::from(vec![1u8; 100]);
+        data.alternating_bit = true;
+        let mut msg = IdscpMessage::new();
+        msg.set_idscpData(data);
+        let out = dissect_frame(&encode(&msg), false);
+        assert!(out.contains("size: 100 bytes"));
+        assert!(out.contains("alternating bit: 1"));
+        assert!(!out.contains("0101"));
+        assert!(dissect_frame(&encode(&msg), true).contains("(36 more bytes)"));
+
+        assert!(dissect_frame(&[0xff, 0xff, 0xff], false).starts_with("malformed"));
+    }
+
+    #[test]
+    fn test_decode_jwt() {
+        // {"alg":"none"}.{"sub":"idscp"}
+        let (header, payload) = decode_jwt("eyJhbGciOiJub25lIn0.eyJzdWIiOiJpZHNjcCJ9.").unwrap();
+        assert_eq!(header, r#"{"alg":"none"}"#);
+        assert_eq!(payload, r#"{"sub":"idscp"}"#);
+        assert!(decode_jwt("INVALID_DAT").is_none());
+        assert!(decode_jwt("%%%.%%%.sig").is_none());
+    }
+}
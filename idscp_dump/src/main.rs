@@ -0,0 +1,150 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod dissector;
+
+use clap::{App, Arg};
+use dissector::{dissect_frame, FrameSplitter};
+use idscp_core::recording::{RecordDirection, SessionRecordReader};
+use std::io::{Cursor, Read};
+use std::time::UNIX_EPOCH;
+
+const INPUT_ARG: &str = "input";
+const FORMAT_ARG: &str = "format";
+const PAYLOAD_ARG: &str = "show-payload";
+
+const RECORDING_MAGIC: &[u8] = b"IDSCPREC";
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|l| format!("    {}", l))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn dump_frames(data: &[u8], show_payload: bool) -> bool {
+    let mut ok = true;
+    for (i, frame) in FrameSplitter::new(data).enumerate() {
+        match frame {
+            Err(e) => {
+                println!("#{}: {}", i, e);
+                ok = false;
+            }
+            Ok(frame) => {
+                println!("#{} ({} bytes)", i, frame.len());
+                println!("{}", indent(&dissect_frame(frame, show_payload)));
+            }
+        }
+    }
+    ok
+}
+
+fn dump_recording(data: Vec<u8>, show_payload: bool) -> bool {
+    let reader = match SessionRecordReader::new(Cursor::new(data)) {
+        Err(e) => {
+            println!("Cannot read session recording: {}", e);
+            return false;
+        }
+        Ok(r) => r,
+    };
+    println!(
+        "Session recording, peer: {:?}",
+        reader.peer_certificate().subject_name()
+    );
+
+    for (i, record) in reader.enumerate() {
+        let record = match record {
+            Err(e) => {
+                println!("#{}: malformed record: {}", i, e);
+                return false;
+            }
+            Ok(r) => r,
+        };
+        let direction = match record.direction {
+            RecordDirection::Sent => "sent",
+            RecordDirection::Received => "received",
+        };
+        let timestamp = record
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        println!(
+            "#{} {}.{:06} {} ({} bytes{})",
+            i,
+            timestamp.as_secs(),
+            timestamp.subsec_micros(),
+            direction,
+            record.frame.len(),
+            if record.redacted { ", redacted" } else { "" }
+        );
+        if record.frame.is_empty() && record.redacted {
+            println!("    <frame removed>");
+        } else {
+            println!("{}", indent(&dissect_frame(&record.frame, show_payload)));
+        }
+    }
+    true
+}
+
+fn main() {
+    let matches = App::new("IDSCP Dump")
+        .version("v0.1.0")
+        .about("Prints IDSCPv2 frames in human readable form")
+        .arg(
+            Arg::with_name(INPUT_ARG)
+                .help("file with length-prefixed frames or a session recording, stdin if omitted or '-'")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name(FORMAT_ARG)
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["auto", "frames", "recording"])
+                .default_value("auto")
+                .help("input format, 'auto' detects session recordings by their header"),
+        )
+        .arg(
+            Arg::with_name(PAYLOAD_ARG)
+                .long("show-payload")
+                .help("print a hex preview of data and RAT payloads"),
+        )
+        .get_matches();
+
+    let mut data = Vec::new();
+    let read_result = match matches.value_of(INPUT_ARG) {
+        None | Some("-") => std::io::stdin().read_to_end(&mut data),
+        Some(path) => std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut data)),
+    };
+    if let Err(e) = read_result {
+        eprintln!("Cannot read input: {}", e);
+        std::process::exit(2);
+    }
+
+    let show_payload = matches.is_present(PAYLOAD_ARG);
+    let is_recording = match matches.value_of(FORMAT_ARG).unwrap() {
+        "frames" => false,
+        "recording" => true,
+        _ => data.starts_with(RECORDING_MAGIC),
+    };
+
+    let ok = if is_recording {
+        dump_recording(data, show_payload)
+    } else {
+        dump_frames(&data, show_payload)
+    };
+
+    if !ok {
+        std::process::exit(1);
+    }
+}
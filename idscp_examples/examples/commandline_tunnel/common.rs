@@ -37,6 +37,9 @@ pub(super) fn connection_handler(connection: Idscp2Connection) {
                 IdscpEvent::Message(msg) => {
                     println!("received {:?}", String::from_utf8_lossy(&msg))
                 }
+                IdscpEvent::MessageDiscarded(msg) => {
+                    println!("not sent {:?}", String::from_utf8_lossy(&msg))
+                }
                 IdscpEvent::ConnectionClosed => {
                     println!("Connection closed. Exiting");
                     break;
@@ -54,3 +57,37 @@ pub(super) fn connection_handler(connection: Idscp2Connection) {
         }
     }
 }
+
+#[cfg(feature = "tpm_rat")]
+pub(super) mod tpm_rat {
+    use idscp_default_drivers::rat_drivers::policy::{ReferencePolicy, ReferenceValue};
+    use idscp_default_drivers::rat_drivers::tpm_rat::prover::{TpmProver, TpmProverConfig};
+    use idscp_default_drivers::rat_drivers::tpm_rat::verifier::{
+        pcr_name, TpmVerifier, TpmVerifierConfig,
+    };
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    pub(crate) fn setup_tpm_prover() -> Arc<TpmProver> {
+        Arc::new(TpmProver::new(TpmProverConfig::default()))
+    }
+
+    // the peer must use the same simulator or its AK must be given in TPM_AK_PEM
+    pub(crate) fn setup_tpm_verifier() -> Arc<TpmVerifier> {
+        let ak_file = std::env::var("TPM_AK_PEM").unwrap_or_else(|_| "ak.pem".to_string());
+        // pcr 0 of a simulator is not extended by any firmware
+        let mut reference_values = BTreeMap::new();
+        let reference = ReferenceValue {
+            digest: vec![0u8; 32],
+            ..Default::default()
+        };
+        reference_values.insert(pcr_name(0), vec![reference]);
+        let config = TpmVerifierConfig {
+            trusted_ak_files: vec![PathBuf::from(ak_file)],
+            pcrs: vec![0],
+            policy: Arc::new(ReferencePolicy::from_reference_values(reference_values)),
+        };
+        Arc::new(TpmVerifier::new(config).expect("Cannot create tpm verifier"))
+    }
+}
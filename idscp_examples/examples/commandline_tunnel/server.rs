@@ -63,15 +63,18 @@ fn setup_idscp_listener() -> (OpensslServer, OpensslAddr, Idscp2Configuration) {
             .map(|v| v.to_string())
             .collect(),
         rat_timeout: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
     };
 
     let config = Idscp2Configuration {
         rat_config,
         daps: Arc::new(daps_client),
+        dat_verifier: None,
         prover_registry,
         verifier_registry,
         handshake_timeout: Duration::from_secs(5),
         ack_timeout: Duration::from_millis(1000),
+        fsm_observer: None,
     };
 
     let key = PathBuf::from(format!(
@@ -0,0 +1,87 @@
+// Copyright (c) 2020, Fraunhofer AISEC. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use idscp_core::api::idscp_configuration::{AttestationConfig, Idscp2Configuration};
+use idscp_core::drivers::rat_driver::RatRegistry;
+use idscp_core::recording::replay::replay_session;
+use idscp_core::recording::SessionRecordReader;
+
+use idscp_default_drivers::daps_drivers::null_daps::NullDaps;
+use idscp_default_drivers::rat_drivers::null_rat::{NullRatProver, NullRatVerifier};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// replays a recorded session into an fsm that uses the same drivers as the commandline tunnel
+fn main() {
+    env_logger::init();
+
+    let path = match std::env::args().nth(1) {
+        None => {
+            println!("Usage: session_replay <recording> [--realtime]");
+            return;
+        }
+        Some(p) => PathBuf::from(p),
+    };
+    let realtime = std::env::args().any(|a| a == "--realtime");
+
+    let mut prover_registry = RatRegistry::new();
+    let mut verifier_registry = RatRegistry::new();
+    prover_registry.register_driver(Arc::new(NullRatProver {}));
+    verifier_registry.register_driver(Arc::new(NullRatVerifier {}));
+
+    let rat_config = AttestationConfig {
+        supported_attestation_suite: prover_registry
+            .get_all_driver_ids()
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+        expected_attestation_suite: verifier_registry
+            .get_all_driver_ids()
+            .iter()
+            .map(|v| v.to_string())
+            .collect(),
+        rat_timeout: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
+    };
+
+    let config = Idscp2Configuration {
+        rat_config,
+        daps: Arc::new(NullDaps {}),
+        dat_verifier: None,
+        prover_registry,
+        verifier_registry,
+        handshake_timeout: Duration::from_secs(5),
+        ack_timeout: Duration::from_millis(1000),
+        fsm_observer: None,
+    };
+
+    let recording = SessionRecordReader::open(&path).expect("Cannot open recording");
+    let transitions = replay_session(recording, &config, realtime).expect("Replay failed");
+
+    for t in transitions {
+        let message = match &t.message {
+            None => "<undecodable frame>".to_string(),
+            Some(m) => format!("{:?}", m),
+        };
+        println!("{} -> {} on {}", t.from_state, t.to_state, message);
+        if let Err(e) = &t.result {
+            println!("    error: {}", e);
+        }
+        for sent in t.sent {
+            println!("    sent: {:?}", sent);
+        }
+    }
+}
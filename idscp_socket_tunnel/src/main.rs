@@ -15,6 +15,9 @@
 use idscp_core::api::idscp_configuration::Idscp2Configuration;
 
 use idscp_core::api::idscp_configuration::AttestationConfig;
+use idscp_core::api::idscp_fsm_observer::FsmObserver;
+#[cfg(feature = "tracing")]
+use idscp_core::api::idscp_fsm_observer::TracingFsmObserver;
 use idscp_core::drivers::rat_driver::RatRegistry;
 
 use idscp_default_drivers::daps_drivers::null_daps::NullDaps;
@@ -36,6 +39,7 @@ use std::thread;
 use clap::{App, Arg};
 use idscp_core::api::idscp_connection::{Idscp2Connection, IdscpEvent};
 use idscp_core::api::idscp_server::Idscp2Server;
+use idscp_core::recording::{RecordingClient, RecordingServer, SessionRecordingConfig};
 use idscp_default_drivers::secure_channels::openssl::server::OpensslServer;
 
 use std::io::Read;
@@ -66,15 +70,24 @@ fn default_config() -> Idscp2Configuration {
             .map(|v| v.to_string())
             .collect(),
         rat_timeout: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
     };
 
+    #[cfg(feature = "tracing")]
+    let fsm_observer: Option<Arc<dyn FsmObserver + Send + Sync>> =
+        Some(Arc::new(TracingFsmObserver {}));
+    #[cfg(not(feature = "tracing"))]
+    let fsm_observer: Option<Arc<dyn FsmObserver + Send + Sync>> = None;
+
     Idscp2Configuration {
         rat_config,
         daps: Arc::new(daps),
+        dat_verifier: None,
         prover_registry,
         verifier_registry,
         handshake_timeout: Duration::from_secs(5),
         ack_timeout: Duration::from_millis(1000),
+        fsm_observer,
     }
 }
 
@@ -150,6 +163,7 @@ fn handle_connection(connection: Idscp2Connection, connnection_id: usize) {
             match event {
                 IdscpEvent::ConnectionClosed => break,
                 IdscpEvent::Message(data) => receive_tx.send(data).unwrap(),
+                IdscpEvent::MessageDiscarded(data) => println!("discarded {} bytes", data.len()),
             }
         }
 
@@ -172,6 +186,8 @@ const TRUSTED_CA_ARG: &str = "trusted-ca";
 const HOST_ARG: &str = "host";
 const PORT_ARG: &str = "port";
 const DOMAIN_ARG: &str = "domain";
+const RECORD_ARG: &str = "record";
+const REDACT_DATA_ARG: &str = "redact-data";
 
 fn main() {
     env_logger::init();
@@ -230,6 +246,18 @@ fn main() {
                 .takes_value(true)
                 .help("domain name of peer/self")
                 .required(true),
+        )
+        .arg(
+            Arg::with_name(RECORD_ARG)
+                .long("record")
+                .takes_value(true)
+                .help("directory to record all IDSCP sessions to"),
+        )
+        .arg(
+            Arg::with_name(REDACT_DATA_ARG)
+                .long("redact-data")
+                .requires(RECORD_ARG)
+                .help("remove the payload of IdscpData messages from recordings"),
         );
     let matches = clap.get_matches();
 
@@ -257,6 +285,13 @@ fn main() {
 
     let config = default_config();
 
+    let recording = matches
+        .value_of(RECORD_ARG)
+        .map(|dir| SessionRecordingConfig {
+            directory: PathBuf::from(dir),
+            redact_data: matches.is_present(REDACT_DATA_ARG),
+        });
+
     match mode {
         Mode::Connector => {
             println!("setting up idscp connection");
@@ -267,7 +302,16 @@ fn main() {
             };
 
             println!("connecting to {}:{}", addr.hostname, addr.port);
-            let connection = idscp_core::connect(secure_channel_client, &addr, &config).unwrap();
+            let connection = match recording {
+                None => idscp_core::connect(secure_channel_client, &addr, &config).unwrap(),
+                Some(recording_config) => {
+                    let recording_client = RecordingClient {
+                        inner: secure_channel_client,
+                        config: recording_config,
+                    };
+                    idscp_core::connect(recording_client, &addr, &config).unwrap()
+                }
+            };
             assert!(connection.is_connected());
             println!("connected.");
             handle_connection(connection, 0);
@@ -282,10 +326,24 @@ fn main() {
             );
 
             println!("Start listening at {}:{}", addr.hostname, addr.port);
-            let server = Idscp2Server::listen(secure_channel_server, addr, &config).unwrap();
-
-            for (counter, connection) in server.incoming_connections().enumerate() {
-                thread::spawn(move || handle_connection(connection, counter));
+            match recording {
+                None => {
+                    let server =
+                        Idscp2Server::listen(secure_channel_server, addr, &config).unwrap();
+                    for (counter, connection) in server.incoming_connections().enumerate() {
+                        thread::spawn(move || handle_connection(connection, counter));
+                    }
+                }
+                Some(recording_config) => {
+                    let recording_server = RecordingServer {
+                        inner: secure_channel_server,
+                        config: recording_config,
+                    };
+                    let server = Idscp2Server::listen(recording_server, addr, &config).unwrap();
+                    for (counter, connection) in server.incoming_connections().enumerate() {
+                        thread::spawn(move || handle_connection(connection, counter));
+                    }
+                }
             }
         }
     }
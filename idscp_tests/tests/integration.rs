@@ -15,17 +15,26 @@
 use idscp_core::api::idscp_configuration::AttestationConfig;
 use idscp_core::api::idscp_configuration::Idscp2Configuration;
 use idscp_core::api::idscp_connection::{Idscp2Connection, IdscpEvent};
+use idscp_core::api::idscp_fsm_observer::{FsmObserver, FsmTransition};
+use idscp_core::api::IdscpError;
+use idscp_core::recording::replay::replay_session;
+use idscp_core::recording::{RecordingClient, SessionRecordReader, SessionRecordingConfig};
 
+use idscp_core::drivers::daps_driver::DatClaims;
 use idscp_core::drivers::rat_driver::RatRegistry;
+use idscp_default_drivers::daps_drivers::default_daps::{
+    connector_id, DefaultDaps, DefaultDapsConfig,
+};
+use idscp_default_drivers::daps_drivers::mock_daps::{MockDaps, MockDapsConfig};
 use idscp_default_drivers::daps_drivers::null_daps::NullDaps;
 use idscp_default_drivers::rat_drivers::null_rat::{NullRatProver, NullRatVerifier};
 use idscp_default_drivers::secure_channels::openssl::client::OpensslClient;
 use idscp_default_drivers::secure_channels::openssl::server::OpensslServer;
 use idscp_default_drivers::secure_channels::openssl::OpensslAddr;
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use idscp_core::api::idscp_server::Idscp2Server;
 
@@ -34,6 +43,8 @@ use std::path::PathBuf;
 use std::sync::mpsc::{channel, Sender};
 use std::thread;
 
+use openssl::x509::X509;
+
 mod common;
 
 #[test]
@@ -169,6 +180,357 @@ fn server_to_client_repeat_rat() {
     }
 }
 
+struct CollectingObserver {
+    transitions: Mutex<Vec<FsmTransition>>,
+    changed: Condvar,
+}
+
+impl CollectingObserver {
+    fn new() -> CollectingObserver {
+        CollectingObserver {
+            transitions: Mutex::new(Vec::new()),
+            changed: Condvar::new(),
+        }
+    }
+
+    // blocks until the condition holds for the transitions so far, returns false on timeout
+    fn wait_for<F>(&self, timeout: Duration, condition: F) -> bool
+    where
+        F: Fn(&[FsmTransition]) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut transitions = self.transitions.lock().unwrap();
+        while !condition(&transitions) {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            transitions = self
+                .changed
+                .wait_timeout(transitions, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+}
+
+impl FsmObserver for CollectingObserver {
+    fn on_transition(&self, transition: &FsmTransition) {
+        self.transitions.lock().unwrap().push(transition.clone());
+        self.changed.notify_all();
+    }
+}
+
+// number of successful transitions triggered by the given event
+fn count_transitions(transitions: &[FsmTransition], event: &str) -> usize {
+    transitions
+        .iter()
+        .filter(|t| t.event == event && t.outcome.is_ok())
+        .count()
+}
+
+#[test]
+fn fsm_observer_transitions() {
+    common::setup_logging();
+
+    let addr = OpensslAddr {
+        port: 4567,
+        hostname: "127.0.0.1".to_string(),
+        domain: "idscp-test.de".to_string(),
+    };
+    let (secure_channel_server, config_server) = setup_idscp_listener();
+    let server_addr = addr.clone();
+    thread::spawn(move || {
+        start_listener(secure_channel_server, server_addr, config_server);
+    });
+
+    let observer = Arc::new(CollectingObserver::new());
+    let (secure_channel_client, mut client_config) = setup_idscp_connection();
+    client_config.fsm_observer = Some(Arc::clone(&observer) as Arc<dyn FsmObserver + Send + Sync>);
+    sleep(Duration::from_millis(100));
+
+    let mut connection = idscp_core::connect(secure_channel_client, &addr, &client_config).unwrap();
+    for _ in 0..10 {
+        match connection.incoming_messages().next().unwrap() {
+            IdscpEvent::ConnectionClosed => panic!("Connection has been closed early"),
+            IdscpEvent::MessageDiscarded(_) => panic!("Message has been discarded"),
+            IdscpEvent::Message(_) => {}
+        }
+    }
+    connection
+        .blocking_send(
+            b"all 10 messages received".to_vec(),
+            Duration::from_millis(3000),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+    connection.close().unwrap();
+
+    let transitions = observer.transitions.lock().unwrap();
+    assert!(transitions
+        .iter()
+        .all(|t| t.connection_id == connection.id()));
+
+    let first = transitions.first().unwrap();
+    assert_eq!(first.old_state, "Closed(Unlocked)");
+    assert_eq!(first.event, "User(StartHandshake)");
+    assert_eq!(first.new_state, "WaitForHello");
+    assert!(transitions.iter().any(|t| t.new_state == "Established"));
+    assert_eq!(
+        transitions
+            .iter()
+            .filter(|t| t.event == "Sc(Data)" && t.outcome.is_ok())
+            .count(),
+        10
+    );
+
+    let last = transitions.last().unwrap();
+    assert_eq!(last.event, "User(Stop)");
+    assert_eq!(last.new_state, "Closed(Locked)");
+}
+
+#[test]
+fn dat_renewal_with_mock_daps() {
+    common::setup_logging();
+
+    let addr = OpensslAddr {
+        port: 5678,
+        hostname: "127.0.0.1".to_string(),
+        domain: "idscp-test.de".to_string(),
+    };
+    let client_cert = X509::from_pem(&std::fs::read(test_pki("test_client.crt")).unwrap()).unwrap();
+    let server_cert = X509::from_pem(&std::fs::read(test_pki("test_server.crt")).unwrap()).unwrap();
+    let client_id = connector_id(&client_cert).unwrap();
+    let server_id = connector_id(&server_cert).unwrap();
+    let mock = MockDaps::start(MockDapsConfig {
+        token_lifetime: Duration::from_secs(3),
+        clients: vec![client_cert, server_cert],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let (secure_channel_server, mut config_server) = setup_idscp_listener();
+    config_server.daps = mock_daps_driver(&mock, "test_server");
+    let server_addr = addr.clone();
+    let server = thread::spawn(move || {
+        // only the test client is admitted
+        let admission =
+            Arc::new(move |claims: &DatClaims| claims.subject == Some(client_id.clone()));
+        let listener = Idscp2Server::listen_with_admission(
+            secure_channel_server,
+            server_addr,
+            &config_server,
+            admission,
+        )
+        .unwrap();
+        let connection = listener.incoming_connections().next().unwrap();
+        // echo the first message, which is sent once the dats of both peers have been renewed
+        match connection.incoming_messages().next().unwrap() {
+            IdscpEvent::Message(msg) => {
+                assert!(connection.peer_dat_claims().is_some());
+                send_between_renewals(&connection, msg).unwrap();
+            }
+            IdscpEvent::MessageDiscarded(_) => panic!("Message has been discarded at the server"),
+            IdscpEvent::ConnectionClosed => panic!("Connection has been closed at the server"),
+        }
+        // the client closes the connection
+        for event in connection.incoming_messages() {
+            if let IdscpEvent::ConnectionClosed = event {
+                break;
+            }
+        }
+    });
+
+    let observer = Arc::new(CollectingObserver::new());
+    let (secure_channel_client, mut client_config) = setup_idscp_connection();
+    client_config.daps = mock_daps_driver(&mock, "test_client");
+    client_config.fsm_observer = Some(Arc::clone(&observer) as Arc<dyn FsmObserver + Send + Sync>);
+    sleep(Duration::from_millis(100));
+
+    let mut connection = idscp_core::connect(secure_channel_client, &addr, &client_config).unwrap();
+    assert!(connection.is_connected());
+    let handshake_claims = connection.peer_dat_claims().unwrap();
+    assert_eq!(handshake_claims.subject, Some(server_id.clone()));
+
+    // each dat expires after 3 seconds. The server dat expires at the client, which requests a
+    // new one via IdscpDatExpired. The client dat expires at the server, the client renews it via
+    // the mock daps
+    let renewed = observer.wait_for(Duration::from_secs(30), |transitions| {
+        ["DatTimeout", "Sc(DatExpired)", "Daps(Dat)", "Sc(Dat)"]
+            .iter()
+            .all(|event| count_transitions(transitions, event) >= 2)
+    });
+    let echo = send_between_renewals(&connection, b"renewed".to_vec())
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|_| {
+            connection
+                .recv_incoming_msg_with_timeout(Duration::from_secs(5))
+                .map_err(|e| format!("{:?}", e))
+        });
+    let renewed_claims = connection.peer_dat_claims();
+    let closed = connection.close();
+    // failed assertions of the server are reported here
+    server.join().expect("Server failed");
+
+    assert!(renewed, "Dats have not been renewed in time");
+    match echo.unwrap() {
+        IdscpEvent::ConnectionClosed => panic!("Connection has been closed"),
+        IdscpEvent::MessageDiscarded(_) => panic!("Message has been discarded"),
+        IdscpEvent::Message(msg) => assert_eq!(msg, b"renewed".to_vec()),
+    }
+    // the claims of the renewed server dat are available
+    let renewed_claims = renewed_claims.unwrap();
+    assert_eq!(renewed_claims.subject, Some(server_id));
+    assert!(renewed_claims.issued_at > handshake_claims.issued_at);
+    closed.unwrap();
+    // handshake dats of both peers and at least two renewals of each
+    assert!(mock.issued_tokens() >= 6);
+}
+
+#[test]
+fn replay_recorded_session() {
+    common::setup_logging();
+
+    let addr = OpensslAddr {
+        port: 6789,
+        hostname: "127.0.0.1".to_string(),
+        domain: "idscp-test.de".to_string(),
+    };
+    let (secure_channel_server, config_server) = setup_idscp_listener();
+    let server_addr = addr.clone();
+    let server = thread::spawn(move || {
+        let listener =
+            Idscp2Server::listen(secure_channel_server, server_addr, &config_server).unwrap();
+        let connection = listener.incoming_connections().next().unwrap();
+        for i in 0..3u32 {
+            connection
+                .blocking_send(
+                    format!("Ping {}", i + 1).into_bytes(),
+                    Duration::from_millis(3000),
+                    Some(Duration::from_millis(100)),
+                )
+                .unwrap();
+        }
+        // the client closes the connection
+        for event in connection.incoming_messages() {
+            if let IdscpEvent::ConnectionClosed = event {
+                break;
+            }
+        }
+    });
+
+    let directory = std::env::temp_dir().join(format!("idscp_replay_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let observer = Arc::new(CollectingObserver::new());
+    let (secure_channel_client, mut client_config) = setup_idscp_connection();
+    client_config.fsm_observer = Some(Arc::clone(&observer) as Arc<dyn FsmObserver + Send + Sync>);
+    let recording_client = RecordingClient {
+        inner: secure_channel_client,
+        config: SessionRecordingConfig {
+            directory: directory.clone(),
+            redact_data: false,
+        },
+    };
+    sleep(Duration::from_millis(100));
+
+    // the client only receives, such that all of its transitions are caused by the server
+    let mut connection = idscp_core::connect(recording_client, &addr, &client_config).unwrap();
+    for _ in 0..3 {
+        match connection.incoming_messages().next().unwrap() {
+            IdscpEvent::ConnectionClosed => panic!("Connection has been closed early"),
+            IdscpEvent::MessageDiscarded(_) => panic!("Message has been discarded"),
+            IdscpEvent::Message(_) => {}
+        }
+    }
+    connection.close().unwrap();
+    server.join().expect("Server failed");
+
+    let recordings: Vec<PathBuf> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(recordings.len(), 1);
+    client_config.fsm_observer = None;
+    // realtime, such that the rat drivers finish in between as in the recorded session
+    let replayed = replay_session(
+        SessionRecordReader::open(&recordings[0]).unwrap(),
+        &client_config,
+        true,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    let recorded: Vec<(String, String)> = observer
+        .transitions
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|t| t.event.starts_with("Sc("))
+        .map(|t| (t.old_state.to_string(), t.new_state.to_string()))
+        .collect();
+    let replayed: Vec<(String, String)> = replayed
+        .iter()
+        .map(|t| {
+            assert!(t.result.is_ok(), "Replay failed: {:?}", t.result);
+            (t.from_state.clone(), t.to_state.clone())
+        })
+        .collect();
+    assert_eq!(replayed.len(), recorded.len());
+    assert_eq!(replayed[0], recorded[0]);
+    // the rat messages of the server interleave with the results of the own rat drivers, which
+    // depends on the scheduling of the driver threads. Once established, the replay must match
+    let established = |transitions: &[(String, String)]| -> Vec<(String, String)> {
+        transitions
+            .iter()
+            .skip_while(|(from, _)| from != "Established")
+            .cloned()
+            .collect()
+    };
+    assert_eq!(established(&replayed).len(), 3);
+    assert_eq!(established(&replayed), established(&recorded));
+}
+
+// data is rejected while the dats are renewed and the peers are re-attested, which happens every
+// few seconds in dat_renewal_with_mock_daps
+fn send_between_renewals(connection: &Idscp2Connection, msg: Vec<u8>) -> Result<(), IdscpError> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match connection.blocking_send(
+            msg.clone(),
+            Duration::from_millis(3000),
+            Some(Duration::from_millis(100)),
+        ) {
+            Err(IdscpError::ConnectionTemporaryNotAvailable) if Instant::now() < deadline => {
+                sleep(Duration::from_millis(100))
+            }
+            result => return result,
+        }
+    }
+}
+
+fn test_pki(file: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/../test_pki/resources/openssl/out/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        file
+    ))
+}
+
+// DefaultDaps of the test_pki identity, requesting dats from the mock daps
+fn mock_daps_driver(mock: &MockDaps, identity: &str) -> Arc<DefaultDaps> {
+    let daps = DefaultDaps::new(DefaultDapsConfig {
+        token_url: mock.token_url(),
+        key_file_path: test_pki(&format!("{}.key", identity)),
+        cert_file_path: test_pki(&format!("{}.crt", identity)),
+        verifier: mock.verifier_config(),
+        renewal_margin: Duration::from_secs(2),
+        ..Default::default()
+    })
+    .unwrap();
+    Arc::new(daps)
+}
+
 fn start_listener(
     secure_channel_server: OpensslServer,
     addr: OpensslAddr,
@@ -195,7 +557,7 @@ fn start_listener(
 
             // block until peer acknowledges 10 messages
             match connection.incoming_messages().next().unwrap() {
-                IdscpEvent::ConnectionClosed => {
+                IdscpEvent::ConnectionClosed | IdscpEvent::MessageDiscarded(_) => {
                     log::error!("expect acknowledgment message");
                     assert!(false);
                 }
@@ -252,7 +614,7 @@ fn start_listener_with_rerat(
 
             // block until peer acknowledges 10 messages
             match connection.incoming_messages().next().unwrap() {
-                IdscpEvent::ConnectionClosed => {
+                IdscpEvent::ConnectionClosed | IdscpEvent::MessageDiscarded(_) => {
                     log::error!("expect acknowledgment message");
                     assert!(false);
                 }
@@ -284,6 +646,11 @@ fn connect(
                 break;
             }
 
+            IdscpEvent::MessageDiscarded(_) => {
+                log::error!("Message has been discarded");
+                break;
+            }
+
             IdscpEvent::Message(msg) => {
                 counter += 1;
                 log::info!("client received {}th message: {:?}", counter, msg);
@@ -336,15 +703,18 @@ fn setup_idscp_connection() -> (OpensslClient, Idscp2Configuration) {
             .map(|v| v.to_string())
             .collect(),
         rat_timeout: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
     };
 
     let config = Idscp2Configuration {
         rat_config,
         daps: Arc::new(daps_client),
+        dat_verifier: None,
         prover_registry,
         verifier_registry,
         handshake_timeout: Duration::from_secs(5),
         ack_timeout: Duration::from_millis(1000),
+        fsm_observer: None,
     };
 
     let key = PathBuf::from(format!(
@@ -395,15 +765,18 @@ fn setup_idscp_listener() -> (OpensslServer, Idscp2Configuration) {
             .map(|v| v.to_string())
             .collect(),
         rat_timeout: Duration::from_secs(24 * 60 * 60),
+        ..Default::default()
     };
 
     let config = Idscp2Configuration {
         rat_config,
         daps: Arc::new(daps_client),
+        dat_verifier: None,
         prover_registry,
         verifier_registry,
         handshake_timeout: Duration::from_secs(5),
         ack_timeout: Duration::from_millis(1000),
+        fsm_observer: None,
     };
 
     let key = PathBuf::from(format!(